@@ -0,0 +1,33 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use proto_rw::types::{BE, LE};
+use proto_rw_fuzz::decode;
+
+// The first byte picks the type
+fuzz_target!(|data: &[u8]| {
+    let _ = match data.first().copied().unwrap_or_default() % 22 {
+        0 => decode::<u8>(data).map(drop),
+        1 => decode::<i8>(data).map(drop),
+        2 => decode::<LE<u16>>(data).map(drop),
+        3 => decode::<LE<u32>>(data).map(drop),
+        4 => decode::<LE<u64>>(data).map(drop),
+        5 => decode::<LE<u128>>(data).map(drop),
+        6 => decode::<LE<i16>>(data).map(drop),
+        7 => decode::<LE<i32>>(data).map(drop),
+        8 => decode::<LE<i64>>(data).map(drop),
+        9 => decode::<LE<i128>>(data).map(drop),
+        10 => decode::<LE<f32>>(data).map(drop),
+        11 => decode::<LE<f64>>(data).map(drop),
+        12 => decode::<BE<u16>>(data).map(drop),
+        13 => decode::<BE<u32>>(data).map(drop),
+        14 => decode::<BE<u64>>(data).map(drop),
+        15 => decode::<BE<u128>>(data).map(drop),
+        16 => decode::<BE<i16>>(data).map(drop),
+        17 => decode::<BE<i32>>(data).map(drop),
+        18 => decode::<BE<i64>>(data).map(drop),
+        19 => decode::<BE<i128>>(data).map(drop),
+        20 => decode::<BE<f32>>(data).map(drop),
+        _ => decode::<BE<f64>>(data).map(drop),
+    };
+});
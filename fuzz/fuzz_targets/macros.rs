@@ -0,0 +1,17 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use proto_rw_fuzz::{
+    decode,
+    examples::{AdvancedEnum, ExampleConvert, ExampleEnum, ExampleStruct},
+};
+
+// The first byte picks the type
+fuzz_target!(|data: &[u8]| {
+    let _ = match data.first().copied().unwrap_or_default() % 4 {
+        0 => decode::<ExampleEnum>(data).map(drop),
+        1 => decode::<ExampleConvert>(data).map(drop),
+        2 => decode::<AdvancedEnum>(data).map(drop),
+        _ => decode::<ExampleStruct>(data).map(drop),
+    };
+});
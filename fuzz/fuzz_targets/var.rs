@@ -0,0 +1,19 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use proto_rw::types::Var;
+use proto_rw_fuzz::decode;
+
+// The first byte picks the type
+fuzz_target!(|data: &[u8]| {
+    let _ = match data.first().copied().unwrap_or_default() % 8 {
+        0 => decode::<Var<u16>>(data).map(drop),
+        1 => decode::<Var<u32>>(data).map(drop),
+        2 => decode::<Var<u64>>(data).map(drop),
+        3 => decode::<Var<u128>>(data).map(drop),
+        4 => decode::<Var<i16>>(data).map(drop),
+        5 => decode::<Var<i32>>(data).map(drop),
+        6 => decode::<Var<i64>>(data).map(drop),
+        _ => decode::<Var<i128>>(data).map(drop),
+    };
+});
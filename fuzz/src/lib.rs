@@ -0,0 +1,14 @@
+//! Types decoded by the fuzz targets, which are shared with the tests, and the allocator
+//! `fuzz_decode` checks allocations with.
+
+pub use proto_rw::fuzz::fuzz_decode;
+use proto_rw::{error::ProtoRwError, fuzz::TrackingAllocator, ProtoRw};
+pub use tests::examples;
+
+#[global_allocator]
+static ALLOCATOR: TrackingAllocator = TrackingAllocator;
+
+/// Decodes the input after its first byte as `T`.
+pub fn decode<T: ProtoRw>(data: &[u8]) -> Result<T, ProtoRwError> {
+    fuzz_decode(data.get(1..).unwrap_or_default())
+}
@@ -1,195 +1,547 @@
-use std::collections::VecDeque;
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use syn::{
+    parse::{ParseStream, Parser},
+    Attribute, DataEnum, Expr, ExprLit, ExprUnary, Lit, Pat, Token, Type, UnOp, Variant,
+    Visibility,
+};
 
-use proc_macro2::{Span, TokenStream};
-use quote::quote;
-use syn::{DataEnum, Expr, Type, Visibility};
+use crate::{
+    fields::{build_fields, FieldsQuotes},
+    generate::{build_fields_generator, build_variants_generator, Generator},
+    schema::{build_encoding, build_optional_string, expr_display},
+    util::{
+        build_size_prefixed_quotes, build_type_quotes, build_version_checks, get_size_prefix,
+        get_versions, is_string_type, Defaults, Errors, Props, Versions,
+    },
+};
 
-use crate::util::{build_type_quotes, check_meta, extract_generic_type, get_attrs_meta};
+struct VariantId {
+    pattern: Pat,
+    value: Expr,
+    /// `write = ...` was given, so the value may not match the pattern.
+    explicit_write: bool,
+    versions: Versions,
+}
 
 pub fn build_enum(
     ty: Type,
+    defaults: &Defaults,
     vis: &Visibility,
     ident: &proc_macro2::Ident,
     data: &DataEnum,
-) -> (TokenStream, TokenStream, TokenStream) {
-    let ty_path = match ty {
-        Type::Path(ty_path) => ty_path,
-        _ => panic!("Enum type must be a path"),
-    };
+    generate: bool,
+) -> syn::Result<(
+    TokenStream,
+    TokenStream,
+    TokenStream,
+    TokenStream,
+    Option<Generator>,
+)> {
+    let (tag_type, read_value_func, write_tag_func) =
+        build_type_quotes(&ty, &mut Props::new(defaults), None)?;
+    let tag_schema = build_encoding(&ty, &mut Props::new(defaults))?;
 
-    let segment = ty_path
-        .path
-        .segments
-        .first()
-        .cloned()
-        .expect("No segments found in type path");
-    let seg_ident = segment.ident.clone();
-
-    let (read_value_func, write_value_func) = match seg_ident.to_string().as_str() {
-        "u8" => (
-            quote! { u8::read_proto(buf)? },
-            quote! { u8::write_proto(&(value as u8), buf)?; },
-        ),
-        "LE" | "BE" | "Var" => {
-            let gen_type = extract_generic_type(&segment)
-                .unwrap_or_else(|| panic!("No generic type found for {}", seg_ident));
-            (
-                quote! { #seg_ident::<#gen_type>::read_proto(buf)?.0 },
-                quote! { #seg_ident::<#gen_type>(value).write_proto(buf)?; },
-            )
-        }
-        _ => panic!("Enum type must be a LE, BE, Var, or u8"),
+    // Strings and byte arrays can't be matched against literals directly, so match on a
+    // `&str` or a reference to the array instead
+    let is_string = is_string_type(&ty);
+    let match_value = if is_string {
+        quote! { value.as_str() }
+    } else if matches!(ty, Type::Array(_)) {
+        quote! { value }
+    } else {
+        quote! { *value }
     };
 
+    let mut errors = Errors::default();
+    let variants = data
+        .variants
+        .iter()
+        .filter_map(|v| {
+            let ids = errors.handle(get_variant_ids(v));
+            let versions = errors.handle(get_versions(&v.attrs));
+            Some((v, ids?, versions?))
+        })
+        .collect::<Vec<_>>();
+    errors.finish()?;
+
+    // Variants with versioned ids may share values, so they can't be discriminants
+    let keep_discriminants = variants.iter().all(|(v, ids, versions)| {
+        matches!(v.fields, syn::Fields::Unit)
+            && ids.len() == 1
+            && versions.since.is_none()
+            && versions.until.is_none()
+    });
+
     let mut new_variants = vec![];
+    let mut tag_funcs = vec![];
     let mut read_funcs = vec![];
     let mut write_funcs = vec![];
+    let mut variant_schemas = vec![];
+    let mut variant_generators = vec![];
 
-    for v in &data.variants {
+    let mut errors = Errors::default();
+    for (v, ids, versions) in &variants {
         let v_ident = &v.ident;
-        let v_value = v.discriminant.clone().expect("No variant value found").1;
-        let v_fields = &v.fields;
+        let v_checks = build_version_checks(versions);
+
+        let (Some(fields), Some(size_prefix)) = (
+            errors.handle(build_fields(&v.fields, defaults, ident, generate)),
+            errors.handle(get_size_prefix(&v.attrs)),
+        ) else {
+            continue;
+        };
+        let FieldsQuotes {
+            declare,
+            read,
+            construct,
+            pattern,
+            write,
+            schema,
+            generate: fields_generator,
+            ..
+        } = fields;
+
+        // Only variants with an id in the latest version can be written by `write_proto`
+        let has_latest_id =
+            versions.until.is_none() && ids.iter().any(|id| id.versions.until.is_none());
+        if let (Some(fields), true) = (fields_generator, has_latest_id) {
+            variant_generators.push(build_fields_generator(
+                fields,
+                quote! { Self::#v_ident },
+                construct.clone(),
+            ));
+        }
+
+        let Some(variant_schema) = errors.handle(build_variant_schema(
+            v,
+            ids,
+            versions,
+            size_prefix
+                .as_ref()
+                .map(|size_prefix| &size_prefix.length_type),
+            defaults,
+            schema,
+        )) else {
+            continue;
+        };
+        variant_schemas.push(variant_schema);
+
+        let v_value = &ids[0].value;
+        if keep_discriminants && is_int_literal(v_value) {
+            new_variants.push(quote! { #v_ident = #v_value, });
+        } else {
+            new_variants.push(quote! { #v_ident #declare, });
+        }
 
-        let (new_declare, read_func, write_func) = match v_fields {
-            syn::Fields::Named(fields) => {
-                handle_named_fields(v_ident, v_value, fields, &write_value_func)
+        // The first id available in the context's version is written
+        let mut tag_func = quote! {
+            Err(proto_rw::error::ProtoRwError::Error(format!(
+                "{}::{} has no id in version {}",
+                stringify!(#ident),
+                stringify!(#v_ident),
+                ctx.version
+            )))
+        };
+        for id in ids.iter().rev() {
+            let mut tag_value = build_tag_value(&id.value, is_string);
+            if id.explicit_write && !matches!(id.pattern, Pat::Wild(_)) {
+                // A write value outside the pattern would be read back as another variant
+                let pattern = &id.pattern;
+                tag_value = quote! {
+                    {
+                        let tag = #tag_value;
+                        let value = &tag;
+                        if !matches!(#match_value, #pattern) {
+                            return Err(proto_rw::error::ProtoRwError::Error(format!(
+                                "{}::{} writes id {:?}, which doesn't match its pattern",
+                                stringify!(#ident),
+                                stringify!(#v_ident),
+                                value
+                            )));
+                        }
+                        tag
+                    }
+                };
             }
-            syn::Fields::Unnamed(fields) => {
-                handle_unnamed_fields(v_ident, v_value, fields, &write_value_func)
+            let checks = v_checks
+                .iter()
+                .cloned()
+                .chain(build_version_checks(&id.versions))
+                .collect::<Vec<_>>();
+
+            tag_func = if checks.is_empty() {
+                quote! { Ok(#tag_value) }
+            } else {
+                quote! {
+                    if #(#checks)&&* {
+                        Ok(#tag_value)
+                    } else {
+                        #tag_func
+                    }
+                }
+            };
+        }
+        tag_funcs.push(quote! {
+            Self::#v_ident { .. } => #tag_func,
+        });
+        let (read, write) = match size_prefix {
+            Some(size_prefix) => {
+                let Some((_, read, write)) = errors.handle(build_size_prefixed_quotes(
+                    &size_prefix,
+                    defaults,
+                    &quote! { #ident::#v_ident },
+                    (
+                        quote! {},
+                        quote! {
+                            #read
+                            Self::#v_ident #construct
+                        },
+                        write,
+                    ),
+                )) else {
+                    continue;
+                };
+                (read, write)
             }
-            syn::Fields::Unit => handle_unit(v_ident, v_value, &write_value_func),
+            None => (
+                quote! {
+                    #read
+                    Self::#v_ident #construct
+                },
+                write,
+            ),
         };
 
-        new_variants.push(new_declare);
-        read_funcs.push(read_func);
-        write_funcs.push(write_func);
+        for id in ids {
+            let pattern = &id.pattern;
+            let checks = v_checks
+                .iter()
+                .cloned()
+                .chain(build_version_checks(&id.versions))
+                .collect::<Vec<_>>();
+            let guard = if checks.is_empty() {
+                quote! {}
+            } else {
+                quote! { if #(#checks)&&* }
+            };
+
+            read_funcs.push(quote! {
+                #pattern #guard => {
+                    #read
+                }
+            });
+        }
+        write_funcs.push(quote! {
+            Self::#v_ident #pattern => {
+                #write
+            }
+        });
     }
 
-    (
+    errors.finish()?;
+
+    let generator = if generate {
+        Some(build_variants_generator(ident, variant_generators)?)
+    } else {
+        None
+    };
+
+    Ok((
         quote! {
             #vis enum #ident {
                 #(#new_variants)*
             }
+
+            impl proto_rw::ProtoRwTagged for #ident {
+                type Tag = #tag_type;
+
+                #[allow(unused_variables)]
+                fn tag(
+                    &self,
+                    ctx: &proto_rw::context::Context,
+                ) -> Result<Self::Tag, proto_rw::error::ProtoRwError> {
+                    match self {
+                        #(#tag_funcs)*
+                    }
+                }
+
+                #[allow(unused_variables)]
+                fn read_proto_tagged(
+                    tag: &Self::Tag,
+                    buf: &mut bytes::Bytes,
+                    ctx: &proto_rw::context::Context,
+                ) -> Result<Self, proto_rw::error::ProtoRwError> {
+                    let value = tag;
+                    #[allow(unreachable_patterns)]
+                    let result = match #match_value {
+                        #(#read_funcs)*
+                        _ => Err(proto_rw::error::ProtoRwError::Error(format!(
+                            "Get {:?} while reading {}",
+                            value,
+                            stringify!(#ident)
+                        )))?,
+                    };
+                    Ok(result)
+                }
+
+                #[allow(unused_variables)]
+                fn write_proto_untagged(
+                    &self,
+                    buf: &mut bytes::BytesMut,
+                    ctx: &proto_rw::context::Context,
+                ) -> Result<(), proto_rw::error::ProtoRwError> {
+                    match self {
+                        #(#write_funcs)*
+                    }
+                    Ok(())
+                }
+            }
         },
         quote! {
             {
                 let value = #read_value_func;
-                match value {
-                    #(#read_funcs)*
-                    _ => Err(proto_rw::error::ProtoRwError::Error(format!(
-                        "Get {} while reading {}",
-                        value,
-                        stringify!(#ident)
-                    )))?,
-                }
+                <Self as proto_rw::ProtoRwTagged>::read_proto_tagged(&value, buf, ctx)?
             }
         },
         quote! {
-            match self {
-                #(#write_funcs)*
+            {
+                let value = &proto_rw::ProtoRwTagged::tag(self, ctx)?;
+                #write_tag_func;
             }
+            proto_rw::ProtoRwTagged::write_proto_untagged(self, buf, ctx)?;
         },
-    )
+        quote! {
+            proto_rw::schema::Layout::Enum {
+                tag: #tag_schema,
+                variants: vec![#(#variant_schemas),*],
+            }
+        },
+        generator,
+    ))
 }
 
-fn handle_named_fields(
-    ident: &proc_macro2::Ident,
-    value: Expr,
-    fields: &syn::FieldsNamed,
-    write_value_func: &TokenStream,
-) -> (TokenStream, TokenStream, TokenStream) {
-    let mut new_fields = vec![];
-    let mut read_funcs = vec![];
-    let mut write_funcs = vec![];
-    let mut idents = vec![];
-
-    for f in &fields.named {
-        let f_ident = f.ident.clone().expect("No field ident found");
-        let metas = get_attrs_meta(&f.attrs);
-        let mut props = (VecDeque::new(), VecDeque::new());
-        metas.iter().for_each(|meta| {
-            check_meta(&mut props, meta);
-        });
+fn build_variant_schema(
+    variant: &Variant,
+    ids: &[VariantId],
+    versions: &Versions,
+    size_prefix: Option<&Type>,
+    defaults: &Defaults,
+    fields: TokenStream,
+) -> syn::Result<TokenStream> {
+    let name = variant.ident.to_string();
+    let ids = ids.iter().map(|id| {
+        let pattern = id.pattern.to_token_stream().to_string();
+        let value = expr_display(&id.value);
+        let since = build_optional_string(id.versions.since.as_ref());
+        let until = build_optional_string(id.versions.until.as_ref());
+        quote! {
+            proto_rw::schema::VariantId {
+                pattern: String::from(#pattern),
+                value: String::from(#value),
+                since: #since,
+                until: #until,
+            }
+        }
+    });
+    let since = build_optional_string(versions.since.as_ref());
+    let until = build_optional_string(versions.until.as_ref());
+    let size_prefix = match size_prefix {
+        Some(length_type) => {
+            let length = build_encoding(length_type, &mut Props::new(defaults))?;
+            quote! { Some(#length) }
+        }
+        None => quote! { None },
+    };
 
-        let ty = &f.ty;
-        let (new_field, read_func, write_func) = build_type_quotes(ty, &mut props, None);
+    Ok(quote! {
+        proto_rw::schema::Variant {
+            name: String::from(#name),
+            ids: vec![#(#ids),*],
+            since: #since,
+            until: #until,
+            size_prefix: #size_prefix,
+            fields: #fields,
+        }
+    })
+}
 
-        new_fields.push(quote! { #f_ident: #new_field, });
-        read_funcs.push(quote! { #f_ident: #read_func, });
-        write_funcs.push(quote! {
-            let value = #f_ident;
-            #write_func;
-        });
-        idents.push(f_ident);
+fn get_variant_ids(variant: &Variant) -> syn::Result<Vec<VariantId>> {
+    let id_attrs = variant
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("id"))
+        .collect::<Vec<_>>();
+
+    if id_attrs.is_empty() {
+        let value = variant
+            .discriminant
+            .clone()
+            .ok_or_else(|| {
+                syn::Error::new_spanned(
+                    &variant.ident,
+                    format!(
+                        "No variant value found for {}. Add a discriminant or #[id(...)]",
+                        variant.ident
+                    ),
+                )
+            })?
+            .1;
+        let pattern = Pat::parse_single
+            .parse2(value.to_token_stream())
+            .map_err(|error| {
+                syn::Error::new(
+                    error.span(),
+                    format!("Variant value of {} is not a pattern", variant.ident),
+                )
+            })?;
+        check_binding(&pattern, variant)?;
+        return Ok(vec![VariantId {
+            pattern,
+            value,
+            explicit_write: false,
+            versions: Versions::default(),
+        }]);
     }
 
-    (
-        quote! { #ident { #(#new_fields)* }, },
-        quote! { #value => Self::#ident { #(#read_funcs)* }, },
-        quote! { Self::#ident { #(#idents),* } => {
-                let value = #value;
-                #write_value_func
-                #(#write_funcs)*
-            }
-        },
-    )
+    if let Some((eq, _)) = &variant.discriminant {
+        return Err(syn::Error::new_spanned(
+            eq,
+            format!(
+                "Variant {} has both a discriminant and an id attribute",
+                variant.ident
+            ),
+        ));
+    }
+
+    let mut errors = Errors::default();
+    let ids = id_attrs
+        .into_iter()
+        .filter_map(|id_attr| errors.handle(parse_variant_id(variant, id_attr)))
+        .collect();
+    errors.finish()?;
+
+    Ok(ids)
 }
 
-fn handle_unnamed_fields(
-    ident: &proc_macro2::Ident,
-    value: Expr,
-    fields: &syn::FieldsUnnamed,
-    write_value_func: &TokenStream,
-) -> (TokenStream, TokenStream, TokenStream) {
-    let mut new_fields = vec![];
-    let mut read_funcs = vec![];
-    let mut write_funcs = vec![];
-    let mut indices = vec![];
-
-    for (i, f) in fields.unnamed.iter().enumerate() {
-        let f_index = proc_macro2::Ident::new(&format!("index_{}", i), Span::call_site());
-        let metas = get_attrs_meta(&f.attrs);
-        let mut props = (VecDeque::new(), VecDeque::new());
-        metas.iter().for_each(|meta| {
-            check_meta(&mut props, meta);
-        });
+fn parse_variant_id(variant: &Variant, id_attr: &Attribute) -> syn::Result<VariantId> {
+    let (pattern, write_value, versions) = id_attr.parse_args_with(|input: ParseStream| {
+        let pattern = Pat::parse_multi_with_leading_vert(input)?;
+        let mut write_value = None;
+        let mut versions = Versions::default();
 
-        let ty = &f.ty;
-        let (new_field, read_func, write_func) = build_type_quotes(ty, &mut props, None);
+        while input.parse::<Token![,]>().is_ok() && !input.is_empty() {
+            let key = input.parse::<syn::Ident>()?;
+            input.parse::<Token![=]>()?;
+            match key.to_string().as_str() {
+                "write" => write_value = Some(input.parse::<Expr>()?),
+                "since" => versions.since = Some(input.parse::<Expr>()?),
+                "until" => versions.until = Some(input.parse::<Expr>()?),
+                _ => {
+                    return Err(syn::Error::new(
+                        key.span(),
+                        "Expected write, since or until",
+                    ))
+                }
+            }
+        }
 
-        new_fields.push(quote! { #new_field, });
-        read_funcs.push(quote! { #read_func, });
-        write_funcs.push(quote! {
-            let value = #f_index;
-            #write_func;
-        });
-        indices.push(f_index);
-    }
+        if !input.is_empty() {
+            return Err(input.error("Expected write, since or until"));
+        }
+
+        Ok((pattern, write_value, versions))
+    })?;
+    check_binding(&pattern, variant)?;
 
-    (
-        quote! { #ident(#(#new_fields)*), },
-        quote! { #value => Self::#ident(#(#read_funcs)*), },
-        quote! { Self::#ident (#(#indices),*) => {
-                let value = #value;
-                #write_value_func;
-                #(#write_funcs)*
+    let explicit_write = write_value.is_some();
+    let value = match write_value {
+        Some(value) => value,
+        None => match &pattern {
+            Pat::Or(_) | Pat::Range(_) | Pat::Wild(_) => {
+                return Err(syn::Error::new_spanned(
+                    &pattern,
+                    format!(
+                        "Variant {} matches more than one id. Add write = value to choose the id to write",
+                        variant.ident
+                    ),
+                ))
             }
+            _ => syn::parse2(pattern.to_token_stream()).map_err(|_| {
+                syn::Error::new_spanned(
+                    &pattern,
+                    format!("Id of {} can't be written as a value", variant.ident),
+                )
+            })?,
         },
-    )
+    };
+
+    Ok(VariantId {
+        pattern,
+        value,
+        explicit_write,
+        versions,
+    })
 }
 
-fn handle_unit(
-    ident: &proc_macro2::Ident,
-    value: Expr,
-    write_value_func: &TokenStream,
-) -> (TokenStream, TokenStream, TokenStream) {
-    (
-        quote! { #ident = #value, },
-        quote! { #value => Self::#ident, },
-        quote! { Self::#ident => {
-                let value = #value;
-                #write_value_func;
-            }
-        },
-    )
+/// Rejects identifiers that would bind any value instead of naming a constant. Constants can't be
+/// told apart from bindings here, so only UPPER_CASE identifiers are taken as constants.
+fn check_binding(pattern: &Pat, variant: &Variant) -> syn::Result<()> {
+    match pattern {
+        Pat::Or(pat_or) => pat_or
+            .cases
+            .iter()
+            .try_for_each(|case| check_binding(case, variant)),
+        Pat::Paren(pat_paren) => check_binding(&pat_paren.pat, variant),
+        Pat::Ident(pat_ident)
+            if pat_ident.by_ref.is_some()
+                || pat_ident.mutability.is_some()
+                || pat_ident.subpat.is_some()
+                || pat_ident
+                    .ident
+                    .to_string()
+                    .chars()
+                    .any(|c| c.is_lowercase()) =>
+        {
+            Err(syn::Error::new_spanned(
+                pattern,
+                format!(
+                    "Id of {} would match any value. Use a literal, a path or an UPPER_CASE constant",
+                    variant.ident
+                ),
+            ))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn build_tag_value(value: &Expr, is_string: bool) -> TokenStream {
+    if is_string {
+        quote! { String::from(#value) }
+    } else if matches!(
+        value,
+        Expr::Lit(ExprLit {
+            lit: Lit::ByteStr(_),
+            ..
+        })
+    ) {
+        // Byte string literals are references to byte arrays
+        quote! { *#value }
+    } else {
+        quote! { #value }
+    }
+}
+
+fn is_int_literal(value: &Expr) -> bool {
+    match value {
+        Expr::Lit(ExprLit {
+            lit: Lit::Int(_), ..
+        }) => true,
+        Expr::Unary(ExprUnary {
+            op: UnOp::Neg(_),
+            expr,
+            ..
+        }) => is_int_literal(expr),
+        _ => false,
+    }
 }
@@ -0,0 +1,1205 @@
+use crate::generate::{
+    build_generator, build_list_generator, build_value_generator, name_self, unstructured,
+    FieldsGenerator, Generator,
+};
+use crate::schema::{build_encoding, build_optional_string, replace_self, type_display};
+use crate::util::{
+    build_size_prefixed_quotes, build_type_quotes, build_version_checks, build_with_quotes,
+    check_meta, expected, extract_generic_type, is_string_type, Defaults, Errors, Props,
+    SizePrefix, Until, Versions,
+};
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::{
+    ext::IdentExt, parse::ParseStream, parse_quote, Attribute, Expr, Field, Fields, Ident, Member,
+    Meta, Path, Token, Type,
+};
+
+pub struct FieldsQuotes {
+    pub declare: TokenStream,
+    /// Declared types of the fields, in order.
+    pub types: Vec<TokenStream>,
+    pub read: TokenStream,
+    pub construct: TokenStream,
+    pub pattern: TokenStream,
+    pub write: TokenStream,
+    /// `Vec` of the `Field` schemas of the fields on the wire.
+    pub schema: TokenStream,
+    /// Generated values of the fields, when the container has a generate option.
+    pub generate: Option<FieldsGenerator>,
+}
+
+struct FieldAttrs {
+    props: Props,
+    tag: Option<Member>,
+    when: Option<Expr>,
+    versions: Versions,
+    size_from: Option<SizeFrom>,
+    size_prefix: Option<SizePrefix>,
+    skip: Option<Option<Expr>>,
+    validate: Validate,
+    pad: Option<Expr>,
+    align: Option<Expr>,
+}
+
+#[derive(Default)]
+struct Validate {
+    range: Option<Expr>,
+    min_len: Option<Expr>,
+    max_len: Option<Expr>,
+    func: Option<Path>,
+    on_write: bool,
+}
+
+enum SizeFrom {
+    Count(Member),
+    ByteLen(Member),
+}
+
+struct FieldInfo<'a> {
+    field: &'a Field,
+    member: Member,
+    local: Ident,
+    attrs: FieldAttrs,
+    new_field: TokenStream,
+    derived: bool,
+}
+
+/// `container` is the struct or enum of the fields, which `Self` refers to.
+pub fn build_fields(
+    fields: &Fields,
+    defaults: &Defaults,
+    container: &Ident,
+    generate: bool,
+) -> syn::Result<FieldsQuotes> {
+    let mut errors = Errors::default();
+    let mut infos = vec![];
+    for (i, f) in fields.iter().enumerate() {
+        let (member, local) = match &f.ident {
+            Some(ident) => (Member::Named(ident.clone()), ident.clone()),
+            None => (
+                Member::Unnamed(i.into()),
+                Ident::new(&format!("index_{}", i), Span::call_site()),
+            ),
+        };
+
+        infos.push(FieldInfo {
+            field: f,
+            member,
+            local,
+            attrs: get_field_attrs(&f.attrs, defaults, &mut errors),
+            new_field: TokenStream::new(),
+            derived: false,
+        });
+    }
+
+    let mut new_fields = vec![];
+    let mut read_funcs = vec![];
+    let mut derived_funcs = vec![];
+    let mut write_funcs = vec![];
+    let mut field_schemas = vec![];
+    let mut generators = vec![];
+
+    // Alignment is relative to the start of the fields, so remember where they begin
+    let start = Ident::new("start", Span::mixed_site());
+    if infos.iter().any(|info| info.attrs.align.is_some()) {
+        read_funcs.push(quote! { let #start = bytes::Buf::remaining(buf); });
+        write_funcs.push(quote! { let #start = buf.len(); });
+    }
+
+    for i in 0..infos.len() {
+        let (previous, rest) = infos.split_at_mut(i);
+        let info = &mut rest[0];
+        let f = info.field;
+        let field_vis = &f.vis;
+        let local = &info.local;
+
+        let (read_padding, write_padding) = build_padding_quotes(&info.attrs, &start);
+        read_funcs.push(read_padding);
+        write_funcs.push(write_padding);
+
+        // Skipped fields are not on the wire and only filled in on read
+        if let Some(default) = &info.attrs.skip {
+            let ty = &f.ty;
+            let read_func = match default {
+                Some(default) => {
+                    let bind_previous = build_bind_previous(previous);
+                    quote! {
+                        #bind_previous
+                        #default
+                    }
+                }
+                None => quote! { Default::default() },
+            };
+
+            match &f.ident {
+                Some(ident) => new_fields.push(quote! { #field_vis #ident: #ty, }),
+                None => new_fields.push(quote! { #field_vis #ty, }),
+            }
+            read_funcs.push(quote! {
+                let #local: #ty = { #read_func };
+            });
+            info.new_field = quote! { #ty };
+            continue;
+        }
+
+        // Built first, as building the quotes takes the length types out of the props
+        if let Some(schema) = errors.handle(build_field_schema(info, defaults, container)) {
+            field_schemas.push(schema);
+        }
+        if generate {
+            if let Some(generator) = errors.handle(build_field_generator(previous, info, container))
+            {
+                generators.push((i, generator));
+            }
+        }
+
+        let Some((new_field, read_func, write_func)) = errors.handle(build_field_quotes(
+            previous,
+            info,
+            defaults,
+            &mut derived_funcs,
+        )) else {
+            continue;
+        };
+        let f = info.field;
+        let local = &info.local;
+
+        match &f.ident {
+            Some(ident) => new_fields.push(quote! { #field_vis #ident: #new_field, }),
+            None => new_fields.push(quote! { #field_vis #new_field, }),
+        }
+        let validation = build_validation(local, &info.attrs.validate, &info.field.ty);
+        let write_validation = if info.attrs.validate.on_write {
+            validation.clone()
+        } else {
+            quote! {}
+        };
+
+        let read_validation = if validation.is_empty() {
+            quote! {}
+        } else {
+            quote! {
+                {
+                    let value = &#local;
+                    #validation
+                }
+            }
+        };
+
+        read_funcs.push(quote! {
+            let #local = { #read_func };
+            #read_validation
+        });
+        write_funcs.push(quote! {
+            {
+                let value = #local;
+                #write_validation
+                #write_func;
+            }
+        });
+
+        info.new_field = new_field;
+    }
+
+    errors.finish()?;
+
+    let generate = generate.then(|| build_fixup(&infos, generators, &derived_funcs));
+
+    // Derived fields are rebound after destructuring, so they are ignored in the pattern
+    let patterns = infos.iter().map(|info| {
+        match (&info.field.ident, info.derived || info.attrs.skip.is_some()) {
+            (Some(ident), true) => quote! { #ident: _ },
+            (Some(ident), false) => quote! { #ident },
+            (None, true) => quote! { _ },
+            (None, false) => {
+                let local = &info.local;
+                quote! { #local }
+            }
+        }
+    });
+
+    let locals = infos.iter().map(|info| &info.local);
+    let (declare, construct, pattern) = match fields {
+        Fields::Named(_) => (
+            quote! { { #(#new_fields)* } },
+            quote! { { #(#locals),* } },
+            quote! { { #(#patterns),* } },
+        ),
+        Fields::Unnamed(_) => (
+            quote! { (#(#new_fields)*) },
+            quote! { (#(#locals),*) },
+            quote! { (#(#patterns),*) },
+        ),
+        Fields::Unit => (quote! {}, quote! {}, quote! {}),
+    };
+
+    Ok(FieldsQuotes {
+        declare,
+        types: infos.iter().map(|info| info.new_field.clone()).collect(),
+        read: quote! { #(#read_funcs)* },
+        construct,
+        pattern,
+        write: quote! {
+            #(#derived_funcs)*
+            #(#write_funcs)*
+        },
+        schema: quote! { vec![#(#field_schemas),*] },
+        generate,
+    })
+}
+
+/// Builds the quotes of a field that is on the wire, marking the earlier fields it derives.
+fn build_field_quotes(
+    previous: &mut [FieldInfo],
+    info: &mut FieldInfo,
+    defaults: &Defaults,
+    derived_funcs: &mut Vec<TokenStream>,
+) -> syn::Result<(TokenStream, TokenStream, TokenStream)> {
+    let f = info.field;
+    let local = &info.local;
+
+    // Conditional fields stored as options only read the inner type when present
+    let condition = build_condition(&info.attrs);
+    let option_type = condition.as_ref().and_then(|_| extract_option_type(&f.ty));
+    let ty = option_type.as_ref().unwrap_or(&f.ty);
+
+    let (new_field, read_func, write_func) = if let Some(tag) = &info.attrs.tag {
+        // Fields used as the tag of a later field are written from that field's variant
+        let tag_info = find_field(previous, tag)?;
+        let tag_local = &tag_info.local;
+        derived_funcs.push(quote! {
+            let #tag_local = &proto_rw::ProtoRwTagged::tag(#local, ctx)?;
+        });
+        tag_info.derived = true;
+
+        build_tagged_quotes(ty, tag_local)
+    } else if let Some(size_from) = &info.attrs.size_from {
+        let (member, is_count) = match size_from {
+            SizeFrom::Count(member) => (member, true),
+            SizeFrom::ByteLen(member) => (member, false),
+        };
+        let size_info = find_field(previous, member)?;
+        let (quotes, encode_func, len) =
+            build_size_from_quotes(ty, &mut info.attrs.props, local, size_info, is_count)?;
+
+        // Fields holding the size of a later field are written from its actual size
+        let size_local = &size_info.local;
+        let size_type = &size_info.new_field;
+        derived_funcs.push(quote! {
+            #encode_func
+            let #size_local = &<#size_type>::try_from(#len).map_err(|_| {
+                proto_rw::error::ProtoRwError::Error(format!(
+                    "Size of {} does not fit in {}",
+                    stringify!(#local),
+                    stringify!(#size_local)
+                ))
+            })?;
+        });
+        size_info.derived = true;
+
+        quotes
+    } else if info.attrs.props.read_with.is_some() || info.attrs.props.write_with.is_some() {
+        build_with_quotes(ty, &mut info.attrs.props)?
+    } else {
+        build_type_quotes(ty, &mut info.attrs.props, None)?
+    };
+
+    let (new_field, read_func, write_func) = match &info.attrs.size_prefix {
+        Some(size_prefix) => build_size_prefixed_quotes(
+            size_prefix,
+            defaults,
+            &quote! { #local },
+            (new_field, read_func, write_func),
+        )?,
+        None => (new_field, read_func, write_func),
+    };
+
+    Ok(match &condition {
+        Some(condition) => build_conditional_quotes(
+            previous,
+            local,
+            condition,
+            option_type.is_some(),
+            (new_field, read_func, write_func),
+        ),
+        None => (new_field, read_func, write_func),
+    })
+}
+
+fn build_field_schema(
+    info: &FieldInfo,
+    defaults: &Defaults,
+    container: &Ident,
+) -> syn::Result<TokenStream> {
+    let attrs = &info.attrs;
+    let mut props = attrs.props.clone();
+
+    // Schemas name other types, so `Self` is named too
+    let field_type = replace_self(&info.field.ty, container);
+    let option_type = build_condition(attrs).and_then(|_| extract_option_type(&field_type));
+    let ty = option_type.as_ref().unwrap_or(&field_type);
+
+    let encoding = if let Some(tag) = &attrs.tag {
+        let name = type_display(ty);
+        let tag = member_name(tag);
+        quote! {
+            proto_rw::schema::Encoding::Untagged {
+                name: String::from(#name),
+                tag: String::from(#tag),
+            }
+        }
+    } else if let Some(size_from) = &attrs.size_from {
+        let length = match size_from {
+            SizeFrom::Count(member) => {
+                let field = member_name(member);
+                quote! { proto_rw::schema::Length::CountFrom { field: String::from(#field) } }
+            }
+            SizeFrom::ByteLen(member) => {
+                let field = member_name(member);
+                quote! { proto_rw::schema::Length::ByteLenFrom { field: String::from(#field) } }
+            }
+        };
+        match ty {
+            Type::Path(type_path)
+                if type_path
+                    .path
+                    .segments
+                    .last()
+                    .is_some_and(|segment| segment.ident == "Vec") =>
+            {
+                let segment = type_path.path.segments.last().unwrap();
+                let gen_type = extract_generic_type(segment).ok_or_else(|| {
+                    syn::Error::new_spanned(segment, "No generic type found for Vec")
+                })?;
+                let item = build_encoding(&gen_type, &mut props)?;
+                quote! {
+                    proto_rw::schema::Encoding::List { length: #length, item: Box::new(#item) }
+                }
+            }
+            _ => quote! { proto_rw::schema::Encoding::String { length: #length } },
+        }
+    } else if props.read_with.is_some() || props.write_with.is_some() {
+        let path_name = |path: &Option<Path>| match path {
+            Some(path) => {
+                let path = quote!(#path).to_string().replace(' ', "");
+                quote! { Some(String::from(#path)) }
+            }
+            None => quote! { None },
+        };
+        let read_with = path_name(&props.read_with);
+        let write_with = path_name(&props.write_with);
+        quote! {
+            proto_rw::schema::Encoding::Custom {
+                read_with: #read_with,
+                write_with: #write_with,
+            }
+        }
+    } else {
+        build_encoding(ty, &mut props)?
+    };
+
+    let encoding = match &attrs.size_prefix {
+        Some(size_prefix) => {
+            let length = build_encoding(&size_prefix.length_type, &mut Props::new(defaults))?;
+            quote! {
+                proto_rw::schema::Encoding::SizePrefixed {
+                    length: Box::new(#length),
+                    value: Box::new(#encoding),
+                }
+            }
+        }
+        None => encoding,
+    };
+
+    let name = member_name(&info.member);
+    let when = build_optional_string(attrs.when.as_ref());
+    let since = build_optional_string(attrs.versions.since.as_ref());
+    let until = build_optional_string(attrs.versions.until.as_ref());
+    let pad = build_optional_string(attrs.pad.as_ref());
+    let align = build_optional_string(attrs.align.as_ref());
+    Ok(quote! {
+        proto_rw::schema::Field {
+            name: String::from(#name),
+            encoding: #encoding,
+            when: #when,
+            since: #since,
+            until: #until,
+            pad: #pad,
+            align: #align,
+        }
+    })
+}
+
+fn member_name(member: &Member) -> String {
+    match member {
+        Member::Named(ident) => ident.unraw().to_string(),
+        Member::Unnamed(index) => index.index.to_string(),
+    }
+}
+
+/// Builds the generator of a field's value on its own, before it is made consistent with the
+/// other fields by `build_fixup`.
+fn build_field_generator(
+    previous: &[FieldInfo],
+    info: &FieldInfo,
+    container: &Ident,
+) -> syn::Result<Generator> {
+    let attrs = &info.attrs;
+    let mut props = attrs.props.clone();
+
+    let field_type = name_self(&info.field.ty, container);
+    let option_type = build_condition(attrs).and_then(|_| extract_option_type(&field_type));
+    let ty = option_type.as_ref().unwrap_or(&field_type);
+    let segment = match ty {
+        Type::Path(type_path) => type_path.path.segments.last(),
+        _ => None,
+    };
+
+    let generator =
+        if let Some(SizeFrom::Count(member) | SizeFrom::ByteLen(member)) = &attrs.size_from {
+            // The size field has to hold the length, so it limits the number of items
+            let size_type = previous
+                .iter()
+                .find(|info| &info.member == member)
+                .map(|info| &info.new_field);
+            match segment {
+                Some(segment) if segment.ident == "Vec" => {
+                    let gen_type = extract_generic_type(segment).ok_or_else(|| {
+                        syn::Error::new_spanned(segment, "No generic type found for Vec")
+                    })?;
+                    build_list_generator(
+                        build_generator(&gen_type, &mut props)?,
+                        quote! { <#size_type as proto_rw::generate::MaxLen>::MAX_LEN },
+                    )
+                }
+                _ => build_value_generator(ty),
+            }
+        } else if attrs.tag.is_some() || props.read_with.is_some() || props.write_with.is_some() {
+            build_value_generator(ty)
+        } else {
+            build_generator(ty, &mut props)?
+        };
+
+    let Generator {
+        ty: new_type,
+        mut arbitrary,
+        mut strategy,
+        recursion,
+    } = generator;
+    let validate = &attrs.validate;
+
+    // Integers are generated in their valid range, instead of being rejected most of the time
+    let is_integer = [
+        "u8", "u16", "u32", "u64", "u128", "i8", "i16", "i32", "i64", "i128",
+    ]
+    .contains(&new_type.to_string().as_str());
+    if let (Some(range), true) = (&validate.range, is_integer) {
+        let bounds = quote! { proto_rw::generate::bounds::<#new_type>(&(#range)) };
+        let u = unstructured();
+        arbitrary = quote! {
+            {
+                let (low, high) = #bounds.ok_or(proto_rw::arbitrary::Error::IncorrectFormat)?;
+                #u.int_in_range(low..=high)?
+            }
+        };
+        strategy = quote! {
+            {
+                let (low, high) = #bounds.expect(concat!("Empty range ", stringify!(#range)));
+                low..=high
+            }
+        };
+    }
+
+    let is_list =
+        segment.is_some_and(|segment| segment.ident == "Vec" || segment.ident == "String");
+    if let (Some(max_len), true, None) = (&validate.max_len, is_list, &option_type) {
+        let truncate = quote! {
+            proto_rw::generate::Truncate::truncate_len(&mut value, #max_len);
+            value
+        };
+        arbitrary = quote! {
+            {
+                let mut value: #new_type = #arbitrary;
+                #truncate
+            }
+        };
+        strategy = quote! {
+            proto_rw::proptest::strategy::Strategy::prop_map(
+                #strategy,
+                |mut value: #new_type| { #truncate },
+            )
+        };
+    }
+
+    Ok(Generator {
+        ty: new_type,
+        arbitrary,
+        strategy,
+        recursion,
+    })
+}
+
+/// Makes generated fields consistent the way reading them back would: fields written from
+/// others are derived from them, missing conditional fields are emptied and skipped fields
+/// get their default. Values that still can't be written or fail validation are rejected.
+fn build_fixup(
+    infos: &[FieldInfo],
+    generators: Vec<(usize, Generator)>,
+    derived_funcs: &[TokenStream],
+) -> FieldsGenerator {
+    let (indexes, generators): (Vec<_>, Vec<_>) = generators
+        .into_iter()
+        .filter(|(i, _)| !infos[*i].derived)
+        .unzip();
+    let locals = indexes
+        .iter()
+        .map(|i| infos[*i].local.clone())
+        .collect::<Vec<_>>();
+
+    let derived = infos
+        .iter()
+        .filter(|info| info.derived)
+        .map(|info| &info.local)
+        .collect::<Vec<_>>();
+    let derive = if derived.is_empty() {
+        quote! {}
+    } else {
+        quote! {
+            let (#(#derived,)*) = {
+                #(
+                    #[allow(unused_variables)]
+                    let #locals = &#locals;
+                )*
+                #(#derived_funcs)*
+                (#(Clone::clone(#derived),)*)
+            };
+        }
+    };
+
+    let mut fixups = vec![];
+    let mut conditional = false;
+    for (i, info) in infos.iter().enumerate() {
+        let local = &info.local;
+        let bind_previous = build_bind_previous(&infos[..i]);
+
+        if let Some(default) = &info.attrs.skip {
+            conditional = true;
+            let ty = &info.field.ty;
+            let default = match default {
+                Some(default) => quote! { #default },
+                None => quote! { Default::default() },
+            };
+            fixups.push(quote! {
+                let #local: #ty = {
+                    #bind_previous
+                    #default
+                };
+            });
+            continue;
+        }
+        if info.derived {
+            continue;
+        }
+
+        if let Some(condition) = build_condition(&info.attrs) {
+            conditional = true;
+            let (present, missing) = match extract_option_type(&info.field.ty) {
+                Some(_) => (quote! { Some(#local) }, quote! { None }),
+                None => (quote! { #local }, quote! { Default::default() }),
+            };
+            fixups.push(quote! {
+                let #local = {
+                    let condition: bool = {
+                        #bind_previous
+                        #condition
+                    };
+                    if condition {
+                        #present
+                    } else {
+                        #missing
+                    }
+                };
+            });
+        }
+
+        let validation = build_validation(local, &info.attrs.validate, &info.field.ty);
+        if !validation.is_empty() {
+            fixups.push(quote! {
+                {
+                    let value = &#local;
+                    #validation
+                }
+            });
+        }
+    }
+
+    // Emptied fields change the size of the fields derived from them
+    let rederive = if conditional {
+        derive.clone()
+    } else {
+        quote! {}
+    };
+
+    FieldsGenerator {
+        locals,
+        generators,
+        fixup: quote! {
+            #[allow(unused_variables)]
+            let ctx = &proto_rw::context::Context::default();
+            #derive
+            #(#fixups)*
+            #rederive
+        },
+    }
+}
+
+fn get_field_attrs(attrs: &[Attribute], defaults: &Defaults, errors: &mut Errors) -> FieldAttrs {
+    let mut field_attrs = FieldAttrs {
+        props: Props::new(defaults),
+        tag: None,
+        when: None,
+        versions: Versions::default(),
+        size_from: None,
+        size_prefix: None,
+        skip: None,
+        validate: Validate::default(),
+        pad: None,
+        align: None,
+    };
+
+    for attr in attrs {
+        errors.handle(parse_field_attr(attr, &mut field_attrs));
+    }
+
+    field_attrs
+}
+
+/// Field attributes that only work with arguments.
+const LIST_ATTRS: &[&str] = &[
+    "tag",
+    "when",
+    "since",
+    "until",
+    "terminator",
+    "until_item",
+    "count_from",
+    "byte_len_from",
+    "size_prefix",
+    "validate",
+    "pad",
+    "align",
+    "convert",
+    "length",
+    "with",
+    "read_with",
+    "write_with",
+];
+
+fn parse_field_attr(attr: &Attribute, field_attrs: &mut FieldAttrs) -> syn::Result<()> {
+    if !matches!(attr.meta, Meta::List(_)) {
+        if let Some(name) = LIST_ATTRS.iter().find(|name| attr.path().is_ident(name)) {
+            return Err(syn::Error::new_spanned(
+                attr,
+                format!("Expected arguments, like #[{}(...)]", name),
+            ));
+        }
+    }
+
+    match &attr.meta {
+        Meta::List(list) if list.path.is_ident("tag") => {
+            field_attrs.tag = Some(expected(
+                list.parse_args::<Member>(),
+                "Expected a field name or index in tag",
+            )?);
+        }
+        Meta::List(list) if list.path.is_ident("when") => {
+            field_attrs.when = Some(expected(
+                list.parse_args::<Expr>(),
+                "Expected a condition in when",
+            )?);
+        }
+        Meta::List(list) if list.path.is_ident("since") => {
+            field_attrs.versions.since = Some(expected(
+                list.parse_args::<Expr>(),
+                "Expected a version in since",
+            )?);
+        }
+        Meta::List(list) if list.path.is_ident("until") => {
+            let version = expected(list.parse_args::<Expr>(), "Expected a version in until")?;
+            // Lists used to end with `#[until(value = x)]` and `#[until(|item| ...)]`
+            if matches!(version, Expr::Assign(_) | Expr::Closure(_)) {
+                return Err(syn::Error::new_spanned(
+                    version,
+                    "#[until] takes a protocol version. Use #[terminator(x)] or #[until_item(|item| ...)] to end a list",
+                ));
+            }
+            field_attrs.versions.until = Some(version);
+        }
+        Meta::List(list) if list.path.is_ident("terminator") => {
+            field_attrs.props.until = Some(Until::Value(expected(
+                list.parse_args::<Expr>(),
+                "Expected a byte or byte string in terminator",
+            )?));
+        }
+        Meta::List(list) if list.path.is_ident("until_item") => {
+            let predicate = expected(
+                list.parse_args::<Expr>(),
+                "Expected a closure in until_item",
+            )?;
+            if !matches!(predicate, Expr::Closure(_)) {
+                return Err(syn::Error::new_spanned(
+                    predicate,
+                    "Expected a closure in until_item",
+                ));
+            }
+            field_attrs.props.until = Some(Until::Predicate(predicate));
+        }
+        Meta::Path(path) if path.is_ident("transparent") => {
+            field_attrs.props.transparent = true;
+        }
+        Meta::Path(path) if path.is_ident("until_eof") => {
+            field_attrs.props.until = Some(Until::Eof);
+        }
+        Meta::List(list) if list.path.is_ident("count_from") => {
+            let member = expected(
+                list.parse_args::<Member>(),
+                "Expected a field name or index in count_from",
+            )?;
+            field_attrs.size_from = Some(SizeFrom::Count(member));
+        }
+        Meta::List(list) if list.path.is_ident("byte_len_from") => {
+            let member = expected(
+                list.parse_args::<Member>(),
+                "Expected a field name or index in byte_len_from",
+            )?;
+            field_attrs.size_from = Some(SizeFrom::ByteLen(member));
+        }
+        Meta::List(list) if list.path.is_ident("size_prefix") => {
+            field_attrs.size_prefix = Some(list.parse_args::<SizePrefix>()?);
+        }
+        Meta::List(list) if list.path.is_ident("pad") => {
+            field_attrs.pad = Some(expected(
+                list.parse_args::<Expr>(),
+                "Expected a number of bytes in pad",
+            )?);
+        }
+        Meta::List(list) if list.path.is_ident("align") => {
+            field_attrs.align = Some(expected(
+                list.parse_args::<Expr>(),
+                "Expected a number of bytes in align",
+            )?);
+        }
+        Meta::Path(path) if path.is_ident("skip") => {
+            field_attrs.skip = Some(None);
+        }
+        Meta::List(list) if list.path.is_ident("skip") => {
+            let default = list.parse_args_with(|input: ParseStream| {
+                let key = input.parse::<Ident>()?;
+                if key != "default" {
+                    return Err(syn::Error::new(key.span(), "Expected default = value"));
+                }
+                input.parse::<Token![=]>()?;
+                input.parse::<Expr>()
+            })?;
+            field_attrs.skip = Some(Some(default));
+        }
+        Meta::List(list) if list.path.is_ident("validate") => {
+            list.parse_args_with(|input: ParseStream| {
+                parse_validate(input, &mut field_attrs.validate)
+            })?;
+        }
+        meta => check_meta(&mut field_attrs.props, meta)?,
+    }
+
+    Ok(())
+}
+
+fn find_field<'a, 'b>(
+    previous: &'a mut [FieldInfo<'b>],
+    member: &Member,
+) -> syn::Result<&'a mut FieldInfo<'b>> {
+    previous
+        .iter_mut()
+        .find(|info| &info.member == member)
+        .ok_or_else(|| {
+            syn::Error::new_spanned(
+                member,
+                format!(
+                    "Field {} not found. Only earlier fields can be referenced",
+                    quote! { #member }
+                ),
+            )
+        })
+}
+
+fn build_tagged_quotes(ty: &Type, tag_local: &Ident) -> (TokenStream, TokenStream, TokenStream) {
+    (
+        quote! { #ty },
+        quote! { <#ty as proto_rw::ProtoRwTagged>::read_proto_tagged(&#tag_local, buf, ctx)? },
+        quote! { proto_rw::ProtoRwTagged::write_proto_untagged(value, buf, ctx)? },
+    )
+}
+
+/// Binds earlier fields as references, the same way they are visible on write.
+fn build_bind_previous(previous: &[FieldInfo]) -> TokenStream {
+    let previous = previous.iter().map(|info| &info.local);
+    quote! {
+        #(
+            #[allow(unused_variables)]
+            let #previous = &#previous;
+        )*
+    }
+}
+
+/// Builds the zero bytes before a field from `#[pad(n)]` and `#[align(n)]`.
+fn build_padding_quotes(attrs: &FieldAttrs, start: &Ident) -> (TokenStream, TokenStream) {
+    let mut read_lens = vec![];
+    let mut write_lens = vec![];
+
+    if let Some(pad) = &attrs.pad {
+        read_lens.push(quote! { let len: usize = #pad; });
+        write_lens.push(quote! { let len: usize = #pad; });
+    }
+    if let Some(align) = &attrs.align {
+        read_lens.push(quote! {
+            let offset = #start - bytes::Buf::remaining(buf);
+            let len: usize = (#align - offset % #align) % #align;
+        });
+        write_lens.push(quote! {
+            let offset = buf.len() - #start;
+            let len: usize = (#align - offset % #align) % #align;
+        });
+    }
+
+    (
+        quote! {
+            #(
+                {
+                    #read_lens
+                    if bytes::Buf::remaining(buf) < len {
+                        Err(proto_rw::error::ProtoRwError::UnexpectedEof)?;
+                    }
+                    let padding = buf.split_to(len);
+                    if padding.iter().any(|byte| *byte != 0) {
+                        Err(proto_rw::error::ProtoRwError::UnexpectedValue {
+                            expected: format!("{} zero bytes", len),
+                            found: format!("{:?}", padding),
+                        })?;
+                    }
+                }
+            )*
+        },
+        quote! {
+            #(
+                {
+                    #write_lens
+                    bytes::BufMut::put_bytes(buf, 0, len);
+                }
+            )*
+        },
+    )
+}
+
+/// Combines the `#[when]` condition with the version checks of `#[since]` and `#[until]`.
+fn build_condition(attrs: &FieldAttrs) -> Option<Expr> {
+    let mut checks = build_version_checks(&attrs.versions);
+    if let Some(when) = &attrs.when {
+        checks.push(quote! { (#when) });
+    }
+
+    if checks.is_empty() {
+        None
+    } else {
+        Some(parse_quote! { #(#checks)&&* })
+    }
+}
+
+fn build_conditional_quotes(
+    previous: &[FieldInfo],
+    local: &Ident,
+    condition: &Expr,
+    is_option: bool,
+    (new_field, read_func, write_func): (TokenStream, TokenStream, TokenStream),
+) -> (TokenStream, TokenStream, TokenStream) {
+    let bind_previous = build_bind_previous(previous);
+    let read_condition = quote! {
+        let condition: bool = {
+            #bind_previous
+            #condition
+        };
+    };
+
+    if is_option {
+        (
+            quote! { Option<#new_field> },
+            quote! {
+                #read_condition
+                if condition {
+                    Some({ #read_func })
+                } else {
+                    None
+                }
+            },
+            quote! {
+                let condition: bool = #condition;
+                match (condition, value) {
+                    (true, Some(value)) => {
+                        #write_func;
+                    }
+                    (false, None) => {}
+                    _ => Err(proto_rw::error::ProtoRwError::Error(format!(
+                        "Field {} is {} but its condition is {}",
+                        stringify!(#local),
+                        if value.is_some() { "Some" } else { "None" },
+                        condition
+                    )))?,
+                }
+            },
+        )
+    } else {
+        // Only the default value is read back when the condition is false
+        let write = quote! {
+                let condition: bool = #condition;
+                if condition {
+                    #write_func;
+                } else if *value != <#new_field as Default>::default() {
+                    Err(proto_rw::error::ProtoRwError::Error(format!(
+                        "Field {} is not the default value but its condition is false",
+                        stringify!(#local)
+                    )))?;
+                }
+        };
+        (
+            new_field,
+            quote! {
+                #read_condition
+                if condition {
+                    #read_func
+                } else {
+                    Default::default()
+                }
+            },
+            write,
+        )
+    }
+}
+
+fn extract_option_type(ty: &Type) -> Option<Type> {
+    let Type::Path(type_path) = ty else {
+        return None;
+    };
+
+    let segment = type_path.path.segments.last()?;
+    if segment.ident != "Option" {
+        return None;
+    }
+
+    extract_generic_type(segment)
+}
+
+fn build_size_from_quotes(
+    ty: &Type,
+    props: &mut Props,
+    local: &Ident,
+    size_info: &FieldInfo,
+    is_count: bool,
+) -> syn::Result<(
+    (TokenStream, TokenStream, TokenStream),
+    TokenStream,
+    TokenStream,
+)> {
+    let size_local = &size_info.local;
+    let read_len = quote! {
+        let len = #size_local;
+        let len = usize::try_from(len).map_err(|_| {
+            proto_rw::error::ProtoRwError::Error(format!(
+                "Invalid size {} for {}",
+                len,
+                stringify!(#local)
+            ))
+        })?;
+    };
+
+    let segment = match ty {
+        Type::Path(type_path) => type_path.path.segments.last(),
+        _ => None,
+    };
+
+    match segment {
+        Some(segment) if segment.ident == "String" => Ok((
+            (
+                quote! { String },
+                quote! {
+                    #read_len
+                    if bytes::Buf::remaining(buf) < len {
+                        Err(proto_rw::error::ProtoRwError::UnexpectedEof)?;
+                    }
+                    String::from_utf8(buf.split_to(len).to_vec())?
+                },
+                quote! { buf.extend_from_slice(value.as_bytes()) },
+            ),
+            quote! {},
+            quote! { #local.len() },
+        )),
+        Some(segment) if segment.ident == "Vec" => {
+            let gen_type = extract_generic_type(segment)
+                .ok_or_else(|| syn::Error::new_spanned(segment, "No generic type found for Vec"))?;
+            let (inner_type, inner_read, inner_write) = build_type_quotes(&gen_type, props, None)?;
+
+            if is_count {
+                Ok((
+                    (
+                        quote! { Vec<#inner_type> },
+                        quote! {
+                            #read_len
+                            // Untrusted lengths can't reserve more items than there are bytes
+                            let mut vec =
+                                Vec::with_capacity(core::cmp::min(len, bytes::Buf::remaining(buf)));
+                            for _ in 0..len {
+                                vec.push({ #inner_read });
+                            }
+                            vec
+                        },
+                        quote! {
+                            for value in value {
+                                { #inner_write };
+                            }
+                        },
+                    ),
+                    quote! {},
+                    quote! { #local.len() },
+                ))
+            } else {
+                // The encoded items are needed up front to know their size
+                let bytes_local = format_ident!("{}_bytes", local);
+                Ok((
+                    (
+                        quote! { Vec<#inner_type> },
+                        quote! {
+                            #read_len
+                            if bytes::Buf::remaining(buf) < len {
+                                Err(proto_rw::error::ProtoRwError::UnexpectedEof)?;
+                            }
+                            let buf = &mut buf.split_to(len);
+                            let mut vec = Vec::new();
+                            while bytes::Buf::has_remaining(buf) {
+                                vec.push({ #inner_read });
+                            }
+                            vec
+                        },
+                        quote! { buf.extend_from_slice(&#bytes_local) },
+                    ),
+                    quote! {
+                        let #bytes_local = {
+                            let mut encoded = bytes::BytesMut::new();
+                            let buf = &mut encoded;
+                            for value in #local {
+                                { #inner_write };
+                            }
+                            encoded
+                        };
+                    },
+                    quote! { #bytes_local.len() },
+                ))
+            }
+        }
+        _ => Err(syn::Error::new_spanned(
+            ty,
+            "count_from and byte_len_from only support Vec and String fields",
+        )),
+    }
+}
+
+fn parse_validate(input: ParseStream, validate: &mut Validate) -> syn::Result<()> {
+    while !input.is_empty() {
+        let key = Ident::parse_any(input)?;
+
+        if key == "on_write" {
+            validate.on_write = true;
+        } else {
+            input.parse::<Token![=]>()?;
+            match key.to_string().as_str() {
+                "range" => validate.range = Some(input.parse()?),
+                "min_len" => validate.min_len = Some(input.parse()?),
+                "max_len" => validate.max_len = Some(input.parse()?),
+                "fn" => validate.func = Some(input.parse()?),
+                _ => {
+                    return Err(syn::Error::new(
+                        key.span(),
+                        "Expected range, min_len, max_len, fn or on_write",
+                    ))
+                }
+            }
+        }
+
+        if !input.is_empty() {
+            input.parse::<Token![,]>()?;
+        }
+    }
+
+    Ok(())
+}
+
+fn build_validation(local: &Ident, validate: &Validate, ty: &Type) -> TokenStream {
+    let mut checks = vec![];
+    // Strings are measured in characters, other values in items
+    let len = if is_string_type(ty) {
+        quote! { value.chars().count() }
+    } else {
+        quote! { value.len() }
+    };
+
+    if let Some(range) = &validate.range {
+        checks.push(quote! {
+            if !(#range).contains(value) {
+                Err(format!("{:?} is not in {}", value, stringify!(#range)))
+            } else {
+                Ok(())
+            }
+        });
+    }
+    if let Some(min_len) = &validate.min_len {
+        checks.push(quote! {
+            if #len < #min_len {
+                Err(format!("length {} is less than {}", #len, #min_len))
+            } else {
+                Ok(())
+            }
+        });
+    }
+    if let Some(max_len) = &validate.max_len {
+        checks.push(quote! {
+            if #len > #max_len {
+                Err(format!("length {} is greater than {}", #len, #max_len))
+            } else {
+                Ok(())
+            }
+        });
+    }
+    if let Some(func) = &validate.func {
+        checks.push(quote! {
+            if !#func(value) {
+                Err(format!("{} returned false", stringify!(#func)))
+            } else {
+                Ok(())
+            }
+        });
+    }
+
+    if checks.is_empty() {
+        return quote! {};
+    }
+
+    quote! {
+        #(
+            let result: Result<(), String> = { #checks };
+            if let Err(message) = result {
+                Err(proto_rw::error::ProtoRwError::Validation {
+                    field: stringify!(#local),
+                    message,
+                })?;
+            }
+        )*
+    }
+}
@@ -0,0 +1,817 @@
+use proc_macro2::{Group, Span, TokenStream, TokenTree};
+use quote::{format_ident, quote, ToTokens};
+use syn::{Expr, Generics, Ident, Type};
+
+use crate::util::{
+    extract_generic_type, extract_generic_types, strip_generic_args, take_length_type, Props, Until,
+};
+
+/// The `arbitrary`, `proptest` and `roundtrip_test` options of a container.
+#[derive(Default, Clone, Copy)]
+pub struct GenerateOptions {
+    pub arbitrary: bool,
+    pub proptest: bool,
+    /// Emits a proptest checking that values are read back as written.
+    pub roundtrip_test: bool,
+}
+
+impl GenerateOptions {
+    pub fn any(&self) -> bool {
+        self.arbitrary || self.proptest || self.roundtrip_test
+    }
+}
+
+/// Expressions creating values of a type, from the `arbitrary::Unstructured` in `u` or as a
+/// proptest `Strategy`.
+pub struct Generator {
+    pub ty: TokenStream,
+    pub arbitrary: TokenStream,
+    pub strategy: TokenStream,
+    pub recursion: Recursion,
+}
+
+/// How generated values hold values of their own type, through `Self`.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Recursion {
+    None,
+    /// In `Option`s, collections or some variants, which stop the nesting when left empty or
+    /// not picked.
+    Optional,
+    /// In every value, so they can't stop nesting.
+    Required,
+}
+
+/// Generated fields, which are created independently and then made consistent by `fixup`.
+pub struct FieldsGenerator {
+    pub locals: Vec<Ident>,
+    pub generators: Vec<Generator>,
+    /// Statements that fill in the fields not generated, like tags and sizes, and may fail with
+    /// a `ProtoRwError` when the values can't be written.
+    pub fixup: TokenStream,
+}
+
+/// The `Unstructured` of `Arbitrary::arbitrary`, which can't clash with the fields.
+pub fn unstructured() -> Ident {
+    Ident::new("u", Span::mixed_site())
+}
+
+/// Names the container `Self` in the type of one of its fields, so values holding their own
+/// type are found however the field names it. Paths like `other::Name` are left as they are.
+pub fn name_self(ty: &Type, container: &Ident) -> Type {
+    fn replace(tokens: TokenStream, container: &Ident) -> TokenStream {
+        let mut after_path_sep = false;
+        tokens
+            .into_iter()
+            .map(|token| {
+                let token = match token {
+                    TokenTree::Ident(ident) if ident == *container && !after_path_sep => {
+                        TokenTree::Ident(Ident::new("Self", ident.span()))
+                    }
+                    TokenTree::Group(group) => {
+                        let mut new_group =
+                            Group::new(group.delimiter(), replace(group.stream(), container));
+                        new_group.set_span(group.span());
+                        TokenTree::Group(new_group)
+                    }
+                    token => token,
+                };
+                after_path_sep =
+                    matches!(&token, TokenTree::Punct(punct) if punct.as_char() == ':');
+                token
+            })
+            .collect()
+    }
+
+    syn::parse2(replace(ty.to_token_stream(), container)).unwrap_or_else(|_| ty.clone())
+}
+
+/// The `Option<BoxedStrategy<Self>>` of `Self` values, which is `None` while building the leaves
+/// of a recursive strategy.
+fn inner_strategy() -> Ident {
+    Ident::new("inner", Span::mixed_site())
+}
+
+/// Builds the generator of a type. Like `build_type_quotes`, it takes the length types and
+/// `until` of the field from `props`, and lengths are limited to what their prefix can hold.
+pub fn build_generator(ty: &Type, props: &mut Props) -> syn::Result<Generator> {
+    match ty {
+        Type::Path(type_path) => build_path_generator(type_path, props),
+        Type::Tuple(type_tuple) => {
+            let items = type_tuple
+                .elems
+                .iter()
+                .map(|elem| build_generator(elem, props))
+                .collect::<syn::Result<Vec<_>>>()?;
+            let types = items.iter().map(|item| &item.ty);
+            let arbitrary = items.iter().map(|item| &item.arbitrary);
+            let strategy = items.iter().map(|item| &item.strategy);
+            Ok(Generator {
+                ty: quote! { (#(#types,)*) },
+                arbitrary: quote! { (#(#arbitrary,)*) },
+                strategy: quote! { (#(#strategy,)*) },
+                recursion: items
+                    .iter()
+                    .map(|item| item.recursion)
+                    .max()
+                    .unwrap_or(Recursion::None),
+            })
+        }
+        Type::Array(type_array) => {
+            let len = &type_array.len;
+            let Generator {
+                ty: item_type,
+                arbitrary,
+                strategy,
+                recursion,
+            } = build_generator(&type_array.elem, props)?;
+            let ty = quote! { [#item_type; #len] };
+            let into_array = quote! {
+                match <#ty>::try_from(items) {
+                    Ok(array) => array,
+                    Err(_) => unreachable!(),
+                }
+            };
+            Ok(Generator {
+                arbitrary: quote! {
+                    {
+                        let mut items = Vec::with_capacity(#len);
+                        for _ in 0..#len {
+                            items.push({ #arbitrary });
+                        }
+                        #into_array
+                    }
+                },
+                strategy: quote! {
+                    proto_rw::proptest::strategy::Strategy::prop_map(
+                        proto_rw::proptest::collection::vec(#strategy, #len),
+                        |items| #into_array,
+                    )
+                },
+                ty,
+                recursion,
+            })
+        }
+        _ => Err(syn::Error::new_spanned(
+            ty,
+            "Unsupported type found. Expected a path, tuple or array",
+        )),
+    }
+}
+
+fn build_path_generator(type_path: &syn::TypePath, props: &mut Props) -> syn::Result<Generator> {
+    let Some(segment) = type_path.path.segments.last() else {
+        return Err(syn::Error::new_spanned(
+            type_path,
+            "No segments found in type path",
+        ));
+    };
+    if type_path.qself.is_some() {
+        return Ok(build_default_generator(type_path, props));
+    }
+    if type_path.path.is_ident("Self") {
+        return Ok(build_self_generator());
+    }
+
+    let generic = || {
+        extract_generic_type(segment).ok_or_else(|| {
+            syn::Error::new_spanned(
+                segment,
+                format!("No generic type found for {}", segment.ident),
+            )
+        })
+    };
+
+    match segment.ident.to_string().as_str() {
+        "LE" | "BE" | "Var" => Ok(build_number_generator(&generic()?)),
+        "Vec" => {
+            let item_type = generic()?;
+            match props.until.take() {
+                Some(until) => build_until_generator(&item_type, &until, props),
+                None => {
+                    let length_type = take_length_type(segment, props)?;
+                    let item = build_generator(&item_type, props)?;
+                    Ok(build_list_generator(
+                        item,
+                        quote! { <#length_type as proto_rw::generate::MaxLen>::MAX_LEN },
+                    ))
+                }
+            }
+        }
+        "Box" | "Rc" | "Arc" => {
+            let Generator {
+                ty,
+                arbitrary,
+                strategy,
+                recursion,
+            } = build_generator(&generic()?, props)?;
+            let path = strip_generic_args(&type_path.path);
+            Ok(Generator {
+                ty: quote! { #path<#ty> },
+                arbitrary: quote! { #path::new({ #arbitrary }) },
+                strategy: quote! {
+                    proto_rw::proptest::strategy::Strategy::prop_map(#strategy, #path::new)
+                },
+                recursion,
+            })
+        }
+        "Option" => {
+            let Generator {
+                ty,
+                arbitrary,
+                strategy,
+                recursion,
+            } = build_generator(&generic()?, props)?;
+            let path = strip_generic_args(&type_path.path);
+            let u = unstructured();
+            let can_recurse = build_recursion_check(recursion);
+            Ok(Generator {
+                ty: quote! { #path<#ty> },
+                arbitrary: quote! {
+                    if #can_recurse #u.arbitrary::<bool>()? {
+                        Some({ #arbitrary })
+                    } else {
+                        None
+                    }
+                },
+                strategy: build_empty_leaf(
+                    quote! { proto_rw::proptest::option::of(#strategy) },
+                    quote! { || None },
+                    recursion,
+                ),
+                recursion: recursion.min(Recursion::Optional),
+            })
+        }
+        "HashMap" | "BTreeMap" => {
+            let [key_type, item_type] =
+                extract_generic_types(segment).try_into().map_err(|_| {
+                    syn::Error::new_spanned(
+                        segment,
+                        format!("Expected key and value types for {}", segment.ident),
+                    )
+                })?;
+            let length_type = take_length_type(segment, props)?;
+            let key = build_generator(&key_type, props)?;
+            let item = build_generator(&item_type, props)?;
+            let path = strip_generic_args(&type_path.path);
+            Ok(build_map_generator(
+                quote! { #path },
+                key,
+                item,
+                quote! { <#length_type as proto_rw::generate::MaxLen>::MAX_LEN },
+            ))
+        }
+        "u16" | "u32" | "u64" | "u128" | "i16" | "i32" | "i64" | "i128" | "f32" | "f64"
+            if props.defaults.endian.is_some() =>
+        {
+            Ok(build_number_generator(&Type::Path(type_path.clone())))
+        }
+        "u8" | "i8" | "u16" | "u32" | "u64" | "u128" | "i16" | "i32" | "i64" | "i128" | "f32"
+        | "f64"
+            if !props.transparent && !matches!(props.convert.front(), Some(Some(_))) =>
+        {
+            Ok(build_number_generator(&Type::Path(type_path.clone())))
+        }
+        _ => Ok(build_default_generator(type_path, props)),
+    }
+}
+
+/// Floats are never NaN, which isn't equal to itself and so can't be read back as written.
+fn build_number_generator(ty: &Type) -> Generator {
+    let u = unstructured();
+    let is_float = matches!(ty, Type::Path(type_path) if type_path.path.is_ident("f32") || type_path.path.is_ident("f64"));
+    let arbitrary = if is_float {
+        quote! {
+            {
+                let value: #ty = proto_rw::arbitrary::Arbitrary::arbitrary(#u)?;
+                if value.is_nan() {
+                    0.0
+                } else {
+                    value
+                }
+            }
+        }
+    } else {
+        quote! { <#ty as proto_rw::arbitrary::Arbitrary>::arbitrary(#u)? }
+    };
+
+    Generator {
+        ty: quote! { #ty },
+        arbitrary,
+        strategy: quote! { proto_rw::proptest::arbitrary::any::<#ty>() },
+        recursion: Recursion::None,
+    }
+}
+
+/// Builds the generator of a type created by its own `Arbitrary` impls.
+pub fn build_value_generator(ty: &Type) -> Generator {
+    let u = unstructured();
+    Generator {
+        ty: quote! { #ty },
+        arbitrary: quote! { <#ty as proto_rw::arbitrary::Arbitrary>::arbitrary(#u)? },
+        strategy: quote! { proto_rw::proptest::arbitrary::any::<#ty>() },
+        recursion: Recursion::None,
+    }
+}
+
+/// Builds the generator of `Self` in the type itself, one level deeper. Its strategy is the
+/// `inner_strategy` of `prop_recursive`, as `any::<Self>()` would build itself forever.
+fn build_self_generator() -> Generator {
+    let u = unstructured();
+    let inner = inner_strategy();
+    Generator {
+        ty: quote! { Self },
+        arbitrary: quote! {
+            proto_rw::generate::recurse(|| {
+                <Self as proto_rw::arbitrary::Arbitrary>::arbitrary(#u)
+            })?
+        },
+        strategy: quote! {
+            Option::clone(&#inner).expect("Self is only generated while recursing")
+        },
+        recursion: Recursion::Required,
+    }
+}
+
+/// The condition before adding an item holding `Self`, which stops at the maximum depth.
+fn build_recursion_check(recursion: Recursion) -> TokenStream {
+    let u = unstructured();
+    match recursion {
+        Recursion::None => quote! {},
+        _ => quote! { proto_rw::generate::can_recurse(!#u.is_empty()) && },
+    }
+}
+
+/// Leaves of recursive strategies have no `Self`, so they use `empty` instead of `strategy`.
+fn build_empty_leaf(
+    strategy: TokenStream,
+    empty: TokenStream,
+    recursion: Recursion,
+) -> TokenStream {
+    if recursion == Recursion::None {
+        return strategy;
+    }
+    let inner = inner_strategy();
+    quote! {
+        match &#inner {
+            Some(_) => proto_rw::proptest::strategy::Strategy::boxed(#strategy),
+            None => proto_rw::proptest::strategy::Strategy::boxed(
+                proto_rw::proptest::strategy::LazyJust::new(#empty),
+            ),
+        }
+    }
+}
+
+fn build_default_generator(type_path: &syn::TypePath, props: &mut Props) -> Generator {
+    let ty = Type::Path(type_path.clone());
+    let Generator {
+        arbitrary,
+        strategy,
+        ..
+    } = build_value_generator(&ty);
+
+    if std::mem::take(&mut props.transparent) {
+        return Generator {
+            ty: quote! { <#ty as proto_rw::ProtoRwWrapper>::Inner },
+            arbitrary: quote! { proto_rw::ProtoRwWrapper::into_inner(#arbitrary) },
+            strategy: quote! {
+                proto_rw::proptest::strategy::Strategy::prop_map(
+                    #strategy,
+                    proto_rw::ProtoRwWrapper::into_inner,
+                )
+            },
+            recursion: Recursion::None,
+        };
+    }
+
+    if let Some(Some(convert_type)) = props.convert.pop_front() {
+        return Generator {
+            ty: quote! { #convert_type },
+            arbitrary: quote! { <#convert_type>::from(#arbitrary) },
+            strategy: quote! {
+                proto_rw::proptest::strategy::Strategy::prop_map(#strategy, <#convert_type>::from)
+            },
+            recursion: Recursion::None,
+        };
+    }
+
+    Generator {
+        ty: quote! { #ty },
+        arbitrary,
+        strategy,
+        recursion: Recursion::None,
+    }
+}
+
+/// Builds the generator of a `Vec` with up to `max_len` items.
+pub fn build_list_generator(item: Generator, max_len: TokenStream) -> Generator {
+    let Generator {
+        ty: item_type,
+        arbitrary,
+        strategy,
+        recursion,
+    } = item;
+    let u = unstructured();
+    let can_recurse = build_recursion_check(recursion);
+
+    Generator {
+        ty: quote! { Vec<#item_type> },
+        arbitrary: quote! {
+            {
+                let max_len: usize = #max_len;
+                let mut vec = Vec::new();
+                while vec.len() < max_len && #can_recurse #u.arbitrary::<bool>()? {
+                    vec.push({ #arbitrary });
+                }
+                vec
+            }
+        },
+        strategy: build_empty_leaf(
+            quote! {
+                proto_rw::proptest::collection::vec(
+                    #strategy,
+                    0..=core::cmp::min(#max_len, proto_rw::generate::MAX_COLLECTION_LEN),
+                )
+            },
+            quote! { Vec::new },
+            recursion,
+        ),
+        recursion: recursion.min(Recursion::Optional),
+    }
+}
+
+fn build_map_generator(
+    path: TokenStream,
+    key: Generator,
+    item: Generator,
+    max_len: TokenStream,
+) -> Generator {
+    let (key_type, item_type) = (&key.ty, &item.ty);
+    let ty = quote! { #path<#key_type, #item_type> };
+    let (key_arbitrary, item_arbitrary) = (&key.arbitrary, &item.arbitrary);
+    let (key_strategy, item_strategy) = (&key.strategy, &item.strategy);
+    let recursion = key.recursion.max(item.recursion);
+    let u = unstructured();
+    let can_recurse = build_recursion_check(recursion);
+
+    Generator {
+        arbitrary: quote! {
+            {
+                let max_len: usize = #max_len;
+                let mut map = <#ty as Default>::default();
+                while map.len() < max_len && #can_recurse #u.arbitrary::<bool>()? {
+                    let key = { #key_arbitrary };
+                    let item = { #item_arbitrary };
+                    map.insert(key, item);
+                }
+                map
+            }
+        },
+        strategy: build_empty_leaf(
+            quote! {
+                proto_rw::proptest::strategy::Strategy::prop_map(
+                    proto_rw::proptest::collection::vec(
+                        (#key_strategy, #item_strategy),
+                        0..=core::cmp::min(#max_len, proto_rw::generate::MAX_COLLECTION_LEN),
+                    ),
+                    |items| items.into_iter().collect::<#ty>(),
+                )
+            },
+            quote! { <#ty as Default>::default },
+            recursion,
+        ),
+        recursion: recursion.min(Recursion::Optional),
+        ty,
+    }
+}
+
+/// Lists ending with a terminator leave out its first byte, so it can't appear early. Lists
+/// ending with a predicate would need their last item to match it, so they aren't supported.
+fn build_until_generator(
+    item_type: &Type,
+    until: &Until,
+    props: &mut Props,
+) -> syn::Result<Generator> {
+    match until {
+        Until::Eof => {
+            let item = build_generator(item_type, props)?;
+            Ok(build_list_generator(item, quote! { usize::MAX }))
+        }
+        Until::Value(terminator) => {
+            if !matches!(item_type, Type::Path(type_path) if type_path.path.is_ident("u8")) {
+                return Err(syn::Error::new_spanned(
+                    terminator,
+                    "Values of lists ending with a terminator can only be generated for Vec<u8>",
+                ));
+            }
+            let terminator = match terminator {
+                Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::ByteStr(_),
+                    ..
+                }) => quote! { #terminator },
+                _ => quote! { &[#terminator] },
+            };
+            let Generator {
+                ty,
+                arbitrary,
+                strategy,
+                recursion,
+            } = build_list_generator(build_number_generator(item_type), quote! { usize::MAX });
+            let remove_terminator = quote! {
+                let terminator: &[u8] = #terminator;
+                vec.retain(|byte| Some(byte) != terminator.first());
+                vec
+            };
+
+            Ok(Generator {
+                ty,
+                arbitrary: quote! {
+                    {
+                        let mut vec = #arbitrary;
+                        #remove_terminator
+                    }
+                },
+                strategy: quote! {
+                    proto_rw::proptest::strategy::Strategy::prop_map(
+                        #strategy,
+                        |mut vec| { #remove_terminator },
+                    )
+                },
+                recursion,
+            })
+        }
+        Until::Predicate(predicate) => Err(syn::Error::new_spanned(
+            predicate,
+            "Values of lists ending with #[until_item] can't be generated",
+        )),
+    }
+}
+
+/// Builds the generator of `constructor #construct`, like `Self { a, b }`, from its fields.
+pub fn build_fields_generator(
+    fields: FieldsGenerator,
+    constructor: TokenStream,
+    construct: TokenStream,
+) -> Generator {
+    let FieldsGenerator {
+        locals,
+        generators,
+        fixup,
+    } = fields;
+    let finish = quote! {
+        #[allow(clippy::redundant_closure_call)]
+        let value: Result<Self, proto_rw::error::ProtoRwError> = (|| {
+            #fixup
+            Ok(#constructor #construct)
+        })();
+    };
+
+    let types = generators.iter().map(|generator| &generator.ty);
+    let arbitrary = generators.iter().map(|generator| &generator.arbitrary);
+    let recursion = generators
+        .iter()
+        .map(|generator| generator.recursion)
+        .max()
+        .unwrap_or(Recursion::None);
+
+    // Tuples of strategies only go up to 12 items, so they are nested instead
+    let mut strategy = quote! { proto_rw::proptest::strategy::Just(()) };
+    let mut pattern = quote! { () };
+    for (local, generator) in locals.iter().zip(&generators).rev() {
+        let item = &generator.strategy;
+        strategy = quote! { (#item, #strategy) };
+        pattern = quote! { (#local, #pattern) };
+    }
+
+    Generator {
+        ty: quote! { Self },
+        arbitrary: quote! {
+            {
+                #(let #locals: #types = #arbitrary;)*
+                #finish
+                value.map_err(|_| proto_rw::arbitrary::Error::IncorrectFormat)?
+            }
+        },
+        strategy: quote! {
+            proto_rw::proptest::strategy::Strategy::prop_filter_map(
+                #strategy,
+                "the fields can't be written",
+                |#pattern| {
+                    #finish
+                    value.ok()
+                },
+            )
+        },
+        recursion,
+    }
+}
+
+/// Builds the generator of an enum, picking one of the generators of its variants.
+pub fn build_variants_generator(ident: &Ident, variants: Vec<Generator>) -> syn::Result<Generator> {
+    if variants.is_empty() {
+        return Err(syn::Error::new_spanned(
+            ident,
+            "No variant of this enum can be written in the latest version, so none can be generated",
+        ));
+    }
+
+    // Variants always holding `Self` come last, so the nesting stops by picking among the others
+    let (leaves, nodes): (Vec<_>, Vec<_>) = variants
+        .into_iter()
+        .partition(|variant| variant.recursion != Recursion::Required);
+    if leaves.is_empty() {
+        return Err(syn::Error::new_spanned(
+            ident,
+            "Every variant of this enum holds Self, so its values can't be generated",
+        ));
+    }
+
+    let u = unstructured();
+    let inner = inner_strategy();
+    let (leaf_count, count) = (leaves.len(), leaves.len() + nodes.len());
+    let indexes = 0..count;
+    let arbitrary = leaves
+        .iter()
+        .chain(&nodes)
+        .map(|variant| &variant.arbitrary);
+    let leaf_strategy = leaves.iter().map(|variant| &variant.strategy);
+    let node_strategy = nodes.iter().map(|variant| &variant.strategy);
+    let recursion = leaves
+        .iter()
+        .map(|variant| variant.recursion)
+        .chain(nodes.first().map(|_| Recursion::Optional))
+        .max()
+        .unwrap_or(Recursion::None);
+
+    let count = if nodes.is_empty() {
+        quote! { #count }
+    } else {
+        quote! {
+            if proto_rw::generate::can_recurse(!#u.is_empty()) {
+                #count
+            } else {
+                #leaf_count
+            }
+        }
+    };
+    let push_nodes = if nodes.is_empty() {
+        quote! {}
+    } else {
+        quote! {
+            if #inner.is_some() {
+                #(variants.push(proto_rw::proptest::strategy::Strategy::boxed(#node_strategy));)*
+            }
+        }
+    };
+
+    Ok(Generator {
+        ty: quote! { Self },
+        arbitrary: quote! {
+            match #u.choose_index(#count)? {
+                #(#indexes => #arbitrary,)*
+                _ => unreachable!(),
+            }
+        },
+        strategy: quote! {
+            {
+                #[allow(unused_mut)]
+                let mut variants: Vec<proto_rw::proptest::strategy::BoxedStrategy<Self>> = vec![
+                    #(proto_rw::proptest::strategy::Strategy::boxed(#leaf_strategy)),*
+                ];
+                #push_nodes
+                proto_rw::proptest::strategy::Union::new(variants)
+            }
+        },
+        recursion,
+    })
+}
+
+/// Builds the `Arbitrary` impls of a type and its round trip test.
+pub fn build_generate_impls(
+    options: GenerateOptions,
+    ident: &Ident,
+    generics: &Generics,
+    generator: Generator,
+) -> syn::Result<TokenStream> {
+    if !generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            generics,
+            "arbitrary, proptest and roundtrip_test don't support generic types",
+        ));
+    }
+
+    let Generator {
+        arbitrary,
+        mut strategy,
+        recursion,
+        ..
+    } = generator;
+    let u = unstructured();
+    let inner = inner_strategy();
+    let mut impls = vec![];
+
+    match recursion {
+        Recursion::None => {}
+        // The leaves are built without `Self`, then each level nests the ones below it
+        Recursion::Optional => {
+            strategy = quote! {
+                {
+                    let #inner: Option<proto_rw::proptest::strategy::BoxedStrategy<Self>> = None;
+                    let leaf = #strategy;
+                    proto_rw::proptest::strategy::Strategy::prop_recursive(
+                        leaf,
+                        proto_rw::generate::MAX_DEPTH as u32,
+                        proto_rw::generate::MAX_COLLECTION_LEN as u32 * 4,
+                        proto_rw::generate::MAX_COLLECTION_LEN as u32,
+                        move |#inner| {
+                            let #inner = Some(#inner);
+                            #strategy
+                        },
+                    )
+                }
+            };
+        }
+        Recursion::Required => {
+            return Err(syn::Error::new_spanned(
+                ident,
+                "Values always holding Self can't be generated, as they would never end",
+            ));
+        }
+    }
+
+    if options.arbitrary {
+        impls.push(quote! {
+            impl<'arbitrary> proto_rw::arbitrary::Arbitrary<'arbitrary> for #ident {
+                fn arbitrary(
+                    #u: &mut proto_rw::arbitrary::Unstructured<'arbitrary>,
+                ) -> proto_rw::arbitrary::Result<Self> {
+                    Ok(#arbitrary)
+                }
+            }
+        });
+    }
+
+    if options.proptest || options.roundtrip_test {
+        impls.push(quote! {
+            impl proto_rw::proptest::arbitrary::Arbitrary for #ident {
+                type Parameters = ();
+                type Strategy = proto_rw::proptest::strategy::BoxedStrategy<Self>;
+
+                fn arbitrary_with(_args: Self::Parameters) -> Self::Strategy {
+                    proto_rw::proptest::strategy::Strategy::boxed(#strategy)
+                }
+            }
+        });
+    }
+
+    if options.roundtrip_test {
+        let test = format_ident!("{}_roundtrip", snake_case(&ident.to_string()));
+        let fail = quote! {
+            |error| proto_rw::proptest::test_runner::TestCaseError::fail(error.to_string())
+        };
+        impls.push(quote! {
+            #[cfg(test)]
+            #[test]
+            fn #test() {
+                let mut runner = proto_rw::proptest::test_runner::TestRunner::new(
+                    proto_rw::proptest::test_runner::Config {
+                        source_file: Some(file!()),
+                        ..Default::default()
+                    },
+                );
+                let result = runner.run(&proto_rw::proptest::arbitrary::any::<#ident>(), |value| {
+                    let mut buf = bytes::BytesMut::new();
+                    proto_rw::ProtoRw::write_proto(&value, &mut buf).map_err(#fail)?;
+                    let mut buf = buf.freeze();
+                    let read = <#ident as proto_rw::ProtoRw>::read_proto(&mut buf).map_err(#fail)?;
+                    proto_rw::proptest::prop_assert_eq!(read, value);
+                    proto_rw::proptest::prop_assert!(
+                        !bytes::Buf::has_remaining(&buf),
+                        "{} bytes left after reading",
+                        bytes::Buf::remaining(&buf)
+                    );
+                    Ok(())
+                });
+                if let Err(error) = result {
+                    panic!("{}", error);
+                }
+            }
+        });
+    }
+
+    Ok(quote! { #(#impls)* })
+}
+
+fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 {
+                snake.push('_');
+            }
+            snake.extend(c.to_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
@@ -1,8 +1,14 @@
 use proc_macro::TokenStream;
 use quote::quote;
-use syn::{parse_macro_input, DeriveInput, Type};
+use syn::{parse_macro_input, DeriveInput};
+use util::ContainerAttrs;
 
 mod enums;
+mod fields;
+mod generate;
+mod packets;
+mod protobuf;
+mod schema;
 mod structs;
 mod util;
 
@@ -10,17 +16,135 @@ mod util;
 pub fn proto_rw(attr: TokenStream, item: TokenStream) -> TokenStream {
     let input = parse_macro_input!(item as DeriveInput);
 
+    expand(attr.into(), &input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Registers packet types by connection state and direction.
+///
+/// Each direction of a state becomes an enum named after both, like `PlayClientbound`, which
+/// reads the id and then the packet with that id. A packet type can be registered in several
+/// states and directions, with an id in each through `Packet<PlayClientbound>`.
+#[proc_macro]
+pub fn packets(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as packets::PacketsInput);
+
+    packets::build_packets(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand(
+    attr: proc_macro2::TokenStream,
+    input: &DeriveInput,
+) -> syn::Result<proc_macro2::TokenStream> {
     let vis = &input.vis;
     let ident = &input.ident;
 
     let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
 
-    let (new_declare, read_funcs, write_funcs) = match &input.data {
-        syn::Data::Struct(data) => structs::build_struct(vis, ident, data),
+    let ContainerAttrs {
+        tag,
+        defaults,
+        transparent,
+        protobuf,
+        generate,
+    } = syn::parse2(attr)?;
+
+    let (new_declare, read_funcs, write_funcs, layout, generator) = match &input.data {
+        _ if protobuf => {
+            if let Some(tag) = tag {
+                return Err(syn::Error::new_spanned(
+                    tag,
+                    "Protobuf enums are written as varints and have no tag type",
+                ));
+            }
+            if transparent {
+                return Err(syn::Error::new_spanned(
+                    ident,
+                    "Protobuf messages can't be transparent",
+                ));
+            }
+            protobuf::build_protobuf(vis, ident, &input.data, generate.any())?
+        }
+        syn::Data::Struct(data) => {
+            if let Some(tag) = tag {
+                return Err(syn::Error::new_spanned(
+                    tag,
+                    "Only enums have a tag type. Expected endian or length",
+                ));
+            }
+            structs::build_struct(
+                &input.attrs,
+                &defaults,
+                transparent,
+                vis,
+                ident,
+                data,
+                generate.any(),
+            )?
+        }
         syn::Data::Enum(data) => {
-            enums::build_enum(parse_macro_input!(attr as Type), vis, ident, data)
+            let tag = tag.ok_or_else(|| {
+                syn::Error::new_spanned(
+                    ident,
+                    "Expected the tag type of the enum, like #[proto_rw(u8)]",
+                )
+            })?;
+            if transparent {
+                return Err(syn::Error::new_spanned(
+                    ident,
+                    "Only structs with one field can be transparent",
+                ));
+            }
+            enums::build_enum(tag, &defaults, vis, ident, data, generate.any())?
+        }
+        syn::Data::Union(data) => {
+            return Err(syn::Error::new_spanned(
+                data.union_token,
+                "Unions are not supported",
+            ))
+        }
+    };
+
+    // The magic comes first, before any size prefix or tag
+    let magic = util::get_magic(&input.attrs)?;
+    let (read_funcs, write_funcs) = match &magic {
+        Some(magic) => {
+            let (read_magic, write_magic) = util::build_magic_quotes(magic, &defaults)?;
+            (
+                quote! {
+                    #read_magic
+                    #read_funcs
+                },
+                quote! {
+                    #write_magic
+                    #write_funcs
+                },
+            )
+        }
+        None => (read_funcs, write_funcs),
+    };
+
+    let magic_schema = match &magic {
+        Some(magic) => schema::build_magic_schema(magic, &defaults)?,
+        None => quote! { None },
+    };
+    let size_prefix_schema = match util::get_size_prefix(&input.attrs)? {
+        Some(size_prefix) if matches!(input.data, syn::Data::Struct(_)) && !protobuf => {
+            let length =
+                schema::build_encoding(&size_prefix.length_type, &mut util::Props::new(&defaults))?;
+            quote! { Some(#length) }
+        }
+        _ => quote! { None },
+    };
+    let name = ident.to_string();
+    let generate_impls = match generator {
+        Some(generator) => {
+            generate::build_generate_impls(generate, ident, &input.generics, generator)?
         }
-        _ => unimplemented!(),
+        None => quote! {},
     };
 
     let expanded = quote! {
@@ -28,15 +152,46 @@ pub fn proto_rw(attr: TokenStream, item: TokenStream) -> TokenStream {
 
         impl #impl_generics proto_rw::ProtoRw for #ident #type_generics #where_clause {
             fn read_proto(buf: &mut bytes::Bytes) -> Result<Self, proto_rw::error::ProtoRwError> {
-                Ok(#read_funcs)
+                Self::read_proto_ctx(buf, &proto_rw::context::Context::default())
             }
 
             fn write_proto(&self, buf: &mut bytes::BytesMut) -> Result<(), proto_rw::error::ProtoRwError> {
+                self.write_proto_ctx(buf, &proto_rw::context::Context::default())
+            }
+
+            #[allow(unused_variables)]
+            fn read_proto_ctx(
+                buf: &mut bytes::Bytes,
+                ctx: &proto_rw::context::Context,
+            ) -> Result<Self, proto_rw::error::ProtoRwError> {
+                let _depth = ctx.enter()?;
+                Ok({ #read_funcs })
+            }
+
+            #[allow(unused_variables)]
+            fn write_proto_ctx(
+                &self,
+                buf: &mut bytes::BytesMut,
+                ctx: &proto_rw::context::Context,
+            ) -> Result<(), proto_rw::error::ProtoRwError> {
                 #write_funcs
                 Ok(())
             }
         }
+
+        impl #impl_generics proto_rw::ProtoSchema for #ident #type_generics #where_clause {
+            fn schema() -> proto_rw::schema::Schema {
+                proto_rw::schema::Schema {
+                    name: String::from(#name),
+                    magic: #magic_schema,
+                    size_prefix: #size_prefix_schema,
+                    layout: #layout,
+                }
+            }
+        }
+
+        #generate_impls
     };
 
-    TokenStream::from(expanded)
+    Ok(expanded)
 }
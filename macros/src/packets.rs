@@ -0,0 +1,334 @@
+use std::collections::{HashMap, HashSet};
+
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote, ToTokens};
+use syn::{
+    braced,
+    parse::{Parse, ParseStream, Parser},
+    punctuated::Punctuated,
+    Attribute, Expr, Ident, Pat, Token, Type, Visibility,
+};
+
+use crate::util::{build_type_quotes, expected, Defaults, Errors, Props};
+
+syn::custom_keyword!(id);
+
+/// Input of `packets!`: the id type, then the packets of every state and direction.
+pub struct PacketsInput {
+    id_type: Type,
+    states: Vec<State>,
+}
+
+struct State {
+    attrs: Vec<Attribute>,
+    vis: Visibility,
+    ident: Ident,
+    directions: Vec<Direction>,
+}
+
+struct Direction {
+    attrs: Vec<Attribute>,
+    ident: Ident,
+    packets: Punctuated<PacketEntry, Token![,]>,
+}
+
+struct PacketEntry {
+    id: Expr,
+    ty: Type,
+}
+
+impl Parse for PacketsInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        expected(
+            input.parse::<id>(),
+            "Expected the id type of the packets, like id = Var<i32>;",
+        )?;
+        input.parse::<Token![=]>()?;
+        let id_type = input.parse()?;
+        input.parse::<Token![;]>()?;
+
+        let mut states = vec![];
+        while !input.is_empty() {
+            states.push(input.parse()?);
+        }
+
+        Ok(PacketsInput { id_type, states })
+    }
+}
+
+impl Parse for State {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        let vis = input.parse()?;
+        let ident = input.parse()?;
+
+        let content;
+        braced!(content in input);
+        let mut directions = vec![];
+        while !content.is_empty() {
+            directions.push(content.parse()?);
+        }
+
+        Ok(State {
+            attrs,
+            vis,
+            ident,
+            directions,
+        })
+    }
+}
+
+impl Parse for Direction {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        let ident = input.parse()?;
+
+        let content;
+        braced!(content in input);
+        let packets = content.parse_terminated(PacketEntry::parse, Token![,])?;
+
+        Ok(Direction {
+            attrs,
+            ident,
+            packets,
+        })
+    }
+}
+
+impl Parse for PacketEntry {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let id = input.parse()?;
+        input.parse::<Token![=>]>()?;
+        let ty = input.parse()?;
+
+        Ok(PacketEntry { id, ty })
+    }
+}
+
+pub fn build_packets(input: PacketsInput) -> syn::Result<TokenStream> {
+    let (id_type, read_id, write_id) =
+        build_type_quotes(&input.id_type, &mut Props::new(&Defaults::default()), None)?;
+
+    let mut errors = Errors::default();
+    let mut groups = vec![];
+    let mut packet_impls = vec![];
+
+    for state in &input.states {
+        for direction in &state.directions {
+            let vis = &state.vis;
+            let attrs = state.attrs.iter().chain(&direction.attrs);
+            let ident = format_ident!("{}{}", state.ident, direction.ident);
+
+            let mut variants = HashSet::new();
+            let mut ids = HashMap::new();
+            let mut declare = vec![];
+            let mut id_funcs = vec![];
+            let mut read_funcs = vec![];
+            let mut write_funcs = vec![];
+            let mut from_impls = vec![];
+
+            for PacketEntry { id, ty } in &direction.packets {
+                let Some((v_ident, pattern)) = errors.handle(parse_packet(id, ty)) else {
+                    continue;
+                };
+
+                if !variants.insert(v_ident.to_string()) {
+                    errors.push(syn::Error::new_spanned(
+                        ty,
+                        format!("{} is registered twice in {}", v_ident, ident),
+                    ));
+                    continue;
+                }
+                if let Some(used) = ids.insert(id_key(id), v_ident.clone()) {
+                    errors.push(syn::Error::new_spanned(
+                        id,
+                        format!(
+                            "id {} is already used by {} in {}",
+                            id.to_token_stream(),
+                            used,
+                            ident
+                        ),
+                    ));
+                    continue;
+                }
+
+                declare.push(quote! { #v_ident(#ty), });
+                id_funcs.push(quote! {
+                    Self::#v_ident(_) => <#ty as proto_rw::Packet<Self>>::ID,
+                });
+                read_funcs.push(quote! {
+                    #pattern => Self::#v_ident(<#ty>::read_proto_ctx(buf, ctx)?),
+                });
+                write_funcs.push(quote! {
+                    Self::#v_ident(packet) => packet.write_proto_ctx(buf, ctx)?,
+                });
+                from_impls.push(quote! {
+                    impl From<#ty> for #ident {
+                        fn from(packet: #ty) -> Self {
+                            Self::#v_ident(packet)
+                        }
+                    }
+                });
+                packet_impls.push(quote! {
+                    impl proto_rw::Packet<#ident> for #ty {
+                        type Id = #id_type;
+
+                        const ID: Self::Id = #id;
+                    }
+                });
+            }
+
+            groups.push(quote! {
+                #(#attrs)*
+                #vis enum #ident {
+                    #(#declare)*
+                }
+
+                impl #ident {
+                    pub fn id(&self) -> #id_type {
+                        match self {
+                            #(#id_funcs)*
+                        }
+                    }
+
+                    /// Reads the packet with the given id, which was already read from `buf`.
+                    pub fn decode(
+                        id: #id_type,
+                        buf: &mut bytes::Bytes,
+                        ctx: &proto_rw::context::Context,
+                    ) -> Result<Self, proto_rw::error::ProtoRwError> {
+                        <Self as proto_rw::ProtoRwTagged>::read_proto_tagged(&id, buf, ctx)
+                    }
+
+                    /// Writes the id of the packet, then the packet.
+                    pub fn encode(
+                        &self,
+                        buf: &mut bytes::BytesMut,
+                        ctx: &proto_rw::context::Context,
+                    ) -> Result<(), proto_rw::error::ProtoRwError> {
+                        proto_rw::ProtoRw::write_proto_ctx(self, buf, ctx)
+                    }
+                }
+
+                #(#from_impls)*
+
+                impl proto_rw::ProtoRwTagged for #ident {
+                    type Tag = #id_type;
+
+                    fn tag(
+                        &self,
+                        _ctx: &proto_rw::context::Context,
+                    ) -> Result<Self::Tag, proto_rw::error::ProtoRwError> {
+                        Ok(self.id())
+                    }
+
+                    #[allow(unused_variables)]
+                    fn read_proto_tagged(
+                        tag: &Self::Tag,
+                        buf: &mut bytes::Bytes,
+                        ctx: &proto_rw::context::Context,
+                    ) -> Result<Self, proto_rw::error::ProtoRwError> {
+                        #[allow(unreachable_patterns)]
+                        let result = match *tag {
+                            #(#read_funcs)*
+                            _ => Err(proto_rw::error::ProtoRwError::Error(format!(
+                                "Unknown packet id {:?} in {}",
+                                tag,
+                                stringify!(#ident)
+                            )))?,
+                        };
+                        Ok(result)
+                    }
+
+                    #[allow(unused_variables)]
+                    fn write_proto_untagged(
+                        &self,
+                        buf: &mut bytes::BytesMut,
+                        ctx: &proto_rw::context::Context,
+                    ) -> Result<(), proto_rw::error::ProtoRwError> {
+                        match self {
+                            #(#write_funcs)*
+                        }
+                        Ok(())
+                    }
+                }
+
+                impl proto_rw::ProtoRw for #ident {
+                    fn read_proto(buf: &mut bytes::Bytes) -> Result<Self, proto_rw::error::ProtoRwError> {
+                        Self::read_proto_ctx(buf, &proto_rw::context::Context::default())
+                    }
+
+                    fn write_proto(&self, buf: &mut bytes::BytesMut) -> Result<(), proto_rw::error::ProtoRwError> {
+                        self.write_proto_ctx(buf, &proto_rw::context::Context::default())
+                    }
+
+                    #[allow(unused_variables)]
+                    fn read_proto_ctx(
+                        buf: &mut bytes::Bytes,
+                        ctx: &proto_rw::context::Context,
+                    ) -> Result<Self, proto_rw::error::ProtoRwError> {
+                        let value = #read_id;
+                        <Self as proto_rw::ProtoRwTagged>::read_proto_tagged(&value, buf, ctx)
+                    }
+
+                    #[allow(unused_variables)]
+                    fn write_proto_ctx(
+                        &self,
+                        buf: &mut bytes::BytesMut,
+                        ctx: &proto_rw::context::Context,
+                    ) -> Result<(), proto_rw::error::ProtoRwError> {
+                        {
+                            let value = &self.id();
+                            #write_id;
+                        }
+                        proto_rw::ProtoRwTagged::write_proto_untagged(self, buf, ctx)
+                    }
+                }
+            });
+        }
+    }
+
+    errors.finish()?;
+
+    Ok(quote! {
+        #(#groups)*
+
+        #(#packet_impls)*
+    })
+}
+
+/// Returns a key comparing equal for the same id, whatever base its literals are written in.
+fn id_key(id: &Expr) -> String {
+    match id {
+        Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Int(lit),
+            ..
+        }) => lit.base10_digits().to_string(),
+        _ => id.to_token_stream().to_string(),
+    }
+}
+
+/// Returns the variant name of a packet, which is the last segment of its type, and the
+/// pattern matching its id.
+fn parse_packet(id: &Expr, ty: &Type) -> syn::Result<(Ident, Pat)> {
+    let Type::Path(type_path) = ty else {
+        return Err(syn::Error::new_spanned(
+            ty,
+            "Expected the path of a packet type",
+        ));
+    };
+    let v_ident = type_path
+        .path
+        .segments
+        .last()
+        .map(|segment| segment.ident.clone())
+        .ok_or_else(|| syn::Error::new_spanned(ty, "Expected the path of a packet type"))?;
+
+    let pattern = Pat::parse_single
+        .parse2(id.to_token_stream())
+        .map_err(|error| {
+            syn::Error::new(error.span(), format!("Id of {} is not a pattern", v_ident))
+        })?;
+
+    Ok((v_ident, pattern))
+}
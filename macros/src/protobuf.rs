@@ -0,0 +1,746 @@
+use std::collections::HashSet;
+
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote, ToTokens};
+use syn::{
+    ext::IdentExt, punctuated::Punctuated, Attribute, Data, DataEnum, Fields, LitInt, Meta, Token,
+    Type, Visibility,
+};
+
+use crate::{
+    generate::{
+        build_fields_generator, build_generator, build_variants_generator, name_self,
+        FieldsGenerator, Generator,
+    },
+    schema::{replace_self, type_display},
+    util::{expected, extract_generic_type, strip_generic_args, Defaults, Errors, Props},
+};
+
+/// How often a field can appear in a message.
+enum Label {
+    Singular,
+    Optional,
+    Repeated,
+    /// `#[oneof(...)]`: an `Option` of an enum whose variants have the listed numbers.
+    Oneof,
+}
+
+struct ProtoFieldInfo {
+    /// The field number, or every number of a oneof.
+    numbers: Vec<u32>,
+    label: Label,
+    /// Type implementing `ProtoField`.
+    wire_type: TokenStream,
+    /// Type of the items in the new struct, without `Option` or `Vec`.
+    item_type: TokenStream,
+    /// Constructor of `LE`, `BE` and `Var`, whose inner value is kept instead.
+    wrapper: Option<TokenStream>,
+    /// Name of the wire type in the schema.
+    schema_type: String,
+}
+
+/// Builds a `#[proto_rw(protobuf)]` message from a struct, or a protobuf enum from an enum
+/// with unit variants.
+pub fn build_protobuf(
+    vis: &Visibility,
+    ident: &proc_macro2::Ident,
+    data: &Data,
+    generate: bool,
+) -> syn::Result<(
+    TokenStream,
+    TokenStream,
+    TokenStream,
+    TokenStream,
+    Option<Generator>,
+)> {
+    match data {
+        Data::Struct(data) => build_message(vis, ident, &data.fields, generate),
+        Data::Enum(data) => build_enum(vis, ident, data, generate),
+        Data::Union(data) => Err(syn::Error::new_spanned(
+            data.union_token,
+            "Unions are not supported",
+        )),
+    }
+}
+
+fn build_message(
+    vis: &Visibility,
+    ident: &proc_macro2::Ident,
+    fields: &Fields,
+    generate: bool,
+) -> syn::Result<(
+    TokenStream,
+    TokenStream,
+    TokenStream,
+    TokenStream,
+    Option<Generator>,
+)> {
+    if matches!(fields, Fields::Unit) {
+        return Err(syn::Error::new_spanned(
+            ident,
+            "Unit structs are not supported",
+        ));
+    }
+
+    let mut errors = Errors::default();
+    let mut numbers = HashSet::new();
+    let mut new_fields = vec![];
+    let mut locals = vec![];
+    let mut read_arms = vec![];
+    let mut construct = vec![];
+    let mut write_funcs = vec![];
+    let mut field_schemas = vec![];
+    let mut generator = build_empty_generator();
+    let mut generated = vec![];
+
+    for (i, f) in fields.iter().enumerate() {
+        let Some(info) = errors.handle(get_field_info(&f.attrs, &f.ty, f, ident)) else {
+            continue;
+        };
+        if let Some(number) = info.numbers.iter().find(|number| !numbers.insert(**number)) {
+            errors.push(syn::Error::new_spanned(
+                f,
+                format!("Field number {} is used twice", number),
+            ));
+            continue;
+        }
+
+        let ProtoFieldInfo {
+            numbers: field_numbers,
+            label,
+            wire_type,
+            item_type,
+            wrapper,
+            schema_type,
+        } = info;
+        let local = format_ident!("field_{}", i);
+        let member = match &f.ident {
+            Some(ident) => quote! { #ident },
+            None => {
+                let index = syn::Index::from(i);
+                quote! { #index }
+            }
+        };
+        let name = match &f.ident {
+            Some(ident) => ident.unraw().to_string(),
+            None => i.to_string(),
+        };
+        field_schemas.push(build_field_schema(
+            &name,
+            &field_numbers,
+            &label,
+            &schema_type,
+        ));
+        let (unwrap, wire_value) = match &wrapper {
+            Some(wrapper) => (quote! { .0 }, quote! { &#wrapper(*value) }),
+            None => (quote! {}, quote! { value }),
+        };
+
+        let number = field_numbers[0];
+        let new_type = match label {
+            Label::Singular => quote! { #item_type },
+            Label::Optional | Label::Oneof => quote! { Option<#item_type> },
+            Label::Repeated => quote! { Vec<#item_type> },
+        };
+        let field_vis = &f.vis;
+        match &f.ident {
+            Some(ident) => new_fields.push(quote! { #field_vis #ident: #new_type, }),
+            None => new_fields.push(quote! { #field_vis #new_type, }),
+        }
+        if generate {
+            let Some(field_generator) = errors.handle(build_proto_generator(&new_type, ident))
+            else {
+                continue;
+            };
+            generator.locals.push(local.clone());
+            generator.generators.push(field_generator);
+            generated.push(match &f.ident {
+                Some(ident) => quote! { #ident: #local },
+                None => quote! { #local },
+            });
+        }
+
+        let (local_type, local_value) = match label {
+            Label::Repeated => (quote! { Vec<#item_type> }, quote! { Vec::new() }),
+            _ => (quote! { Option<#item_type> }, quote! { None }),
+        };
+        locals.push(quote! {
+            let mut #local: #local_type = #local_value;
+        });
+
+        let field_construct = match label {
+            // Missing fields have their default value, as in proto3
+            Label::Singular => quote! { #local.unwrap_or_default() },
+            _ => quote! { #local },
+        };
+        construct.push(match &f.ident {
+            Some(ident) => quote! { #ident: #field_construct },
+            None => field_construct,
+        });
+
+        match label {
+            Label::Oneof => {
+                read_arms.push(quote! {
+                    #(#field_numbers)|* => {
+                        #local = Some(<#wire_type as proto_rw::protobuf::ProtoOneof>::read_oneof(
+                            number,
+                            wire_type,
+                            buf,
+                            ctx,
+                        )?);
+                    }
+                });
+                write_funcs.push(quote! {
+                    if let Some(value) = &self.#member {
+                        proto_rw::protobuf::ProtoOneof::write_oneof(value, buf, ctx)?;
+                    }
+                });
+            }
+            Label::Repeated => {
+                read_arms.push(quote! {
+                    #number => proto_rw::protobuf::read_repeated::<#wire_type>(
+                        wire_type,
+                        buf,
+                        ctx,
+                        |item| #local.push(item #unwrap),
+                    )?,
+                });
+
+                let items = match &wrapper {
+                    Some(wrapper) => quote! { self.#member.iter().map(|value| #wrapper(*value)) },
+                    None => quote! { &self.#member },
+                };
+                write_funcs.push(quote! {
+                    proto_rw::protobuf::write_repeated::<#wire_type, _>(#number, #items, buf, ctx)?;
+                });
+            }
+            Label::Singular | Label::Optional => {
+                read_arms.push(quote! {
+                    #number => {
+                        #local = Some(
+                            proto_rw::protobuf::read_field::<#wire_type>(wire_type, buf, ctx)? #unwrap
+                        );
+                    }
+                });
+
+                let write_func = quote! {
+                    proto_rw::protobuf::write_field::<#wire_type>(#number, #wire_value, buf, ctx)?;
+                };
+                write_funcs.push(match label {
+                    Label::Optional => quote! {
+                        if let Some(value) = &self.#member {
+                            #write_func
+                        }
+                    },
+                    // Implicit presence fields aren't written at their default, as in proto3
+                    _ => quote! {
+                        {
+                            let value = &self.#member;
+                            if !proto_rw::protobuf::ProtoField::is_default(#wire_value) {
+                                #write_func
+                            }
+                        }
+                    },
+                });
+            }
+        }
+    }
+
+    errors.finish()?;
+
+    let (declare, construct, generated) = match fields {
+        Fields::Named(_) => (
+            quote! { { #(#new_fields)* } },
+            quote! { { #(#construct),* } },
+            quote! { { #(#generated),* } },
+        ),
+        _ => (
+            quote! { (#(#new_fields)*); },
+            quote! { (#(#construct),*) },
+            quote! { (#(#generated),*) },
+        ),
+    };
+    let generator = generate.then(|| build_fields_generator(generator, quote! { Self }, generated));
+
+    Ok((
+        quote! {
+            #vis struct #ident #declare
+
+            impl proto_rw::protobuf::ProtoField for #ident {
+                const WIRE_TYPE: proto_rw::protobuf::WireType = proto_rw::protobuf::WireType::Len;
+
+                fn read_field(
+                    buf: &mut bytes::Bytes,
+                    ctx: &proto_rw::context::Context,
+                ) -> Result<Self, proto_rw::error::ProtoRwError> {
+                    let mut data = proto_rw::protobuf::read_len_delimited(buf)?;
+                    <Self as proto_rw::ProtoRw>::read_proto_ctx(&mut data, ctx)
+                }
+
+                fn write_field(
+                    &self,
+                    buf: &mut bytes::BytesMut,
+                    ctx: &proto_rw::context::Context,
+                ) -> Result<(), proto_rw::error::ProtoRwError> {
+                    let mut data = bytes::BytesMut::new();
+                    proto_rw::ProtoRw::write_proto_ctx(self, &mut data, ctx)?;
+                    proto_rw::protobuf::write_len_delimited(&data, buf)
+                }
+            }
+        },
+        quote! {
+            #(#locals)*
+            // A message has no length of its own, so it runs until the end of the buffer
+            while bytes::Buf::has_remaining(buf) {
+                let (number, wire_type) = proto_rw::protobuf::read_key(buf)?;
+                match number {
+                    #(#read_arms)*
+                    _ => proto_rw::protobuf::skip_field(wire_type, buf)?,
+                }
+            }
+            Self #construct
+        },
+        quote! {
+            #(#write_funcs)*
+        },
+        quote! {
+            proto_rw::schema::Layout::Message {
+                fields: vec![#(#field_schemas),*],
+            }
+        },
+        generator,
+    ))
+}
+
+/// Builds the generator of a field from its type in the new struct. Lengths on the wire are
+/// varints, so they don't limit the values.
+fn build_proto_generator(
+    new_type: &TokenStream,
+    container: &proc_macro2::Ident,
+) -> syn::Result<Generator> {
+    let ty = name_self(&syn::parse2::<Type>(new_type.clone())?, container);
+    let defaults = Defaults {
+        length: Some(syn::parse_quote!(proto_rw::types::Var<u64>)),
+        ..Defaults::default()
+    };
+    build_generator(&ty, &mut Props::new(&defaults))
+}
+
+fn build_empty_generator() -> FieldsGenerator {
+    FieldsGenerator {
+        locals: vec![],
+        generators: vec![],
+        fixup: quote! {},
+    }
+}
+
+fn build_field_schema(name: &str, numbers: &[u32], label: &Label, ty: &str) -> TokenStream {
+    let label = match label {
+        Label::Singular => quote! { Singular },
+        Label::Optional => quote! { Optional },
+        Label::Repeated => quote! { Repeated },
+        Label::Oneof => quote! { Oneof },
+    };
+    quote! {
+        proto_rw::schema::MessageField {
+            name: String::from(#name),
+            numbers: vec![#(#numbers),*],
+            label: proto_rw::schema::Label::#label,
+            ty: String::from(#ty),
+        }
+    }
+}
+
+/// Reads the field number of a message field or oneof variant, which `spanned` reports
+/// errors on.
+fn get_field_info(
+    attrs: &[Attribute],
+    ty: &Type,
+    spanned: &dyn ToTokens,
+    container: &proc_macro2::Ident,
+) -> syn::Result<ProtoFieldInfo> {
+    let mut numbers = None;
+    let mut oneof = false;
+    for attr in attrs {
+        match &attr.meta {
+            Meta::List(list) if list.path.is_ident("field") => {
+                let lit = expected(list.parse_args::<LitInt>(), "Expected a field number")?;
+                numbers = Some(vec![lit.base10_parse::<u32>()?]);
+            }
+            Meta::List(list) if list.path.is_ident("oneof") => {
+                let lits = expected(
+                    list.parse_args_with(Punctuated::<LitInt, Token![,]>::parse_terminated),
+                    "Expected the field numbers of the oneof",
+                )?;
+                numbers = Some(
+                    lits.iter()
+                        .map(LitInt::base10_parse::<u32>)
+                        .collect::<syn::Result<_>>()?,
+                );
+                oneof = true;
+            }
+            Meta::List(list) => {
+                return Err(syn::Error::new_spanned(
+                    &list.path,
+                    "Unsupported meta found. Protobuf messages only support #[field(...)] and #[oneof(...)]",
+                ))
+            }
+            _ => {}
+        }
+    }
+    let numbers = numbers
+        .filter(|numbers| !numbers.is_empty())
+        .ok_or_else(|| {
+            syn::Error::new_spanned(
+                spanned,
+                "Missing field number. Add #[field(...)] to the field",
+            )
+        })?;
+
+    // `Vec<u8>` is a single `bytes` value
+    let (label, ty) = match generic_of(ty) {
+        Some(("Option", inner)) if oneof => (Label::Oneof, inner),
+        _ if oneof => {
+            return Err(syn::Error::new_spanned(
+                ty,
+                "Expected an Option of the oneof enum",
+            ))
+        }
+        Some(("Option", inner)) => (Label::Optional, inner),
+        Some(("Vec", inner)) if !is_ident(&inner, "u8") => (Label::Repeated, inner),
+        _ => (Label::Singular, ty.clone()),
+    };
+
+    let (wire_type, item_type, wrapper) = match (&ty, generic_of(&ty)) {
+        (Type::Path(type_path), Some(("LE" | "BE" | "Var", inner))) => {
+            let wrapper = strip_generic_args(&type_path.path);
+            (quote! { #ty }, quote! { #inner }, Some(quote! { #wrapper }))
+        }
+        _ => (quote! { #ty }, quote! { #ty }, None),
+    };
+
+    Ok(ProtoFieldInfo {
+        numbers,
+        label,
+        wire_type,
+        item_type,
+        wrapper,
+        schema_type: type_display(&replace_self(&ty, container)),
+    })
+}
+
+/// Returns the last segment of a path type with its generic type, like `Vec` and `T` of `Vec<T>`.
+fn generic_of(ty: &Type) -> Option<(&'static str, Type)> {
+    let Type::Path(type_path) = ty else {
+        return None;
+    };
+    let segment = type_path.path.segments.last()?;
+    let name = ["Option", "Vec", "LE", "BE", "Var"]
+        .into_iter()
+        .find(|name| segment.ident == name)?;
+    Some((name, extract_generic_type(segment)?))
+}
+
+fn is_ident(ty: &Type, name: &str) -> bool {
+    matches!(ty, Type::Path(type_path) if type_path.path.is_ident(name))
+}
+
+/// Protobuf enums are `int32` values, matched against the discriminants of the variants.
+fn build_enum(
+    vis: &Visibility,
+    ident: &proc_macro2::Ident,
+    data: &DataEnum,
+    generate: bool,
+) -> syn::Result<(
+    TokenStream,
+    TokenStream,
+    TokenStream,
+    TokenStream,
+    Option<Generator>,
+)> {
+    // Variants with a value are the fields of a oneof
+    if data
+        .variants
+        .iter()
+        .any(|v| !matches!(v.fields, Fields::Unit))
+    {
+        return build_oneof(vis, ident, data, generate);
+    }
+
+    let mut errors = Errors::default();
+    for v in &data.variants {
+        if !matches!(v.fields, Fields::Unit) {
+            errors.push(syn::Error::new_spanned(
+                &v.fields,
+                "Protobuf enums can only have unit variants",
+            ));
+        } else if v.discriminant.is_none() {
+            errors.push(syn::Error::new_spanned(
+                &v.ident,
+                format!("No value found for {}. Add a discriminant", v.ident),
+            ));
+        }
+    }
+    errors.finish()?;
+
+    let generator = if generate {
+        let variants = data
+            .variants
+            .iter()
+            .map(|v| {
+                let v_ident = &v.ident;
+                build_fields_generator(
+                    build_empty_generator(),
+                    quote! { Self::#v_ident },
+                    quote! {},
+                )
+            })
+            .collect();
+        Some(build_variants_generator(ident, variants)?)
+    } else {
+        None
+    };
+
+    let variants = data.variants.iter().map(|v| {
+        let v_ident = &v.ident;
+        let (_, value) = v.discriminant.as_ref().unwrap();
+        quote! { #v_ident = #value, }
+    });
+    let v_idents = data.variants.iter().map(|v| &v.ident).collect::<Vec<_>>();
+    let values = data.variants.iter().map(|v| {
+        let name = v.ident.to_string();
+        let (_, value) = v.discriminant.as_ref().unwrap();
+        let value = value.to_token_stream().to_string();
+        quote! {
+            proto_rw::schema::EnumValue {
+                name: String::from(#name),
+                value: String::from(#value),
+            }
+        }
+    });
+
+    Ok((
+        quote! {
+            #vis enum #ident {
+                #(#variants)*
+            }
+
+            impl proto_rw::protobuf::ProtoField for #ident {
+                const WIRE_TYPE: proto_rw::protobuf::WireType =
+                    proto_rw::protobuf::WireType::Varint;
+
+                fn read_field(
+                    buf: &mut bytes::Bytes,
+                    ctx: &proto_rw::context::Context,
+                ) -> Result<Self, proto_rw::error::ProtoRwError> {
+                    let value = <i32 as proto_rw::protobuf::ProtoField>::read_field(buf, ctx)?;
+                    #(
+                        if value == Self::#v_idents as i32 {
+                            return Ok(Self::#v_idents);
+                        }
+                    )*
+                    // Values added by newer schemas read as the zero value, like proto3 enums
+                    #(
+                        if Self::#v_idents as i32 == 0 {
+                            return Ok(Self::#v_idents);
+                        }
+                    )*
+                    Err(proto_rw::error::ProtoRwError::Error(format!(
+                        "Get {} while reading {}",
+                        value,
+                        stringify!(#ident)
+                    )))
+                }
+
+                fn write_field(
+                    &self,
+                    buf: &mut bytes::BytesMut,
+                    ctx: &proto_rw::context::Context,
+                ) -> Result<(), proto_rw::error::ProtoRwError> {
+                    let value = match self {
+                        #(Self::#v_idents => Self::#v_idents as i32,)*
+                    };
+                    proto_rw::protobuf::ProtoField::write_field(&value, buf, ctx)
+                }
+
+                fn is_default(&self) -> bool {
+                    match self {
+                        #(Self::#v_idents => Self::#v_idents as i32 == 0,)*
+                    }
+                }
+            }
+        },
+        quote! {
+            <Self as proto_rw::protobuf::ProtoField>::read_field(buf, ctx)?
+        },
+        quote! {
+            proto_rw::protobuf::ProtoField::write_field(self, buf, ctx)?;
+        },
+        quote! {
+            proto_rw::schema::Layout::MessageEnum {
+                values: vec![#(#values),*],
+            }
+        },
+        generator,
+    ))
+}
+
+/// Oneof enums have a variant with one value for each field, like `Name(String)`.
+fn build_oneof(
+    vis: &Visibility,
+    ident: &proc_macro2::Ident,
+    data: &DataEnum,
+    generate: bool,
+) -> syn::Result<(
+    TokenStream,
+    TokenStream,
+    TokenStream,
+    TokenStream,
+    Option<Generator>,
+)> {
+    let mut errors = Errors::default();
+    let mut numbers = HashSet::new();
+    let mut new_variants = vec![];
+    let mut read_arms = vec![];
+    let mut write_arms = vec![];
+    let mut field_schemas = vec![];
+    let mut variant_generators = vec![];
+
+    for v in &data.variants {
+        let v_ident = &v.ident;
+        let field = match &v.fields {
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => &fields.unnamed[0],
+            _ => {
+                errors.push(syn::Error::new_spanned(
+                    v,
+                    "Oneof variants must have a single unnamed field, like Name(String)",
+                ));
+                continue;
+            }
+        };
+        let Some(info) = errors.handle(get_field_info(&v.attrs, &field.ty, v, ident)) else {
+            continue;
+        };
+        let ProtoFieldInfo {
+            numbers: field_numbers,
+            label,
+            wire_type,
+            item_type,
+            wrapper,
+            schema_type,
+        } = info;
+        if !matches!(label, Label::Singular) || field_numbers.len() != 1 {
+            errors.push(syn::Error::new_spanned(
+                v,
+                "Oneof variants can't be optional, repeated or oneofs",
+            ));
+            continue;
+        }
+        let number = field_numbers[0];
+        if !numbers.insert(number) {
+            errors.push(syn::Error::new_spanned(
+                v,
+                format!("Field number {} is used twice", number),
+            ));
+            continue;
+        }
+        field_schemas.push(build_field_schema(
+            &v_ident.to_string(),
+            &field_numbers,
+            &label,
+            &schema_type,
+        ));
+
+        let (unwrap, wire_value) = match &wrapper {
+            Some(wrapper) => (quote! { .0 }, quote! { &#wrapper(*value) }),
+            None => (quote! {}, quote! { value }),
+        };
+        if generate {
+            let Some(generator) = errors.handle(build_proto_generator(&item_type, ident)) else {
+                continue;
+            };
+            let value = format_ident!("value");
+            variant_generators.push(build_fields_generator(
+                FieldsGenerator {
+                    locals: vec![value.clone()],
+                    generators: vec![generator],
+                    fixup: quote! {},
+                },
+                quote! { Self::#v_ident },
+                quote! { (#value) },
+            ));
+        }
+        new_variants.push(quote! { #v_ident(#item_type), });
+        read_arms.push(quote! {
+            #number => Self::#v_ident(
+                proto_rw::protobuf::read_field::<#wire_type>(wire_type, buf, ctx)? #unwrap
+            ),
+        });
+        write_arms.push(quote! {
+            Self::#v_ident(value) => {
+                proto_rw::protobuf::write_field::<#wire_type>(#number, #wire_value, buf, ctx)?
+            }
+        });
+    }
+
+    errors.finish()?;
+
+    let generator = if generate {
+        Some(build_variants_generator(ident, variant_generators)?)
+    } else {
+        None
+    };
+
+    Ok((
+        quote! {
+            #vis enum #ident {
+                #(#new_variants)*
+            }
+
+            impl proto_rw::protobuf::ProtoOneof for #ident {
+                fn read_oneof(
+                    number: u32,
+                    wire_type: proto_rw::protobuf::WireType,
+                    buf: &mut bytes::Bytes,
+                    ctx: &proto_rw::context::Context,
+                ) -> Result<Self, proto_rw::error::ProtoRwError> {
+                    Ok(match number {
+                        #(#read_arms)*
+                        _ => Err(proto_rw::error::ProtoRwError::Error(format!(
+                            "Get field {} while reading {}",
+                            number,
+                            stringify!(#ident)
+                        )))?,
+                    })
+                }
+
+                fn write_oneof(
+                    &self,
+                    buf: &mut bytes::BytesMut,
+                    ctx: &proto_rw::context::Context,
+                ) -> Result<(), proto_rw::error::ProtoRwError> {
+                    match self {
+                        #(#write_arms)*
+                    }
+                    Ok(())
+                }
+            }
+        },
+        // On its own, a oneof is written like a message with one field
+        quote! {
+            {
+                let (number, wire_type) = proto_rw::protobuf::read_key(buf)?;
+                <Self as proto_rw::protobuf::ProtoOneof>::read_oneof(number, wire_type, buf, ctx)?
+            }
+        },
+        quote! {
+            proto_rw::protobuf::ProtoOneof::write_oneof(self, buf, ctx)?;
+        },
+        quote! {
+            proto_rw::schema::Layout::Oneof {
+                fields: vec![#(#field_schemas),*],
+            }
+        },
+        generator,
+    ))
+}
@@ -0,0 +1,295 @@
+use proc_macro2::{Group, TokenStream, TokenTree};
+use quote::{quote, ToTokens};
+use syn::{Expr, Ident, Type};
+
+use crate::util::{extract_generic_type, Defaults, Magic, Props, Until};
+
+/// Builds an expression of the `Encoding` of a type. Like `build_type_quotes`, it takes the
+/// length types and `until` of the field from `props`.
+pub fn build_encoding(ty: &Type, props: &mut Props) -> syn::Result<TokenStream> {
+    match ty {
+        Type::Path(type_path) => build_path_encoding(type_path, props),
+        Type::Tuple(type_tuple) => {
+            let items = type_tuple
+                .elems
+                .iter()
+                .map(|elem| build_encoding(elem, props))
+                .collect::<syn::Result<Vec<_>>>()?;
+            Ok(quote! {
+                proto_rw::schema::Encoding::Tuple { items: vec![#(#items),*] }
+            })
+        }
+        Type::Array(type_array) => {
+            let len = &type_array.len;
+            let item = build_encoding(&type_array.elem, props)?;
+            Ok(quote! {
+                proto_rw::schema::Encoding::Array { len: #len, item: Box::new(#item) }
+            })
+        }
+        _ => Err(syn::Error::new_spanned(
+            ty,
+            "Unsupported type found. Expected a path, tuple or array",
+        )),
+    }
+}
+
+fn build_path_encoding(type_path: &syn::TypePath, props: &mut Props) -> syn::Result<TokenStream> {
+    let Some(segment) = type_path.path.segments.last() else {
+        return Err(syn::Error::new_spanned(
+            type_path,
+            "No segments found in type path",
+        ));
+    };
+    if type_path.qself.is_some() {
+        return Ok(build_named_encoding(&type_display(&Type::Path(
+            type_path.clone(),
+        ))));
+    }
+
+    let generic = || {
+        extract_generic_type(segment).ok_or_else(|| {
+            syn::Error::new_spanned(
+                segment,
+                format!("No generic type found for {}", segment.ident),
+            )
+        })
+    };
+    let number =
+        |name: String| quote! { proto_rw::schema::Encoding::Number { name: String::from(#name) } };
+
+    match segment.ident.to_string().as_str() {
+        "LE" | "BE" | "Var" => Ok(number(format!(
+            "{}<{}>",
+            segment.ident,
+            type_display(&generic()?)
+        ))),
+        "Vec" => {
+            let length = match props.until.take() {
+                Some(until) => build_until_length(&until),
+                None => {
+                    let length_type = props
+                        .length
+                        .pop_front()
+                        .or_else(|| props.defaults.length.clone())
+                        .ok_or_else(|| {
+                            syn::Error::new_spanned(
+                                segment,
+                                "No length type found for Vec. Add #[length(...)] to the field",
+                            )
+                        })?;
+                    build_prefix_length(&length_type, &props.defaults)?
+                }
+            };
+            let item = build_encoding(&generic()?, props)?;
+            Ok(quote! {
+                proto_rw::schema::Encoding::List { length: #length, item: Box::new(#item) }
+            })
+        }
+        "Box" | "Rc" | "Arc" => build_encoding(&generic()?, props),
+        "Option" => {
+            let value = build_encoding(&generic()?, props)?;
+            Ok(quote! {
+                proto_rw::schema::Encoding::Optional { value: Box::new(#value) }
+            })
+        }
+        "HashMap" | "BTreeMap" => {
+            let syn::PathArguments::AngleBracketed(args) = &segment.arguments else {
+                return Err(syn::Error::new_spanned(
+                    segment,
+                    format!("Expected key and value types for {}", segment.ident),
+                ));
+            };
+            let types = args
+                .args
+                .iter()
+                .filter_map(|arg| match arg {
+                    syn::GenericArgument::Type(ty) => Some(ty),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            let [key_type, value_type] = types[..] else {
+                return Err(syn::Error::new_spanned(
+                    segment,
+                    format!("Expected key and value types for {}", segment.ident),
+                ));
+            };
+
+            let length_type = props
+                .length
+                .pop_front()
+                .or_else(|| props.defaults.length.clone())
+                .ok_or_else(|| {
+                    syn::Error::new_spanned(
+                        segment,
+                        format!(
+                            "No length type found for {}. Add #[length(...)] to the field",
+                            segment.ident
+                        ),
+                    )
+                })?;
+            let length = build_encoding(&length_type, &mut Props::new(&props.defaults))?;
+            let key = build_encoding(key_type, props)?;
+            let value = build_encoding(value_type, props)?;
+            Ok(quote! {
+                proto_rw::schema::Encoding::Map {
+                    length: Box::new(#length),
+                    key: Box::new(#key),
+                    value: Box::new(#value),
+                }
+            })
+        }
+        "u16" | "u32" | "u64" | "u128" | "i16" | "i32" | "i64" | "i128" | "f32" | "f64"
+            if props.defaults.endian.is_some() =>
+        {
+            let endian = props.defaults.endian.as_ref().unwrap();
+            Ok(number(format!("{}<{}>", endian, segment.ident)))
+        }
+        // Transparent fields are written as the type as written, so they need nothing else
+        _ if std::mem::take(&mut props.transparent) => Ok(build_named_encoding(&type_display(
+            &Type::Path(type_path.clone()),
+        ))),
+        "u8" | "i8" | "u16" | "u32" | "u64" | "u128" | "i16" | "i32" | "i64" | "i128" | "f32"
+        | "f64" => Ok(number(segment.ident.to_string())),
+        "bool" => Ok(quote! { proto_rw::schema::Encoding::Bool }),
+        "String" => {
+            let length = build_prefix_length(&syn::parse_quote!(Var<u32>), &Defaults::default())?;
+            Ok(quote! { proto_rw::schema::Encoding::String { length: #length } })
+        }
+        "Const" => {
+            let syn::PathArguments::AngleBracketed(args) = &segment.arguments else {
+                return Err(syn::Error::new_spanned(segment, "Expected Const<T, V>"));
+            };
+            let (Some(syn::GenericArgument::Type(ty)), Some(value)) =
+                (args.args.first(), args.args.get(1))
+            else {
+                return Err(syn::Error::new_spanned(segment, "Expected Const<T, V>"));
+            };
+            let encoding = build_encoding(ty, &mut Props::new(&props.defaults))?;
+            Ok(build_constant(
+                encoding,
+                &value.to_token_stream().to_string(),
+            ))
+        }
+        _ => Ok(build_named_encoding(&type_display(&Type::Path(
+            type_path.clone(),
+        )))),
+    }
+}
+
+pub fn build_prefix_length(length_type: &Type, defaults: &Defaults) -> syn::Result<TokenStream> {
+    let encoding = build_encoding(length_type, &mut Props::new(defaults))?;
+    Ok(quote! {
+        proto_rw::schema::Length::Prefix { encoding: Box::new(#encoding) }
+    })
+}
+
+fn build_until_length(until: &Until) -> TokenStream {
+    match until {
+        Until::Value(value) => {
+            let value = expr_display(value);
+            quote! { proto_rw::schema::Length::Terminator { value: String::from(#value) } }
+        }
+        Until::Predicate(predicate) => {
+            let predicate = expr_display(predicate);
+            quote! { proto_rw::schema::Length::Predicate { predicate: String::from(#predicate) } }
+        }
+        Until::Eof => quote! { proto_rw::schema::Length::Eof },
+    }
+}
+
+fn build_named_encoding(name: &str) -> TokenStream {
+    quote! { proto_rw::schema::Encoding::Type { name: String::from(#name) } }
+}
+
+fn build_constant(encoding: TokenStream, value: &str) -> TokenStream {
+    quote! {
+        proto_rw::schema::Encoding::Constant(Box::new(proto_rw::schema::Constant {
+            encoding: #encoding,
+            value: String::from(#value),
+        }))
+    }
+}
+
+pub fn build_magic_schema(magic: &Magic, defaults: &Defaults) -> syn::Result<TokenStream> {
+    let (encoding, value) = match magic {
+        Magic::Bytes(bytes) => {
+            let len = bytes.value().len();
+            (
+                quote! {
+                    proto_rw::schema::Encoding::Array {
+                        len: #len,
+                        item: Box::new(proto_rw::schema::Encoding::Number {
+                            name: String::from("u8"),
+                        }),
+                    }
+                },
+                bytes.to_token_stream().to_string(),
+            )
+        }
+        Magic::Value(magic) => {
+            let (ty, expr) = &**magic;
+            (
+                build_encoding(ty, &mut Props::new(defaults))?,
+                expr_display(expr),
+            )
+        }
+    };
+
+    Ok(quote! {
+        Some(proto_rw::schema::Constant {
+            encoding: #encoding,
+            value: String::from(#value),
+        })
+    })
+}
+
+/// `Some` of an expression as written, or `None`.
+pub fn build_optional_string(expr: Option<&Expr>) -> TokenStream {
+    match expr {
+        Some(expr) => {
+            let expr = expr_display(expr);
+            quote! { Some(String::from(#expr)) }
+        }
+        None => quote! { None },
+    }
+}
+
+/// The last segment of a path type, or the whole type otherwise, without spaces.
+/// Replaces `Self` in `ty` with the container it refers to.
+pub fn replace_self(ty: &Type, container: &Ident) -> Type {
+    fn replace(tokens: TokenStream, container: &Ident) -> TokenStream {
+        tokens
+            .into_iter()
+            .map(|token| match token {
+                TokenTree::Ident(ident) if ident == "Self" => {
+                    TokenTree::Ident(Ident::new(&container.to_string(), ident.span()))
+                }
+                TokenTree::Group(group) => {
+                    let mut new_group =
+                        Group::new(group.delimiter(), replace(group.stream(), container));
+                    new_group.set_span(group.span());
+                    TokenTree::Group(new_group)
+                }
+                token => token,
+            })
+            .collect()
+    }
+
+    syn::parse2(replace(ty.to_token_stream(), container)).unwrap_or_else(|_| ty.clone())
+}
+
+pub fn type_display(ty: &Type) -> String {
+    let tokens = match ty {
+        Type::Path(type_path) if type_path.qself.is_none() => type_path
+            .path
+            .segments
+            .last()
+            .map_or_else(|| ty.to_token_stream(), ToTokens::to_token_stream),
+        _ => ty.to_token_stream(),
+    };
+    tokens.to_string().replace(' ', "")
+}
+
+pub fn expr_display(expr: &Expr) -> String {
+    expr.to_token_stream().to_string()
+}
@@ -1,105 +1,124 @@
-use crate::util::{build_type_quotes, check_meta, get_attrs_meta};
+use crate::{
+    fields::{build_fields, FieldsQuotes},
+    generate::{build_fields_generator, Generator},
+    util::{build_size_prefixed_quotes, get_size_prefix, Defaults},
+};
 use proc_macro2::TokenStream;
 use quote::quote;
-use std::collections::VecDeque;
-use syn::{DataStruct, Fields, Index, Visibility};
+use syn::{Attribute, DataStruct, Fields, Visibility};
 
 pub fn build_struct(
+    attrs: &[Attribute],
+    defaults: &Defaults,
+    transparent: bool,
     vis: &Visibility,
     ident: &proc_macro2::Ident,
     data: &DataStruct,
-) -> (TokenStream, TokenStream, TokenStream) {
-    let (new_declare, read_funcs, write_funcs) = match &data.fields {
-        Fields::Named(fields) => handle_named_fields(vis, ident, fields),
-        Fields::Unnamed(fields) => handle_unnamed_fields(vis, ident, fields),
-        Fields::Unit => panic!("Unit structs are not supported"),
+    generate: bool,
+) -> syn::Result<(
+    TokenStream,
+    TokenStream,
+    TokenStream,
+    TokenStream,
+    Option<Generator>,
+)> {
+    let semicolon = match &data.fields {
+        Fields::Named(_) => quote! {},
+        Fields::Unnamed(_) => quote! { ; },
+        Fields::Unit => {
+            return Err(syn::Error::new_spanned(
+                ident,
+                "Unit structs are not supported",
+            ))
+        }
     };
 
-    (
-        new_declare,
-        quote! { Self { #(#read_funcs)* } },
-        quote! { #(#write_funcs)* },
-    )
-}
-
-fn handle_named_fields(
-    vis: &Visibility,
-    ident: &proc_macro2::Ident,
-    fields: &syn::FieldsNamed,
-) -> (TokenStream, Vec<TokenStream>, Vec<TokenStream>) {
-    let mut new_fields = vec![];
-    let mut read_funcs = vec![];
-    let mut write_funcs = vec![];
-
-    for f in &fields.named {
-        let metas = get_attrs_meta(&f.attrs);
-        let mut props = (VecDeque::new(), VecDeque::new());
-        metas.iter().for_each(|meta| {
-            check_meta(&mut props, meta);
-        });
+    let FieldsQuotes {
+        declare,
+        types,
+        read,
+        construct,
+        pattern,
+        write,
+        schema,
+        generate,
+    } = build_fields(&data.fields, defaults, ident, generate)?;
+    let generator =
+        generate.map(|fields| build_fields_generator(fields, quote! { Self }, construct.clone()));
 
-        let ty = &f.ty;
-        let (new_field, read_func, write_func) = build_type_quotes(ty, &mut props, None);
-        let field_vis = &f.vis;
-        let field_ident = &f.ident;
+    let wrapper = if transparent {
+        build_wrapper_impl(ident, &data.fields, &types)?
+    } else {
+        quote! {}
+    };
 
-        new_fields.push(quote! {
-            #field_vis #field_ident: #new_field,
-        });
-        read_funcs.push(quote! {
-            #field_ident: { #read_func },
-        });
-        write_funcs.push(quote! {
-            let value = &self.#field_ident;
-            { #write_func; };
-        });
-    }
+    let (new_declare, read_funcs, write_funcs) = (
+        quote! {
+            #vis struct #ident #declare #semicolon
 
-    (
+            #wrapper
+        },
         quote! {
-            #vis struct #ident {
-                #(#new_fields)*
+            {
+                #read
+                Self #construct
             }
         },
+        quote! {
+            let Self #pattern = self;
+            #write
+        },
+    );
+
+    let (new_declare, read_funcs, write_funcs) = match get_size_prefix(attrs)? {
+        Some(size_prefix) => build_size_prefixed_quotes(
+            &size_prefix,
+            defaults,
+            &quote! { #ident },
+            (new_declare, read_funcs, write_funcs),
+        )?,
+        None => (new_declare, read_funcs, write_funcs),
+    };
+
+    Ok((
+        new_declare,
         read_funcs,
         write_funcs,
-    )
+        quote! { proto_rw::schema::Layout::Struct { fields: #schema } },
+        generator,
+    ))
 }
 
-fn handle_unnamed_fields(
-    vis: &Visibility,
+/// Lets `#[transparent]` fields unwrap a newtype to its only field.
+fn build_wrapper_impl(
     ident: &proc_macro2::Ident,
-    fields: &syn::FieldsUnnamed,
-) -> (TokenStream, Vec<TokenStream>, Vec<TokenStream>) {
-    let mut new_fields = vec![];
-    let mut read_funcs = vec![];
-    let mut write_funcs = vec![];
+    fields: &Fields,
+    types: &[TokenStream],
+) -> syn::Result<TokenStream> {
+    let (Some(field), 1) = (fields.iter().next(), fields.len()) else {
+        return Err(syn::Error::new_spanned(
+            ident,
+            "Transparent structs must have exactly one field",
+        ));
+    };
 
-    for (i, f) in fields.unnamed.iter().enumerate() {
-        let index = Index::from(i);
-        let metas = get_attrs_meta(&f.attrs);
-        let mut props = (VecDeque::new(), VecDeque::new());
-        metas.iter().for_each(|meta| {
-            check_meta(&mut props, meta);
-        });
+    let inner_type = &types[0];
+    let (from_inner, into_inner) = match &field.ident {
+        Some(name) => (quote! { Self { #name: inner } }, quote! { self.#name }),
+        None => (quote! { Self(inner) }, quote! { self.0 }),
+    };
 
-        let ty = &f.ty;
-        let (new_field, read_func, write_func) = build_type_quotes(ty, &mut props, None);
-        let field_vis = &f.vis;
+    Ok(quote! {
+        impl proto_rw::ProtoRwWrapper for #ident {
+            type Inner = #inner_type;
 
-        new_fields.push(quote! { #field_vis #new_field, });
-        read_funcs.push(quote! { #index: #read_func, });
-        write_funcs.push(quote! {
-            let value = &self.#index;
-            #write_func;
-        });
-    }
+            fn from_inner(inner: Self::Inner) -> Self {
+                #from_inner
+            }
 
-    (
-        quote! {
-            #vis struct #ident(#(#new_fields)*);
-        },
-        read_funcs,
-        write_funcs,
-    )
+            fn into_inner(self) -> Self::Inner {
+                #into_inner
+            }
+        }
+    })
 }
@@ -1,14 +1,201 @@
-use core::panic;
 use proc_macro2::TokenStream;
 use quote::quote;
 use std::collections::VecDeque;
-use syn::{parse::ParseStream, Attribute, Index, Meta, MetaList, PathSegment, Token, Type};
+use syn::{
+    parse::{Parse, ParseStream},
+    parse_quote, Attribute, Expr, Ident, Index, LitByteStr, LitStr, Meta, MetaList, Path,
+    PathSegment, Token, Type,
+};
+
+use crate::generate::GenerateOptions;
+
+/// Field attributes that apply while building the quotes of a type.
+#[derive(Default, Clone)]
+pub struct Props {
+    pub convert: VecDeque<Option<Type>>,
+    pub length: VecDeque<Type>,
+    pub read_with: Option<Path>,
+    pub write_with: Option<Path>,
+    pub until: Option<Until>,
+    pub transparent: bool,
+    pub defaults: Defaults,
+}
+
+/// How a `Vec` without a length prefix ends.
+#[derive(Clone)]
+pub enum Until {
+    /// `#[terminator(x)]`: a byte or byte string after the last item.
+    Value(Expr),
+    /// `#[until_item(|item| ...)]`: the last item is the first one matching the predicate.
+    Predicate(Expr),
+    /// `#[until_eof]`: the items run until the end of the buffer.
+    Eof,
+}
+
+impl Props {
+    pub fn new(defaults: &Defaults) -> Self {
+        Props {
+            defaults: defaults.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Defaults of a container, used by types that don't set them.
+#[derive(Default, Clone)]
+pub struct Defaults {
+    /// `LE` or `BE`, used for bare multi-byte numbers.
+    pub endian: Option<Ident>,
+    /// Length prefix of `Vec`s without `#[length]`.
+    pub length: Option<Type>,
+}
+
+/// Arguments of `#[proto_rw(...)]`: the tag type of enums followed by flags and
+/// `key = "value"` pairs.
+pub struct ContainerAttrs {
+    pub tag: Option<Type>,
+    pub defaults: Defaults,
+    pub transparent: bool,
+    /// Reads and writes the Protocol Buffers wire format instead.
+    pub protobuf: bool,
+    pub generate: GenerateOptions,
+}
+
+const CONTAINER_FLAGS: &[&str] = &[
+    "transparent",
+    "protobuf",
+    "arbitrary",
+    "proptest",
+    "roundtrip_test",
+];
+
+fn peek_container_key(input: ParseStream) -> bool {
+    let fork = input.fork();
+    match fork.parse::<Ident>() {
+        Ok(ident) if CONTAINER_FLAGS.iter().any(|flag| ident == flag) => {
+            fork.is_empty() || fork.peek(Token![,])
+        }
+        Ok(_) => fork.peek(Token![=]),
+        Err(_) => false,
+    }
+}
+
+impl Parse for ContainerAttrs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut tag = None;
+        let mut defaults = Defaults::default();
+        let mut transparent = false;
+        let mut protobuf = false;
+        let mut generate = GenerateOptions::default();
+
+        // A leading flag or `key =` means there is no tag type
+        if !input.is_empty() && !peek_container_key(input) {
+            tag = Some(input.parse::<Type>()?);
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+
+        while !input.is_empty() {
+            let key = input.parse::<Ident>()?;
+            if CONTAINER_FLAGS.iter().any(|flag| key == flag) {
+                match key.to_string().as_str() {
+                    "transparent" => transparent = true,
+                    "protobuf" => protobuf = true,
+                    "arbitrary" => generate.arbitrary = true,
+                    "proptest" => generate.proptest = true,
+                    _ => generate.roundtrip_test = true,
+                }
+                if !input.is_empty() {
+                    input.parse::<Token![,]>()?;
+                }
+                continue;
+            }
+
+            input.parse::<Token![=]>()?;
+            let value = input.parse::<LitStr>()?;
+
+            match key.to_string().as_str() {
+                "endian" => {
+                    let endian = match value.value().as_str() {
+                        "le" => "LE",
+                        "be" => "BE",
+                        _ => {
+                            return Err(syn::Error::new(value.span(), "Expected \"le\" or \"be\""))
+                        }
+                    };
+                    defaults.endian = Some(Ident::new(endian, value.span()));
+                }
+                "length" => defaults.length = Some(value.parse::<Type>()?),
+                _ => {
+                    return Err(syn::Error::new(
+                        key.span(),
+                        "Expected transparent, protobuf, arbitrary, proptest, roundtrip_test, endian or length",
+                    ))
+                }
+            }
+
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+
+        Ok(ContainerAttrs {
+            tag,
+            defaults,
+            transparent,
+            protobuf,
+            generate,
+        })
+    }
+}
+
+/// Collects errors so that all of them are reported at once.
+#[derive(Default)]
+pub struct Errors(Option<syn::Error>);
+
+impl Errors {
+    pub fn push(&mut self, error: syn::Error) {
+        match &mut self.0 {
+            Some(errors) => errors.combine(error),
+            None => self.0 = Some(error),
+        }
+    }
+
+    /// Keeps the error of the result, if any, and returns its value otherwise.
+    pub fn handle<T>(&mut self, result: syn::Result<T>) -> Option<T> {
+        result.map_err(|error| self.push(error)).ok()
+    }
+
+    pub fn finish(self) -> syn::Result<()> {
+        match self.0 {
+            Some(errors) => Err(errors),
+            None => Ok(()),
+        }
+    }
+}
+
+pub fn is_string_type(ty: &Type) -> bool {
+    match ty {
+        Type::Path(type_path) => type_path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "String"),
+        _ => false,
+    }
+}
+
+/// Replaces the message of a parse error with what was expected, keeping its span.
+pub fn expected<T>(result: syn::Result<T>, message: &str) -> syn::Result<T> {
+    result.map_err(|error| syn::Error::new(error.span(), message))
+}
 
 pub fn build_type_quotes(
     ty: &Type,
-    props: &mut (VecDeque<Option<Type>>, VecDeque<Type>),
+    props: &mut Props,
     index: Option<Index>,
-) -> (TokenStream, TokenStream, TokenStream) {
+) -> syn::Result<(TokenStream, TokenStream, TokenStream)> {
     let value = if let Some(index) = index {
         quote! { (&value.#index) }
     } else {
@@ -19,140 +206,643 @@ pub fn build_type_quotes(
         Type::Path(type_path) => handle_path_type(type_path, props, value),
         Type::Tuple(type_tuple) => handle_tuple_type(type_tuple, props, value),
         Type::Array(type_array) => handle_array_type(type_array, props, value),
-        _ => panic!("Unsupported type found. Expected a path or tuple"),
+        _ => Err(syn::Error::new_spanned(
+            ty,
+            "Unsupported type found. Expected a path, tuple or array",
+        )),
     }
 }
 
 fn handle_path_type(
     type_path: &syn::TypePath,
-    props: &mut (VecDeque<Option<Type>>, VecDeque<Type>),
+    props: &mut Props,
     value: proc_macro2::TokenStream,
-) -> (TokenStream, TokenStream, TokenStream) {
+) -> syn::Result<(TokenStream, TokenStream, TokenStream)> {
+    // Library types are recognised by their last segment, so qualified paths work too
     let segment = type_path
         .path
         .segments
-        .first()
+        .last()
         .cloned()
-        .expect("No segments found in type path");
+        .ok_or_else(|| syn::Error::new_spanned(type_path, "No segments found in type path"))?;
     let ident = segment.ident.clone();
+    if type_path.qself.is_some() {
+        return Ok(handle_default_type(type_path, props, value));
+    }
 
     match ident.to_string().as_str() {
-        "LE" | "BE" | "Var" => handle_numeric_type(&segment, &ident, value),
+        "LE" | "BE" | "Var" => handle_numeric_type(&type_path.path, &segment, value),
         "Vec" => handle_vec_type(&segment, props, value),
-        _ => handle_default_type(type_path, props, value),
+        "Box" | "Rc" | "Arc" => handle_pointer_type(&type_path.path, &segment, props, value),
+        "Option" => handle_option_type(&type_path.path, &segment, props, value),
+        "HashMap" | "BTreeMap" => handle_map_type(&type_path.path, &segment, props, value),
+        "u16" | "u32" | "u64" | "u128" | "i16" | "i32" | "i64" | "i128" | "f32" | "f64"
+            if props.defaults.endian.is_some() =>
+        {
+            Ok(handle_endian_default(&ident, props, value))
+        }
+        _ => Ok(handle_default_type(type_path, props, value)),
     }
 }
 
 fn handle_numeric_type(
+    path: &Path,
     segment: &PathSegment,
+    value: TokenStream,
+) -> syn::Result<(TokenStream, TokenStream, TokenStream)> {
+    let gen_type = extract_generic_type(segment).ok_or_else(|| {
+        syn::Error::new_spanned(
+            segment,
+            format!("No generic type found for {}", segment.ident),
+        )
+    })?;
+
+    let path = strip_generic_args(path);
+
+    Ok((
+        quote! { #gen_type },
+        quote! { #path::<#gen_type>::read_proto(buf)?.0 },
+        quote! { #path(*#value).write_proto(buf)? },
+    ))
+}
+
+fn handle_endian_default(
     ident: &syn::Ident,
+    props: &Props,
     value: TokenStream,
 ) -> (TokenStream, TokenStream, TokenStream) {
-    let gen_type = extract_generic_type(segment)
-        .unwrap_or_else(|| panic!("No generic type found for {}", ident));
+    let endian = &props.defaults.endian;
 
     (
-        quote! { #gen_type },
-        quote! { #ident::<#gen_type>::read_proto(buf)?.0 },
-        quote! { #ident(*#value).write_proto(buf)? },
+        quote! { #ident },
+        quote! { <proto_rw::types::#endian<#ident> as proto_rw::ProtoRw>::read_proto(buf)?.0 },
+        quote! { proto_rw::ProtoRw::write_proto(&proto_rw::types::#endian(*#value), buf)? },
     )
 }
 
 fn handle_vec_type(
     segment: &PathSegment,
-    props: &mut (VecDeque<Option<Type>>, VecDeque<Type>),
+    props: &mut Props,
     value: TokenStream,
-) -> (TokenStream, TokenStream, TokenStream) {
-    let gen_type =
-        extract_generic_type(segment).unwrap_or_else(|| panic!("No generic type found for Vec"));
-    let length_type = props.1.pop_front().expect("No length type found for Vec");
+) -> syn::Result<(TokenStream, TokenStream, TokenStream)> {
+    let gen_type = extract_generic_type(segment)
+        .ok_or_else(|| syn::Error::new_spanned(segment, "No generic type found for Vec"))?;
+    if let Some(until) = props.until.take() {
+        let (inner_type, inner_read, inner_write) = build_type_quotes(&gen_type, props, None)?;
+        return Ok(build_until_quotes(
+            &until,
+            value,
+            (inner_type, inner_read, inner_write),
+        ));
+    }
 
-    let (length_ident, length_gen_type) = extract_length_type(&length_type);
-    let (inner_type, inner_read, inner_write) = build_type_quotes(&gen_type, props, None);
+    let length_type = take_length_type(segment, props)?;
+    let (read_len, write_len) = build_length_quotes(&length_type, &props.defaults)?;
+    let (inner_type, inner_read, inner_write) = build_type_quotes(&gen_type, props, None)?;
 
-    (
+    Ok((
         quote! { Vec<#inner_type> },
         quote! {
-            let len = #length_ident::<#length_gen_type>::read_proto(buf)?.0;
-            let mut vec = Vec::with_capacity(len as usize);
+            #read_len
+            // Untrusted lengths can't reserve more items than there are bytes
+            let mut vec =
+                Vec::with_capacity(core::cmp::min(len, bytes::Buf::remaining(buf)));
             for _ in 0..len {
                 vec.push({ #inner_read });
             }
             vec
         },
         quote! {
-            let len = #value.len() as #length_gen_type;
-            #length_ident(len).write_proto(buf)?;
+            {
+                let len = #value.len();
+                #write_len
+            }
             for value in #value {
                 { #inner_write }
             }
         },
-    )
+    ))
+}
+
+pub fn take_length_type(segment: &PathSegment, props: &mut Props) -> syn::Result<Type> {
+    props
+        .length
+        .pop_front()
+        .or_else(|| props.defaults.length.clone())
+        .ok_or_else(|| {
+            syn::Error::new_spanned(
+                segment,
+                format!(
+                    "No length type found for {}. Add #[length(...)] to the field",
+                    segment.ident
+                ),
+            )
+        })
+}
+
+/// Keeps a path as written, without the generic arguments of its last segment.
+pub fn strip_generic_args(path: &Path) -> Path {
+    let mut path = path.clone();
+    if let Some(last) = path.segments.last_mut() {
+        last.arguments = syn::PathArguments::None;
+    }
+    path
+}
+
+fn handle_pointer_type(
+    path: &Path,
+    segment: &PathSegment,
+    props: &mut Props,
+    value: TokenStream,
+) -> syn::Result<(TokenStream, TokenStream, TokenStream)> {
+    let gen_type = extract_generic_type(segment).ok_or_else(|| {
+        syn::Error::new_spanned(
+            segment,
+            format!("No generic type found for {}", segment.ident),
+        )
+    })?;
+    let (inner_type, inner_read, inner_write) = build_type_quotes(&gen_type, props, None)?;
+    let path = strip_generic_args(path);
+
+    Ok((
+        quote! { #path<#inner_type> },
+        quote! { #path::new({ #inner_read }) },
+        quote! {
+            {
+                let value = &**#value;
+                #inner_write;
+            }
+        },
+    ))
+}
+
+/// Options without `#[when]` are written with a `bool` before the value.
+fn handle_option_type(
+    path: &Path,
+    segment: &PathSegment,
+    props: &mut Props,
+    value: TokenStream,
+) -> syn::Result<(TokenStream, TokenStream, TokenStream)> {
+    let gen_type = extract_generic_type(segment)
+        .ok_or_else(|| syn::Error::new_spanned(segment, "No generic type found for Option"))?;
+    let (inner_type, inner_read, inner_write) = build_type_quotes(&gen_type, props, None)?;
+    let path = strip_generic_args(path);
+
+    Ok((
+        quote! { #path<#inner_type> },
+        quote! {
+            if <bool as proto_rw::ProtoRw>::read_proto(buf)? {
+                Some({ #inner_read })
+            } else {
+                None
+            }
+        },
+        quote! {
+            match #value {
+                Some(value) => {
+                    proto_rw::ProtoRw::write_proto(&true, buf)?;
+                    #inner_write;
+                }
+                None => proto_rw::ProtoRw::write_proto(&false, buf)?,
+            }
+        },
+    ))
+}
+
+fn handle_map_type(
+    path: &Path,
+    segment: &PathSegment,
+    props: &mut Props,
+    value: TokenStream,
+) -> syn::Result<(TokenStream, TokenStream, TokenStream)> {
+    let [key_type, item_type] = extract_generic_types(segment).try_into().map_err(|_| {
+        syn::Error::new_spanned(
+            segment,
+            format!("Expected key and value types for {}", segment.ident),
+        )
+    })?;
+    let length_type = take_length_type(segment, props)?;
+
+    let (read_len, write_len) = build_length_quotes(&length_type, &props.defaults)?;
+    let (key_type, key_read, key_write) = build_type_quotes(&key_type, props, None)?;
+    let (item_type, item_read, item_write) = build_type_quotes(&item_type, props, None)?;
+    let path = strip_generic_args(path);
+    let map_type = quote! { #path<#key_type, #item_type> };
+
+    Ok((
+        map_type.clone(),
+        quote! {
+            #read_len
+            let mut map = <#map_type as Default>::default();
+            for _ in 0..len {
+                let key = { #key_read };
+                let item = { #item_read };
+                map.insert(key, item);
+            }
+            map
+        },
+        quote! {
+            {
+                let len = #value.len();
+                #write_len
+            }
+            for (key, item) in #value {
+                {
+                    let value = key;
+                    #key_write;
+                }
+                {
+                    let value = item;
+                    #item_write;
+                }
+            }
+        },
+    ))
+}
+
+fn build_until_quotes(
+    until: &Until,
+    value: TokenStream,
+    (inner_type, inner_read, inner_write): (TokenStream, TokenStream, TokenStream),
+) -> (TokenStream, TokenStream, TokenStream) {
+    let (read_items, write_items) = match until {
+        Until::Value(terminator) => {
+            // Byte strings are already slices, other values are single bytes
+            let terminator = match terminator {
+                Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::ByteStr(_),
+                    ..
+                }) => quote! { #terminator },
+                _ => quote! { &[#terminator] },
+            };
+
+            (
+                quote! {
+                    let terminator: &[u8] = #terminator;
+                    let mut vec = Vec::new();
+                    loop {
+                        if buf.starts_with(terminator) {
+                            bytes::Buf::advance(buf, terminator.len());
+                            break;
+                        }
+                        if !bytes::Buf::has_remaining(buf) {
+                            Err(proto_rw::error::ProtoRwError::UnexpectedEof)?;
+                        }
+                        vec.push({ #inner_read });
+                    }
+                    vec
+                },
+                quote! {
+                    let terminator: &[u8] = #terminator;
+                    let mut starts = Vec::new();
+                    for value in #value {
+                        starts.push(buf.len());
+                        { #inner_write }
+                    }
+                    buf.extend_from_slice(terminator);
+                    // The list ends at the first item boundary followed by the terminator
+                    if starts.into_iter().any(|start| buf[start..].starts_with(terminator)) {
+                        Err(proto_rw::error::ProtoRwError::Error(
+                            "An item of the list starts with its terminator".to_string(),
+                        ))?;
+                    }
+                },
+            )
+        }
+        Until::Predicate(predicate) => (
+            quote! {
+                let predicate: &dyn Fn(&#inner_type) -> bool = &(#predicate);
+                let mut vec = Vec::new();
+                loop {
+                    let value = { #inner_read };
+                    let done = predicate(&value);
+                    vec.push(value);
+                    if done {
+                        break;
+                    }
+                }
+                vec
+            },
+            quote! {
+                let predicate: &dyn Fn(&#inner_type) -> bool = &(#predicate);
+                let items = #value;
+                // Only the last item may end the list, or it would be cut short on read
+                let ends = items.iter().position(|item| predicate(item));
+                if ends.map(|i| i + 1) != Some(items.len()) {
+                    Err(proto_rw::error::ProtoRwError::Error(
+                        "Only the last item of the list can match until_item".to_string(),
+                    ))?;
+                }
+                for value in items {
+                    { #inner_write }
+                }
+            },
+        ),
+        Until::Eof => (
+            quote! {
+                let mut vec = Vec::new();
+                while bytes::Buf::has_remaining(buf) {
+                    vec.push({ #inner_read });
+                }
+                vec
+            },
+            quote! {
+                for value in #value {
+                    { #inner_write }
+                }
+            },
+        ),
+    };
+
+    (quote! { Vec<#inner_type> }, read_items, write_items)
 }
 
 fn handle_default_type(
     ty: &syn::TypePath,
-    props: &mut (VecDeque<Option<Type>>, VecDeque<Type>),
+    props: &mut Props,
     value: TokenStream,
 ) -> (TokenStream, TokenStream, TokenStream) {
-    if let Some(convert_type) = props.0.pop_front() {
-        if let Some(convert_type) = convert_type {
-            return (
-                quote! { #convert_type },
-                quote! { #ty::read_proto(buf)?.into() },
-                quote! { #ty::from(#value.clone()).write_proto(buf)? },
-            );
-        }
+    if std::mem::take(&mut props.transparent) {
+        return (
+            quote! { <#ty as proto_rw::ProtoRwWrapper>::Inner },
+            quote! { proto_rw::ProtoRwWrapper::into_inner(<#ty>::read_proto_ctx(buf, ctx)?) },
+            quote! {
+                <#ty as proto_rw::ProtoRwWrapper>::from_inner(#value.clone())
+                    .write_proto_ctx(buf, ctx)?
+            },
+        );
+    }
+
+    if let Some(Some(convert_type)) = props.convert.pop_front() {
+        return (
+            quote! { #convert_type },
+            quote! { <#ty>::read_proto_ctx(buf, ctx)?.into() },
+            quote! { <#ty>::from(#value.clone()).write_proto_ctx(buf, ctx)? },
+        );
     }
 
     (
         quote! { #ty },
-        quote! { #ty::read_proto(buf)? },
-        quote! { #value.write_proto(buf)? },
+        quote! { <#ty>::read_proto_ctx(buf, ctx)? },
+        quote! { #value.write_proto_ctx(buf, ctx)? },
     )
 }
 
-fn extract_length_type(length_type: &Type) -> (syn::Ident, Type) {
-    if let Type::Path(type_path) = length_type {
-        let segment = type_path
-            .path
-            .segments
-            .first()
-            .cloned()
-            .expect("No segments found in type path");
-        let ident = segment.ident.clone();
-
-        if ["LE", "BE", "Var"].contains(&ident.to_string().as_str()) {
-            let gen_type =
-                extract_generic_type(&segment).expect("No generic type found for length type");
-            (ident, gen_type)
-        } else {
-            panic!("Invalid length type found for Vec: Not LE, BE, or Var");
+/// Builds the code reading a length prefix into `len` and writing `len` as a length prefix.
+pub fn build_length_quotes(
+    length_type: &Type,
+    defaults: &Defaults,
+) -> syn::Result<(TokenStream, TokenStream)> {
+    let (len_type, read_len, write_len) =
+        build_type_quotes(length_type, &mut Props::new(defaults), None)?;
+
+    Ok((
+        quote! {
+            let len = { #read_len };
+            let len = usize::try_from(len).map_err(|_| {
+                proto_rw::error::ProtoRwError::Error(format!("Invalid length {}", len))
+            })?;
+        },
+        quote! {
+            let value = &<#len_type>::try_from(len).map_err(|_| {
+                proto_rw::error::ProtoRwError::Error(format!(
+                    "Length {} does not fit in {}",
+                    len,
+                    stringify!(#length_type)
+                ))
+            })?;
+            #write_len;
+        },
+    ))
+}
+
+pub struct SizePrefix {
+    pub length_type: Type,
+    pub skip_trailing: bool,
+}
+
+impl Parse for SizePrefix {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let length_type = input.parse::<Type>()?;
+        let mut skip_trailing = false;
+
+        if input.parse::<Token![,]>().is_ok() && !input.is_empty() {
+            let option = input.parse::<syn::Ident>()?;
+            if option != "skip_trailing" {
+                return Err(syn::Error::new(option.span(), "Expected skip_trailing"));
+            }
+            skip_trailing = true;
+            input.parse::<Token![,]>().ok();
         }
+
+        Ok(SizePrefix {
+            length_type,
+            skip_trailing,
+        })
+    }
+}
+
+pub fn get_size_prefix(attrs: &[Attribute]) -> syn::Result<Option<SizePrefix>> {
+    attrs
+        .iter()
+        .find(|attr| attr.path().is_ident("size_prefix"))
+        .map(|attr| attr.parse_args::<SizePrefix>())
+        .transpose()
+}
+
+/// Wraps the given code so the value is read from and written to a length prefixed sub-buffer.
+pub fn build_size_prefixed_quotes(
+    size_prefix: &SizePrefix,
+    defaults: &Defaults,
+    name: &TokenStream,
+    (new_field, read_func, write_func): (TokenStream, TokenStream, TokenStream),
+) -> syn::Result<(TokenStream, TokenStream, TokenStream)> {
+    let (read_len, write_len) = build_length_quotes(&size_prefix.length_type, defaults)?;
+
+    let check_trailing = if size_prefix.skip_trailing {
+        quote! {}
     } else {
-        panic!("Invalid length type found for Vec: Not a path");
+        quote! {
+            if bytes::Buf::has_remaining(&sub) {
+                Err(proto_rw::error::ProtoRwError::Error(format!(
+                    "{} bytes left after reading {}",
+                    bytes::Buf::remaining(&sub),
+                    stringify!(#name)
+                )))?;
+            }
+        }
+    };
+
+    Ok((
+        new_field,
+        quote! {
+            #read_len
+            if bytes::Buf::remaining(buf) < len {
+                Err(proto_rw::error::ProtoRwError::UnexpectedEof)?;
+            }
+            let mut sub = buf.split_to(len);
+            let value = {
+                let buf = &mut sub;
+                #read_func
+            };
+            #check_trailing
+            value
+        },
+        quote! {
+            let encoded = {
+                let mut encoded = bytes::BytesMut::new();
+                {
+                    let buf = &mut encoded;
+                    #write_func;
+                }
+                encoded
+            };
+            {
+                let len = encoded.len();
+                #write_len
+            }
+            buf.extend_from_slice(&encoded);
+        },
+    ))
+}
+
+/// Fixed value at the start of a container, from `#[magic(b"...")]` or `#[magic(Type = value)]`.
+pub enum Magic {
+    Bytes(LitByteStr),
+    Value(Box<(Type, Expr)>),
+}
+
+impl Parse for Magic {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        if input.peek(LitByteStr) {
+            return Ok(Magic::Bytes(input.parse()?));
+        }
+
+        let ty = input.parse::<Type>()?;
+        input.parse::<Token![=]>()?;
+        Ok(Magic::Value(Box::new((ty, input.parse()?))))
     }
 }
 
+pub fn get_magic(attrs: &[Attribute]) -> syn::Result<Option<Magic>> {
+    attrs
+        .iter()
+        .find(|attr| attr.path().is_ident("magic"))
+        .map(|attr| {
+            expected(
+                attr.parse_args::<Magic>(),
+                "Expected a byte string or Type = value in magic",
+            )
+        })
+        .transpose()
+}
+
+/// Builds the code checking the magic on read and writing it on write.
+pub fn build_magic_quotes(
+    magic: &Magic,
+    defaults: &Defaults,
+) -> syn::Result<(TokenStream, TokenStream)> {
+    match magic {
+        Magic::Bytes(bytes) => Ok((
+            quote! {
+                let expected: &[u8] = #bytes;
+                if bytes::Buf::remaining(buf) < expected.len() {
+                    Err(proto_rw::error::ProtoRwError::UnexpectedEof)?;
+                }
+                let found = buf.split_to(expected.len());
+                if found != expected {
+                    Err(proto_rw::error::ProtoRwError::UnexpectedValue {
+                        expected: format!("{:?}", bytes::Bytes::from_static(expected)),
+                        found: format!("{:?}", found),
+                    })?;
+                }
+            },
+            quote! {
+                buf.extend_from_slice(#bytes);
+            },
+        )),
+        Magic::Value(value) => {
+            let (ty, expected) = value.as_ref();
+            let (new_type, read_func, write_func) =
+                build_type_quotes(ty, &mut Props::new(defaults), None)?;
+
+            Ok((
+                quote! {
+                    let expected: #new_type = #expected;
+                    let found = { #read_func };
+                    if found != expected {
+                        Err(proto_rw::error::ProtoRwError::UnexpectedValue {
+                            expected: format!("{:?}", expected),
+                            found: format!("{:?}", found),
+                        })?;
+                    }
+                },
+                quote! {
+                    {
+                        let value: &#new_type = &#expected;
+                        #write_func;
+                    }
+                },
+            ))
+        }
+    }
+}
+
+/// Protocol versions set by `#[since(v)]` and `#[until(v)]`.
+#[derive(Default)]
+pub struct Versions {
+    pub since: Option<Expr>,
+    pub until: Option<Expr>,
+}
+
+pub fn get_versions(attrs: &[Attribute]) -> syn::Result<Versions> {
+    let mut versions = Versions::default();
+
+    for attr in attrs {
+        if attr.path().is_ident("since") {
+            versions.since = Some(expected(attr.parse_args(), "Expected a version in since")?);
+        } else if attr.path().is_ident("until") {
+            versions.until = Some(expected(attr.parse_args(), "Expected a version in until")?);
+        }
+    }
+
+    Ok(versions)
+}
+
+/// Builds the checks of `ctx.version` against the given versions. `since` is inclusive and
+/// `until` is exclusive.
+pub fn build_version_checks(versions: &Versions) -> Vec<TokenStream> {
+    let mut checks = vec![];
+
+    if let Some(since) = &versions.since {
+        checks.push(quote! { ctx.version >= #since });
+    }
+    if let Some(until) = &versions.until {
+        checks.push(quote! { ctx.version < #until });
+    }
+
+    checks
+}
+
 fn handle_tuple_type(
     type_tuple: &syn::TypeTuple,
-    props: &mut (VecDeque<Option<Type>>, VecDeque<Type>),
+    props: &mut Props,
     value: TokenStream,
-) -> (TokenStream, TokenStream, TokenStream) {
+) -> syn::Result<(TokenStream, TokenStream, TokenStream)> {
     let mut new_fields = vec![];
     let mut read_funcs = vec![];
     let mut write_funcs = vec![];
 
     for (i, ty) in type_tuple.elems.iter().enumerate() {
         let index = Index::from(i);
-        let (new_field, read_func, write_func) = build_type_quotes(ty, props, Some(index));
+        let (new_field, read_func, write_func) = build_type_quotes(ty, props, Some(index))?;
 
         new_fields.push(new_field);
         read_funcs.push(read_func);
         write_funcs.push(write_func);
     }
 
-    (
+    Ok((
         quote! { (#(#new_fields),*) },
         quote! { (#({ #read_funcs }),*) },
         quote! {
@@ -161,18 +851,18 @@ fn handle_tuple_type(
                 #({ #write_funcs };)*
             }
         },
-    )
+    ))
 }
 
 fn handle_array_type(
     type_array: &syn::TypeArray,
-    props: &mut (VecDeque<Option<Type>>, VecDeque<Type>),
+    props: &mut Props,
     value: TokenStream,
-) -> (TokenStream, TokenStream, TokenStream) {
+) -> syn::Result<(TokenStream, TokenStream, TokenStream)> {
     let len = &type_array.len;
-    let (inner_type, inner_read, inner_write) = build_type_quotes(&type_array.elem, props, None);
+    let (inner_type, inner_read, inner_write) = build_type_quotes(&type_array.elem, props, None)?;
 
-    (
+    Ok((
         quote! { [#inner_type; #len] },
         quote! {
             {
@@ -192,51 +882,60 @@ fn handle_array_type(
                 { #inner_write };
             }
         },
-    )
+    ))
 }
 
-pub fn get_attrs_meta(attrs: &[Attribute]) -> Vec<Meta> {
-    attrs.iter().map(|attr| attr.meta.clone()).collect()
-}
-
-pub fn check_meta(props: &mut (VecDeque<Option<Type>>, VecDeque<Type>), meta: &Meta) {
+pub fn check_meta(props: &mut Props, meta: &Meta) -> syn::Result<()> {
     let Meta::List(list) = meta else {
-        return;
+        return Ok(());
     };
 
     let ident = list
         .path
         .get_ident()
-        .expect("No ident found in meta list")
+        .ok_or_else(|| syn::Error::new_spanned(&list.path, "No ident found in meta list"))?
         .to_string();
 
     match ident.as_str() {
-        "convert" => handle_convert(list, props),
-        "length" => handle_length(list, props),
-        _ => panic!("Unsupported meta found. Expected convert or length"),
+        "convert" => handle_convert(list, props)?,
+        "length" => handle_length(list, props)?,
+        "with" => handle_with(list, props)?,
+        "read_with" => {
+            props.read_with = Some(expected(list.parse_args(), "Expected a function path")?);
+        }
+        "write_with" => {
+            props.write_with = Some(expected(list.parse_args(), "Expected a function path")?);
+        }
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &list.path,
+                "Unsupported meta found. Expected convert, length, with, read_with or write_with",
+            ))
+        }
     }
-}
 
-fn handle_convert(list: &MetaList, props: &mut (VecDeque<Option<Type>>, VecDeque<Type>)) {
-    let convert_types = list
-        .parse_args_with(|input: ParseStream| {
-            let mut types = Vec::new();
+    Ok(())
+}
 
-            while !input.is_empty() {
-                let ty = input.parse::<Type>()?;
-                let content;
-                syn::bracketed!(content in input);
-                let index = content
-                    .parse::<syn::Index>()
-                    .expect("Expected a numeric index in brackets");
-
-                types.push((ty, index.index));
-                input.parse::<Token![,]>().ok();
-            }
+fn handle_convert(list: &MetaList, props: &mut Props) -> syn::Result<()> {
+    let convert_types = list.parse_args_with(|input: ParseStream| {
+        let mut types = Vec::new();
+
+        while !input.is_empty() {
+            let ty = input.parse::<Type>()?;
+            let content;
+            syn::bracketed!(content in input);
+            let index = expected(
+                content.parse::<syn::Index>(),
+                "Expected a numeric index in brackets",
+            )?;
+
+            types.push((ty, index.index));
+            input.parse::<Token![,]>().ok();
+        }
 
-            Ok(types)
-        })
-        .expect("Expected type[index] pairs");
+        Ok(types)
+    })?;
 
     let mut convert_type_list = VecDeque::new();
     for (ty, index) in convert_types {
@@ -246,22 +945,69 @@ fn handle_convert(list: &MetaList, props: &mut (VecDeque<Option<Type>>, VecDeque
         convert_type_list.push_back(Some(ty));
     }
 
-    props.0 = convert_type_list;
+    props.convert = convert_type_list;
+    Ok(())
 }
 
-fn handle_length(list: &MetaList, props: &mut (VecDeque<Option<Type>>, VecDeque<Type>)) {
-    let length_types = list
-        .parse_args_with(|input: ParseStream| {
+fn handle_length(list: &MetaList, props: &mut Props) -> syn::Result<()> {
+    let length_types = expected(
+        list.parse_args_with(|input: ParseStream| {
             let mut types = Vec::new();
             while !input.is_empty() {
                 types.push(input.parse::<Type>()?);
                 input.parse::<Token![,]>().ok();
             }
             Ok(types)
-        })
-        .expect("Expected a list of types");
+        }),
+        "Expected a list of types",
+    )?;
+
+    props.length = VecDeque::from(length_types);
+    Ok(())
+}
 
-    props.1 = VecDeque::from(length_types);
+fn handle_with(list: &MetaList, props: &mut Props) -> syn::Result<()> {
+    let module = expected(list.parse_args::<Path>(), "Expected a module path")?;
+
+    props.read_with = Some(parse_quote! { #module::read });
+    props.write_with = Some(parse_quote! { #module::write });
+    Ok(())
+}
+
+/// Builds the quotes of a field that is read or written by user functions.
+pub fn build_with_quotes(
+    ty: &Type,
+    props: &mut Props,
+) -> syn::Result<(TokenStream, TokenStream, TokenStream)> {
+    let (new_field, read_func, write_func) = match (&props.read_with, &props.write_with) {
+        (Some(_), Some(_)) => (quote! { #ty }, quote! {}, quote! {}),
+        _ => build_type_quotes(ty, props, None)?,
+    };
+
+    let read_func = match &props.read_with {
+        Some(read_with) => quote! { #read_with(buf)? },
+        None => read_func,
+    };
+    let write_func = match &props.write_with {
+        Some(write_with) => quote! { #write_with(value, buf)? },
+        None => write_func,
+    };
+
+    Ok((new_field, read_func, write_func))
+}
+
+pub fn extract_generic_types(segment: &PathSegment) -> Vec<Type> {
+    let syn::PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return vec![];
+    };
+
+    args.args
+        .iter()
+        .filter_map(|arg| match arg {
+            syn::GenericArgument::Type(ty) => Some(ty.clone()),
+            _ => None,
+        })
+        .collect()
 }
 
 pub fn extract_generic_type(segment: &PathSegment) -> Option<Type> {
@@ -0,0 +1,391 @@
+//! Writes the parsed types as Rust types with `#[proto_rw(protobuf)]`.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write,
+};
+
+use crate::{
+    parser::{Enum, Field, Label, Message, ProtoFile},
+    Error,
+};
+
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "dyn", "else", "enum", "extern", "false",
+    "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref",
+    "return", "static", "struct", "trait", "true", "type", "unsafe", "use", "where", "while",
+    "abstract", "become", "box", "do", "final", "gen", "macro", "override", "priv", "try",
+    "typeof", "unsized", "virtual", "yield",
+];
+
+struct TypeInfo {
+    rust_name: String,
+    is_enum: bool,
+}
+
+/// Rust type of a field, without its label.
+struct FieldType {
+    name: String,
+    is_message: bool,
+    /// Messages containing themselves, directly or through other messages, are boxed, except in a
+    /// `Vec`.
+    recursive: bool,
+}
+
+impl FieldType {
+    fn boxed(&self) -> String {
+        if self.recursive {
+            format!("Box<{}>", self.name)
+        } else {
+            self.name.clone()
+        }
+    }
+}
+
+struct Generator<'a> {
+    package: Option<&'a str>,
+    /// Types by their name without the package, like `Outer.Inner`.
+    types: HashMap<String, TypeInfo>,
+    /// Messages contained without a `Vec` in each message, by Rust name.
+    contains: HashMap<String, Vec<String>>,
+    out: String,
+}
+
+pub fn generate(file: &ProtoFile) -> Result<String, Error> {
+    let mut generator = Generator {
+        package: file.package.as_deref(),
+        types: HashMap::new(),
+        contains: HashMap::new(),
+        out: String::from("// Generated by proto-rw-build. Do not edit.\n"),
+    };
+
+    for message in &file.messages {
+        generator.register_message(message, &[])?;
+    }
+    for value in &file.enums {
+        generator.register_type(&value.name, &[], true)?;
+    }
+    for message in &file.messages {
+        generator.register_contains(message, &[]);
+    }
+
+    for message in &file.messages {
+        generator.write_message(message, &[])?;
+    }
+    for value in &file.enums {
+        generator.write_enum(value, &[]);
+    }
+
+    Ok(generator.out)
+}
+
+impl Generator<'_> {
+    fn register_type(&mut self, name: &str, scope: &[&str], is_enum: bool) -> Result<(), Error> {
+        let full_name = scope
+            .iter()
+            .chain([&name])
+            .copied()
+            .collect::<Vec<_>>()
+            .join(".");
+        let rust_name = scope
+            .iter()
+            .chain([&name])
+            .map(|name| to_camel_case(name, false))
+            .collect::<String>();
+
+        if self.types.values().any(|info| info.rust_name == rust_name) {
+            return Err(Error::Schema(format!(
+                "{} is generated more than once as {}",
+                full_name, rust_name
+            )));
+        }
+        self.types
+            .insert(full_name, TypeInfo { rust_name, is_enum });
+        Ok(())
+    }
+
+    fn register_message(&mut self, message: &Message, scope: &[&str]) -> Result<(), Error> {
+        self.register_type(&message.name, scope, false)?;
+
+        let scope = [scope, &[message.name.as_str()]].concat();
+        for nested in &message.messages {
+            self.register_message(nested, &scope)?;
+        }
+        for value in &message.enums {
+            self.register_type(&value.name, &scope, true)?;
+        }
+        Ok(())
+    }
+
+    fn register_contains(&mut self, message: &Message, scope: &[&str]) {
+        let scope = [scope, &[message.name.as_str()]].concat();
+        let rust_name = self
+            .resolve(&scope.join("."), &[])
+            .unwrap()
+            .rust_name
+            .clone();
+
+        let fields = message
+            .fields
+            .iter()
+            .filter(|field| !matches!(field.label, Label::Repeated))
+            .chain(message.oneofs.iter().flat_map(|oneof| &oneof.fields));
+        // Unknown types are reported when the message is written
+        let contained = fields
+            .filter_map(|field| self.resolve(&field.ty, &scope))
+            .filter(|info| !info.is_enum)
+            .map(|info| info.rust_name.clone())
+            .collect();
+        self.contains.insert(rust_name, contained);
+
+        for nested in &message.messages {
+            self.register_contains(nested, &scope);
+        }
+    }
+
+    /// Whether message `from` contains message `to`, directly or through other messages.
+    fn reaches(&self, from: &str, to: &str) -> bool {
+        let mut seen = HashSet::new();
+        let mut stack = vec![from];
+        while let Some(name) = stack.pop() {
+            if name == to {
+                return true;
+            }
+            if seen.insert(name) {
+                stack.extend(
+                    self.contains
+                        .get(name)
+                        .into_iter()
+                        .flatten()
+                        .map(String::as_str),
+                );
+            }
+        }
+        false
+    }
+
+    /// Finds a type the way protoc does, from the innermost scope outwards.
+    fn resolve(&self, name: &str, scope: &[&str]) -> Option<&TypeInfo> {
+        let without_package = |name: &str| {
+            self.package
+                .and_then(|package| name.strip_prefix(package)?.strip_prefix('.'))
+                .map(str::to_owned)
+        };
+
+        if let Some(name) = name.strip_prefix('.') {
+            return self.types.get(&without_package(name)?);
+        }
+
+        (0..=scope.len())
+            .rev()
+            .map(|i| {
+                scope[..i]
+                    .iter()
+                    .chain([&name])
+                    .copied()
+                    .collect::<Vec<_>>()
+                    .join(".")
+            })
+            .chain(without_package(name))
+            .find_map(|candidate| self.types.get(&candidate))
+    }
+
+    fn field_type(&self, field: &Field, message: &str, scope: &[&str]) -> Result<FieldType, Error> {
+        let scalar = match field.ty.as_str() {
+            "double" => "f64",
+            "float" => "f32",
+            "int32" => "i32",
+            "int64" => "i64",
+            "uint32" => "u32",
+            "uint64" => "u64",
+            "sint32" => "proto_rw::types::Var<i32>",
+            "sint64" => "proto_rw::types::Var<i64>",
+            "fixed32" => "proto_rw::types::LE<u32>",
+            "fixed64" => "proto_rw::types::LE<u64>",
+            "sfixed32" => "proto_rw::types::LE<i32>",
+            "sfixed64" => "proto_rw::types::LE<i64>",
+            "bool" => "bool",
+            "string" => "String",
+            "bytes" => "Vec<u8>",
+            _ => "",
+        };
+        if !scalar.is_empty() {
+            return Ok(FieldType {
+                name: scalar.to_owned(),
+                is_message: false,
+                recursive: false,
+            });
+        }
+
+        let info = self.resolve(&field.ty, scope).ok_or_else(|| {
+            Error::Schema(format!(
+                "Unknown type {} of {}.{}",
+                field.ty, message, field.name
+            ))
+        })?;
+
+        // A message containing itself, directly or through other messages, needs a box. Every
+        // field on the cycle is boxed, so the result doesn't depend on the order of the fields.
+        let outer = &self.resolve(&scope.join("."), &[]).unwrap().rust_name;
+        let recursive = !info.is_enum && self.reaches(&info.rust_name, outer);
+
+        Ok(FieldType {
+            name: info.rust_name.clone(),
+            is_message: !info.is_enum,
+            recursive,
+        })
+    }
+
+    fn write_message(&mut self, message: &Message, scope: &[&str]) -> Result<(), Error> {
+        let scope = [scope, &[message.name.as_str()]].concat();
+        let rust_name = self
+            .resolve(&scope.join("."), &[])
+            .unwrap()
+            .rust_name
+            .clone();
+
+        let mut body = String::new();
+        for field in &message.fields {
+            let ty = self.field_type(field, &message.name, &scope)?;
+            // Messages are always optional in proto3
+            let ty = match field.label {
+                Label::Singular if !ty.is_message => ty.name,
+                Label::Singular | Label::Optional => format!("Option<{}>", ty.boxed()),
+                Label::Repeated => format!("Vec<{}>", ty.name),
+            };
+            writeln!(body, "    #[field({})]", field.number).unwrap();
+            writeln!(body, "    pub {}: {},", to_field_name(&field.name), ty).unwrap();
+        }
+
+        let mut oneofs = String::new();
+        for oneof in &message.oneofs {
+            let enum_name = format!("{}{}", rust_name, to_camel_case(&oneof.name, false));
+            let numbers = oneof
+                .fields
+                .iter()
+                .map(|field| field.number.to_string())
+                .collect::<Vec<_>>();
+            writeln!(body, "    #[oneof({})]", numbers.join(", ")).unwrap();
+            writeln!(
+                body,
+                "    pub {}: Option<{}>,",
+                to_field_name(&oneof.name),
+                enum_name
+            )
+            .unwrap();
+
+            writeln!(oneofs).unwrap();
+            writeln!(oneofs, "#[derive(Debug, Clone, PartialEq)]").unwrap();
+            writeln!(oneofs, "#[proto_rw::macros::proto_rw(protobuf)]").unwrap();
+            writeln!(oneofs, "pub enum {} {{", enum_name).unwrap();
+            for field in &oneof.fields {
+                let ty = self.field_type(field, &message.name, &scope)?;
+                writeln!(oneofs, "    #[field({})]", field.number).unwrap();
+                writeln!(
+                    oneofs,
+                    "    {}({}),",
+                    to_camel_case(&field.name, false),
+                    ty.boxed()
+                )
+                .unwrap();
+            }
+            writeln!(oneofs, "}}").unwrap();
+        }
+
+        writeln!(self.out).unwrap();
+        writeln!(self.out, "#[derive(Debug, Clone, PartialEq, Default)]").unwrap();
+        writeln!(self.out, "#[proto_rw::macros::proto_rw(protobuf)]").unwrap();
+        writeln!(self.out, "pub struct {} {{", rust_name).unwrap();
+        self.out.push_str(&body);
+        writeln!(self.out, "}}").unwrap();
+        self.out.push_str(&oneofs);
+
+        for nested in &message.messages {
+            self.write_message(nested, &scope)?;
+        }
+        for value in &message.enums {
+            self.write_enum(value, &scope);
+        }
+        Ok(())
+    }
+
+    fn write_enum(&mut self, value: &Enum, scope: &[&str]) {
+        let scope = [scope, &[value.name.as_str()]].concat();
+        let rust_name = self
+            .resolve(&scope.join("."), &[])
+            .unwrap()
+            .rust_name
+            .clone();
+        // Values are usually prefixed with the enum name, like KIND_TEXT in Kind
+        let prefix = format!("{}_", to_screaming_case(&value.name));
+
+        writeln!(self.out).unwrap();
+        writeln!(
+            self.out,
+            "#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]"
+        )
+        .unwrap();
+        writeln!(self.out, "#[proto_rw::macros::proto_rw(protobuf)]").unwrap();
+        writeln!(self.out, "pub enum {} {{", rust_name).unwrap();
+
+        let mut numbers = vec![];
+        for (name, number) in &value.values {
+            // Aliases share a number, which Rust enums can't
+            if numbers.contains(number) {
+                continue;
+            }
+            numbers.push(*number);
+
+            let name = name
+                .strip_prefix(&prefix)
+                .filter(|name| name.starts_with(|c: char| c.is_ascii_alphabetic()))
+                .unwrap_or(name);
+            if *number == 0 {
+                writeln!(self.out, "    #[default]").unwrap();
+            }
+            writeln!(self.out, "    {} = {},", to_camel_case(name, true), number).unwrap();
+        }
+        writeln!(self.out, "}}").unwrap();
+    }
+}
+
+/// `foo_bar` to `FooBar`. With `lower_rest`, `FOO_BAR` becomes `FooBar` too.
+fn to_camel_case(name: &str, lower_rest: bool) -> String {
+    name.split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            let first = chars.next().unwrap().to_ascii_uppercase();
+            let rest = chars.as_str();
+            if lower_rest {
+                format!("{}{}", first, rest.to_ascii_lowercase())
+            } else {
+                format!("{}{}", first, rest)
+            }
+        })
+        .collect()
+}
+
+/// `FooBar` to `FOO_BAR`.
+fn to_screaming_case(name: &str) -> String {
+    let mut screaming = String::new();
+    let mut last_lower = false;
+    for c in name.chars() {
+        if c.is_ascii_uppercase() && last_lower {
+            screaming.push('_');
+        }
+        last_lower = c.is_ascii_lowercase() || c.is_ascii_digit();
+        screaming.push(c.to_ascii_uppercase());
+    }
+    screaming
+}
+
+/// `fooBar` to `foo_bar`, escaping keywords.
+fn to_field_name(name: &str) -> String {
+    let snake = to_screaming_case(name).to_ascii_lowercase();
+    match snake.as_str() {
+        "self" | "super" | "crate" => format!("{}_", snake),
+        _ if KEYWORDS.contains(&snake.as_str()) => format!("r#{}", snake),
+        _ => snake,
+    }
+}
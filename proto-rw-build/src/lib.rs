@@ -0,0 +1,151 @@
+//! Generates `#[proto_rw(protobuf)]` types from proto3 files, without `protoc`.
+//!
+//! Call it from `build.rs` and include the generated file, which is named after the package of
+//! the protos:
+//!
+//! ```ignore
+//! // build.rs
+//! proto_rw_build::compile_protos(&["protos/chat.proto"]).unwrap();
+//!
+//! // src/lib.rs
+//! include!(concat!(env!("OUT_DIR"), "/chat.rs"));
+//! ```
+
+use std::path::{Path, PathBuf};
+
+mod codegen;
+mod parser;
+
+#[derive(Default)]
+pub struct Builder {
+    out_dir: Option<PathBuf>,
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Where the generated files are written. Defaults to `OUT_DIR`.
+    pub fn out_dir(mut self, out_dir: impl Into<PathBuf>) -> Self {
+        self.out_dir = Some(out_dir.into());
+        self
+    }
+
+    /// Writes a file for each package, or for each file without a package. Files of the same
+    /// package can refer to each other's types.
+    pub fn compile(&self, protos: &[impl AsRef<Path>]) -> Result<(), Error> {
+        let out_dir = match &self.out_dir {
+            Some(out_dir) => out_dir.clone(),
+            None => std::env::var_os("OUT_DIR")
+                .map(PathBuf::from)
+                .ok_or_else(|| Error::Schema(String::from("OUT_DIR is not set")))?,
+        };
+
+        let mut outputs: Vec<(String, parser::ProtoFile)> = vec![];
+        for path in protos {
+            let path = path.as_ref();
+            println!("cargo:rerun-if-changed={}", path.display());
+
+            let source = std::fs::read_to_string(path)?;
+            let file = parser::parse(&source).map_err(|error| error.in_file(path))?;
+            let name = match &file.package {
+                Some(package) => package.clone(),
+                None => path
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+            };
+
+            match outputs.iter_mut().find(|(output, _)| *output == name) {
+                Some((_, merged)) => {
+                    merged.messages.extend(file.messages);
+                    merged.enums.extend(file.enums);
+                }
+                None => outputs.push((name, file)),
+            }
+        }
+
+        for (name, file) in outputs {
+            let code = codegen::generate(&file)?;
+            std::fs::write(out_dir.join(format!("{}.rs", name)), code)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compiles the protos into `OUT_DIR`.
+pub fn compile_protos(protos: &[impl AsRef<Path>]) -> Result<(), Error> {
+    Builder::new().compile(protos)
+}
+
+/// Returns the Rust code for the source of a proto file.
+pub fn generate(source: &str) -> Result<String, Error> {
+    codegen::generate(&parser::parse(source)?)
+}
+
+pub enum Error {
+    IoError(std::io::Error),
+    /// Invalid or unsupported syntax.
+    Parse {
+        file: Option<PathBuf>,
+        line: usize,
+        message: String,
+    },
+    /// Types that can't be generated, like fields of unknown types.
+    Schema(String),
+}
+
+impl Error {
+    fn parse(line: usize, message: impl Into<String>) -> Self {
+        Error::Parse {
+            file: None,
+            line,
+            message: message.into(),
+        }
+    }
+
+    fn in_file(self, path: &Path) -> Self {
+        match self {
+            Error::Parse { line, message, .. } => Error::Parse {
+                file: Some(path.to_owned()),
+                line,
+                message,
+            },
+            error => error,
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::IoError(e)
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Error::IoError(e) => write!(f, "Io error: {}", e),
+            Error::Parse {
+                file: Some(file),
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", file.display(), line, message),
+            Error::Parse {
+                file: None,
+                line,
+                message,
+            } => write!(f, "Line {}: {}", line, message),
+            Error::Schema(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::fmt::Debug for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
@@ -0,0 +1,440 @@
+//! A parser for the parts of proto3 files that describe types.
+
+use crate::Error;
+
+#[derive(Debug, Default)]
+pub struct ProtoFile {
+    pub package: Option<String>,
+    pub messages: Vec<Message>,
+    pub enums: Vec<Enum>,
+}
+
+#[derive(Debug)]
+pub struct Message {
+    pub name: String,
+    pub fields: Vec<Field>,
+    pub oneofs: Vec<Oneof>,
+    pub messages: Vec<Message>,
+    pub enums: Vec<Enum>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Label {
+    Singular,
+    Optional,
+    Repeated,
+}
+
+#[derive(Debug)]
+pub struct Field {
+    pub name: String,
+    pub label: Label,
+    /// Scalar type or message or enum name as written, like `int32` or `.pkg.Outer.Inner`.
+    pub ty: String,
+    pub number: u32,
+}
+
+#[derive(Debug)]
+pub struct Oneof {
+    pub name: String,
+    pub fields: Vec<Field>,
+}
+
+#[derive(Debug)]
+pub struct Enum {
+    pub name: String,
+    pub values: Vec<(String, i32)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Int(String),
+    Str(String),
+    Symbol(char),
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+}
+
+pub fn parse(source: &str) -> Result<ProtoFile, Error> {
+    let mut parser = Parser {
+        tokens: tokenize(source)?,
+        pos: 0,
+    };
+    parser.parse_file()
+}
+
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, Error> {
+    let mut tokens = vec![];
+    let mut chars = source.chars().peekable();
+    let mut line = 1;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => line += 1,
+            c if c.is_whitespace() => {}
+            '/' if chars.peek() == Some(&'/') => while chars.next_if(|c| *c != '\n').is_some() {},
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut last = ' ';
+                loop {
+                    match chars.next() {
+                        Some('/') if last == '*' => break,
+                        Some(c) => {
+                            if c == '\n' {
+                                line += 1;
+                            }
+                            last = c;
+                        }
+                        None => return Err(Error::parse(line, "Unterminated comment")),
+                    }
+                }
+            }
+            '"' | '\'' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some(end) if end == c => break,
+                        Some('\\') => value.extend(chars.next()),
+                        Some('\n') | None => return Err(Error::parse(line, "Unterminated string")),
+                        Some(c) => value.push(c),
+                    }
+                }
+                tokens.push((Token::Str(value), line));
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let mut ident = String::from(c);
+                while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_') {
+                    ident.push(c);
+                }
+                tokens.push((Token::Ident(ident), line));
+            }
+            // Floats only appear in options, which are skipped, so they are kept as they are
+            c if c.is_ascii_digit() => {
+                let mut number = String::from(c);
+                while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '.') {
+                    number.push(c);
+                }
+                tokens.push((Token::Int(number), line));
+            }
+            c => tokens.push((Token::Symbol(c), line)),
+        }
+    }
+
+    Ok(tokens)
+}
+
+impl Parser {
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .or(self.tokens.last())
+            .map_or(1, |(_, line)| *line)
+    }
+
+    fn error(&self, message: impl Into<String>) -> Error {
+        Error::parse(self.line(), message)
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).map(|(token, _)| token.clone());
+        self.pos += 1;
+        token
+    }
+
+    fn peek_ident(&self, ident: &str) -> bool {
+        matches!(self.peek(), Some(Token::Ident(value)) if value == ident)
+    }
+
+    fn eat_symbol(&mut self, symbol: char) -> bool {
+        if self.peek() == Some(&Token::Symbol(symbol)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_symbol(&mut self, symbol: char) -> Result<(), Error> {
+        if self.eat_symbol(symbol) {
+            Ok(())
+        } else {
+            Err(self.error(format!("Expected '{}'", symbol)))
+        }
+    }
+
+    fn expect_ident(&mut self) -> Result<String, Error> {
+        match self.next() {
+            Some(Token::Ident(ident)) => Ok(ident),
+            _ => {
+                self.pos -= 1;
+                Err(self.error("Expected a name"))
+            }
+        }
+    }
+
+    fn expect_int(&mut self) -> Result<i64, Error> {
+        let negative = self.eat_symbol('-');
+        let value = match self.next() {
+            Some(Token::Int(value)) => value,
+            _ => {
+                self.pos -= 1;
+                return Err(self.error("Expected an integer"));
+            }
+        };
+
+        let parsed = if let Some(hex) = value
+            .strip_prefix("0x")
+            .or_else(|| value.strip_prefix("0X"))
+        {
+            i64::from_str_radix(hex, 16)
+        } else if value.len() > 1 && value.starts_with('0') {
+            i64::from_str_radix(&value[1..], 8)
+        } else {
+            value.parse()
+        };
+        let parsed = parsed.map_err(|_| self.error(format!("Invalid integer {}", value)))?;
+
+        Ok(if negative { -parsed } else { parsed })
+    }
+
+    /// Reads a dotted name like `google.protobuf.Timestamp` or `.pkg.Message`.
+    fn expect_full_ident(&mut self) -> Result<String, Error> {
+        let mut name = String::new();
+        if self.eat_symbol('.') {
+            name.push('.');
+        }
+        name.push_str(&self.expect_ident()?);
+        while self.eat_symbol('.') {
+            name.push('.');
+            name.push_str(&self.expect_ident()?);
+        }
+        Ok(name)
+    }
+
+    /// Skips tokens up to the next `;`, along with any brackets before it.
+    fn skip_statement(&mut self) -> Result<(), Error> {
+        let mut depth = 0;
+        loop {
+            match self.next() {
+                Some(Token::Symbol(';')) if depth == 0 => return Ok(()),
+                Some(Token::Symbol('}' | ']' | ')')) if depth == 0 => {
+                    self.pos -= 1;
+                    return Err(self.error("Expected ';'"));
+                }
+                Some(Token::Symbol('{' | '[' | '(')) => depth += 1,
+                Some(Token::Symbol('}' | ']' | ')')) => depth -= 1,
+                Some(_) => {}
+                None => return Err(self.error("Expected ';'")),
+            }
+        }
+    }
+
+    /// Skips a block like `service Name { ... }`.
+    fn skip_block(&mut self) -> Result<(), Error> {
+        while !self.eat_symbol('{') {
+            if self.next().is_none() {
+                return Err(self.error("Expected '{'"));
+            }
+        }
+
+        let mut depth = 1;
+        while depth > 0 {
+            match self.next() {
+                Some(Token::Symbol('{')) => depth += 1,
+                Some(Token::Symbol('}')) => depth -= 1,
+                Some(_) => {}
+                None => return Err(self.error("Expected '}'")),
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_file(&mut self) -> Result<ProtoFile, Error> {
+        let mut file = ProtoFile::default();
+
+        while let Some(token) = self.next() {
+            let Token::Ident(keyword) = token else {
+                if token == Token::Symbol(';') {
+                    continue;
+                }
+                self.pos -= 1;
+                return Err(self.error("Expected a message, enum or statement"));
+            };
+
+            match keyword.as_str() {
+                "syntax" => {
+                    self.expect_symbol('=')?;
+                    match self.next() {
+                        Some(Token::Str(syntax)) if syntax == "proto3" => {}
+                        _ => {
+                            self.pos -= 1;
+                            return Err(self.error("Only proto3 files are supported"));
+                        }
+                    }
+                    self.expect_symbol(';')?;
+                }
+                "package" => {
+                    file.package = Some(self.expect_full_ident()?);
+                    self.expect_symbol(';')?;
+                }
+                "import" | "option" => self.skip_statement()?,
+                "message" => file.messages.push(self.parse_message()?),
+                "enum" => file.enums.push(self.parse_enum()?),
+                "service" | "extend" => self.skip_block()?,
+                _ => {
+                    self.pos -= 1;
+                    return Err(self.error(format!("Unexpected {}", keyword)));
+                }
+            }
+        }
+
+        Ok(file)
+    }
+
+    fn parse_message(&mut self) -> Result<Message, Error> {
+        let mut message = Message {
+            name: self.expect_ident()?,
+            fields: vec![],
+            oneofs: vec![],
+            messages: vec![],
+            enums: vec![],
+        };
+        self.expect_symbol('{')?;
+
+        while !self.eat_symbol('}') {
+            if self.eat_symbol(';') {
+                continue;
+            }
+
+            match self.peek() {
+                Some(Token::Ident(keyword)) => match keyword.as_str() {
+                    "message" => {
+                        self.pos += 1;
+                        message.messages.push(self.parse_message()?);
+                    }
+                    "enum" => {
+                        self.pos += 1;
+                        message.enums.push(self.parse_enum()?);
+                    }
+                    "oneof" => {
+                        self.pos += 1;
+                        message.oneofs.push(self.parse_oneof()?);
+                    }
+                    "option" | "reserved" | "extensions" => self.skip_statement()?,
+                    "extend" => self.skip_block()?,
+                    "map"
+                        if self.tokens.get(self.pos + 1).map(|(token, _)| token)
+                            == Some(&Token::Symbol('<')) =>
+                    {
+                        return Err(self.error("Map fields are not supported"));
+                    }
+                    _ => message.fields.push(self.parse_field(true)?),
+                },
+                None => return Err(self.error("Expected '}'")),
+                _ => return Err(self.error("Expected a field")),
+            }
+        }
+
+        Ok(message)
+    }
+
+    fn parse_field(&mut self, allow_label: bool) -> Result<Field, Error> {
+        let label = match self.peek() {
+            Some(Token::Ident(label)) if allow_label && label == "repeated" => Label::Repeated,
+            Some(Token::Ident(label)) if allow_label && label == "optional" => Label::Optional,
+            Some(Token::Ident(label)) if label == "required" => {
+                return Err(self.error("Required fields are not supported in proto3"));
+            }
+            Some(Token::Ident(label)) if label == "group" => {
+                return Err(self.error("Groups are not supported"));
+            }
+            _ => Label::Singular,
+        };
+        if label != Label::Singular {
+            self.pos += 1;
+        }
+
+        let ty = self.expect_full_ident()?;
+        let name = self.expect_ident()?;
+        self.expect_symbol('=')?;
+        let number = self.expect_int()?;
+        let number = u32::try_from(number)
+            .ok()
+            .filter(|number| (1..1 << 29).contains(number))
+            .ok_or_else(|| self.error(format!("Invalid field number {}", number)))?;
+
+        // Field options like [packed = false] don't change the types
+        self.skip_statement()?;
+
+        Ok(Field {
+            name,
+            label,
+            ty,
+            number,
+        })
+    }
+
+    fn parse_oneof(&mut self) -> Result<Oneof, Error> {
+        let mut oneof = Oneof {
+            name: self.expect_ident()?,
+            fields: vec![],
+        };
+        self.expect_symbol('{')?;
+
+        while !self.eat_symbol('}') {
+            if self.eat_symbol(';') {
+                continue;
+            }
+            if self.peek_ident("option") {
+                self.skip_statement()?;
+                continue;
+            }
+            oneof.fields.push(self.parse_field(false)?);
+        }
+
+        Ok(oneof)
+    }
+
+    fn parse_enum(&mut self) -> Result<Enum, Error> {
+        let mut value = Enum {
+            name: self.expect_ident()?,
+            values: vec![],
+        };
+        self.expect_symbol('{')?;
+
+        while !self.eat_symbol('}') {
+            if self.eat_symbol(';') {
+                continue;
+            }
+            if self.peek_ident("option") || self.peek_ident("reserved") {
+                self.skip_statement()?;
+                continue;
+            }
+
+            let name = self.expect_ident()?;
+            self.expect_symbol('=')?;
+            let number = self.expect_int()?;
+            let number = i32::try_from(number)
+                .map_err(|_| self.error(format!("Invalid enum value {}", number)))?;
+            self.skip_statement()?;
+            value.values.push((name, number));
+        }
+
+        if value.values.first().map(|(_, number)| *number) != Some(0) {
+            return Err(self.error(format!(
+                "The first value of {} must be zero in proto3",
+                value.name
+            )));
+        }
+
+        Ok(value)
+    }
+}
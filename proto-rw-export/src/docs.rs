@@ -0,0 +1,512 @@
+//! Layout tables of schemas, with the offset, wire type, size and notes of each field.
+//!
+//! Offsets are only known while every earlier field has a fixed size, so they become `var`
+//! after the first variable-size field.
+
+use std::collections::HashSet;
+
+use proto_rw::schema::{Encoding, Field, Label, Layout, Length, MessageField, Schema};
+
+/// Renders a Markdown section for each schema.
+pub fn to_markdown(schemas: &[Schema]) -> String {
+    let mut out = String::new();
+    for section in schemas
+        .iter()
+        .flat_map(|schema| build_sections(schema, schemas))
+    {
+        out.push_str(&format!(
+            "{} {}\n\n",
+            "#".repeat(section.level),
+            section.title
+        ));
+        for paragraph in &section.paragraphs {
+            out.push_str(&format!("{}\n\n", paragraph));
+        }
+
+        let Some(table) = &section.table else {
+            continue;
+        };
+        out.push_str(&format!("| {} |\n", table.headers.join(" | ")));
+        out.push_str(&format!("|{}\n", " --- |".repeat(table.headers.len())));
+        for row in &table.rows {
+            let cells = row
+                .iter()
+                .map(|cell| cell.replace('|', "\\|"))
+                .collect::<Vec<_>>();
+            out.push_str(&format!("| {} |\n", cells.join(" | ")));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Renders an HTML fragment with a heading and a table for each schema.
+pub fn to_html(schemas: &[Schema]) -> String {
+    let mut out = String::new();
+    for section in schemas
+        .iter()
+        .flat_map(|schema| build_sections(schema, schemas))
+    {
+        out.push_str(&format!(
+            "<h{level}>{}</h{level}>\n",
+            html_inline(&section.title),
+            level = section.level
+        ));
+        for paragraph in &section.paragraphs {
+            out.push_str(&format!("<p>{}</p>\n", html_inline(paragraph)));
+        }
+
+        let Some(table) = &section.table else {
+            continue;
+        };
+        out.push_str("<table>\n<thead>\n<tr>");
+        for header in table.headers {
+            out.push_str(&format!("<th>{}</th>", header));
+        }
+        out.push_str("</tr>\n</thead>\n<tbody>\n");
+        for row in &table.rows {
+            out.push_str("<tr>");
+            for cell in row {
+                out.push_str(&format!("<td>{}</td>", html_inline(cell)));
+            }
+            out.push_str("</tr>\n");
+        }
+        out.push_str("</tbody>\n</table>\n");
+    }
+    out
+}
+
+/// A heading with its text and table. Text marks code with backticks, as in Markdown.
+struct Section {
+    level: usize,
+    title: String,
+    paragraphs: Vec<String>,
+    table: Option<Table>,
+}
+
+struct Table {
+    headers: &'static [&'static str],
+    rows: Vec<Vec<String>>,
+}
+
+const FIELD_HEADERS: &[&str] = &["Offset", "Field", "Wire type", "Size", "Notes"];
+const MESSAGE_HEADERS: &[&str] = &["Number", "Field", "Label", "Type"];
+const ONEOF_HEADERS: &[&str] = &["Number", "Variant", "Label", "Type"];
+
+fn build_sections(schema: &Schema, schemas: &[Schema]) -> Vec<Section> {
+    let mut prefix = vec![];
+    if let Some(magic) = &schema.magic {
+        let note = format!("always `{}`", magic.value);
+        prefix.push(Row::new("(magic)", &magic.encoding, &note));
+    }
+    if let Some(size_prefix) = &schema.size_prefix {
+        prefix.push(Row::new(
+            "(size prefix)",
+            size_prefix,
+            "size in bytes of the rest",
+        ));
+    }
+
+    let mut section = Section {
+        level: 2,
+        title: schema.name.clone(),
+        paragraphs: vec![],
+        table: None,
+    };
+    match &schema.layout {
+        Layout::Struct { fields } => {
+            prefix.extend(fields.iter().map(Row::from_field));
+            section.table = Some(build_field_table(&prefix, Some(0), schemas));
+            vec![section]
+        }
+        Layout::Enum { tag, variants } => {
+            prefix.push(Row::new("(tag)", tag, "id of the variant"));
+            section.table = Some(build_field_table(&prefix, Some(0), schemas));
+            let tag_end = rows_end(&prefix, 0, schemas, &mut HashSet::new());
+
+            let mut ids = Table {
+                headers: &["Id", "Variant", "Size", "Notes"],
+                rows: vec![],
+            };
+            let mut sections = vec![];
+            for variant in variants {
+                let mut rows = vec![];
+                if let Some(size_prefix) = &variant.size_prefix {
+                    rows.push(Row::new(
+                        "(size prefix)",
+                        size_prefix,
+                        "size in bytes of the fields",
+                    ));
+                }
+                rows.extend(variant.fields.iter().map(Row::from_field));
+
+                let notes = version_notes(&variant.since, &variant.until);
+                let size = rows_end(&rows, 0, schemas, &mut HashSet::new());
+                for id in &variant.ids {
+                    let mut id_notes = notes.clone();
+                    id_notes.extend(version_notes(&id.since, &id.until));
+                    if id.pattern != id.value {
+                        id_notes.push(format!("written as `{}`", id.value));
+                    }
+                    ids.rows.push(vec![
+                        format!("`{}`", id.pattern),
+                        variant.name.clone(),
+                        display_size(size),
+                        id_notes.join(", "),
+                    ]);
+                }
+
+                if !rows.is_empty() {
+                    sections.push(Section {
+                        level: 3,
+                        title: format!("{}::{}", schema.name, variant.name),
+                        paragraphs: vec![],
+                        table: Some(build_field_table(&rows, tag_end, schemas)),
+                    });
+                }
+            }
+
+            let variants_section = Section {
+                level: 3,
+                title: String::from("Variants"),
+                paragraphs: vec![],
+                table: Some(ids),
+            };
+            [section, variants_section]
+                .into_iter()
+                .chain(sections)
+                .collect()
+        }
+        Layout::Message { fields } => {
+            section.paragraphs.push(String::from("Protobuf message."));
+            section.table = Some(build_message_table(fields, MESSAGE_HEADERS));
+            vec![section]
+        }
+        Layout::Oneof { fields } => {
+            section.paragraphs.push(String::from(
+                "Protobuf oneof, written as one of these fields.",
+            ));
+            section.table = Some(build_message_table(fields, ONEOF_HEADERS));
+            vec![section]
+        }
+        Layout::MessageEnum { values } => {
+            section
+                .paragraphs
+                .push(String::from("Protobuf enum, written as an `int32` varint."));
+            section.table = Some(Table {
+                headers: &["Value", "Name"],
+                rows: values
+                    .iter()
+                    .map(|value| vec![format!("`{}`", value.value), value.name.clone()])
+                    .collect(),
+            });
+            vec![section]
+        }
+    }
+}
+
+/// A value on the wire, from a field or the magic, size prefix or tag of a container.
+struct Row {
+    field: String,
+    encoding: Encoding,
+    notes: Vec<String>,
+    /// Whether the value is only on the wire sometimes.
+    conditional: bool,
+    pad: Option<String>,
+    align: Option<String>,
+}
+
+impl Row {
+    fn new(field: &str, encoding: &Encoding, note: &str) -> Self {
+        Row {
+            field: String::from(field),
+            encoding: encoding.clone(),
+            notes: vec![String::from(note)],
+            conditional: false,
+            pad: None,
+            align: None,
+        }
+    }
+
+    fn from_field(field: &Field) -> Self {
+        let mut notes = vec![];
+        if let Some(pad) = &field.pad {
+            notes.push(format!("after `{}` zero bytes", pad));
+        }
+        if let Some(align) = &field.align {
+            notes.push(format!("aligned to `{}` bytes", align));
+        }
+        if let Some(when) = &field.when {
+            notes.push(format!("only when `{}`", when));
+        }
+        notes.extend(version_notes(&field.since, &field.until));
+
+        Row {
+            field: field.name.clone(),
+            encoding: field.encoding.clone(),
+            notes,
+            conditional: field.when.is_some() || field.since.is_some() || field.until.is_some(),
+            pad: field.pad.clone(),
+            align: field.align.clone(),
+        }
+    }
+}
+
+/// Builds a table of fields starting at `offset`, if it is known.
+fn build_field_table(rows: &[Row], mut offset: Option<usize>, schemas: &[Schema]) -> Table {
+    let mut table = Table {
+        headers: FIELD_HEADERS,
+        rows: vec![],
+    };
+
+    for row in rows {
+        offset = offset.and_then(|offset| padded_offset(row, offset));
+        let size = fixed_size(&row.encoding, schemas, &mut HashSet::new());
+
+        let mut notes = encoding_notes(&row.encoding);
+        notes.extend(row.notes.iter().cloned());
+        table.rows.push(vec![
+            offset.map_or_else(|| String::from("var"), |offset| offset.to_string()),
+            row.field.clone(),
+            format!("`{}`", display_encoding(&row.encoding)),
+            match (row.conditional, size) {
+                (true, Some(size)) => format!("0 or {}", size),
+                (_, size) => display_size(size),
+            },
+            notes.join(", "),
+        ]);
+
+        offset = offset
+            .zip(size.filter(|_| !row.conditional))
+            .map(|(offset, size)| offset + size);
+    }
+    table
+}
+
+fn build_message_table(fields: &[MessageField], headers: &'static [&'static str]) -> Table {
+    Table {
+        headers,
+        rows: fields
+            .iter()
+            .map(|field| {
+                let numbers = field.numbers.iter().map(u32::to_string).collect::<Vec<_>>();
+                let label = match field.label {
+                    Label::Singular => "singular",
+                    Label::Optional => "optional",
+                    Label::Repeated => "repeated",
+                    Label::Oneof => "oneof",
+                };
+                vec![
+                    numbers.join(", "),
+                    field.name.clone(),
+                    String::from(label),
+                    format!("`{}`", field.ty),
+                ]
+            })
+            .collect(),
+    }
+}
+
+/// The offset after the padding and alignment of a row, if they are numbers.
+fn padded_offset(row: &Row, offset: usize) -> Option<usize> {
+    let mut offset = offset;
+    if let Some(pad) = &row.pad {
+        offset += pad.parse::<usize>().ok()?;
+    }
+    if let Some(align) = &row.align {
+        let align = align.parse::<usize>().ok().filter(|align| *align > 0)?;
+        offset = offset.div_ceil(align) * align;
+    }
+    Some(offset)
+}
+
+/// The offset after all rows, if they have fixed sizes.
+fn rows_end(
+    rows: &[Row],
+    start: usize,
+    schemas: &[Schema],
+    visiting: &mut HashSet<String>,
+) -> Option<usize> {
+    rows.iter().try_fold(start, |offset, row| {
+        if row.conditional {
+            return None;
+        }
+        let offset = padded_offset(row, offset)?;
+        Some(offset + fixed_size(&row.encoding, schemas, visiting)?)
+    })
+}
+
+/// The size of an encoding, or `None` when it varies. Other types are looked up in `schemas`.
+pub(crate) fn fixed_size(
+    encoding: &Encoding,
+    schemas: &[Schema],
+    visiting: &mut HashSet<String>,
+) -> Option<usize> {
+    match encoding {
+        Encoding::Number { name } => number_size(name),
+        Encoding::Bool => Some(1),
+        Encoding::Array { len, item } => Some(len * fixed_size(item, schemas, visiting)?),
+        Encoding::Tuple { items } => items
+            .iter()
+            .map(|item| fixed_size(item, schemas, visiting))
+            .sum(),
+        Encoding::Constant(constant) => fixed_size(&constant.encoding, schemas, visiting),
+        Encoding::Type { name } => {
+            let schema = schemas.iter().find(|schema| schema.name == *name)?;
+            if schema.size_prefix.is_some() || !visiting.insert(name.clone()) {
+                return None;
+            }
+            let Layout::Struct { fields } = &schema.layout else {
+                return None;
+            };
+
+            let mut rows = vec![];
+            if let Some(magic) = &schema.magic {
+                rows.push(Row::new("(magic)", &magic.encoding, ""));
+            }
+            rows.extend(fields.iter().map(Row::from_field));
+            let size = rows_end(&rows, 0, schemas, visiting);
+            visiting.remove(name);
+            size
+        }
+        Encoding::String { .. }
+        | Encoding::List { .. }
+        | Encoding::Map { .. }
+        | Encoding::Optional { .. }
+        | Encoding::Untagged { .. }
+        | Encoding::SizePrefixed { .. }
+        | Encoding::Custom { .. } => None,
+    }
+}
+
+/// The size of a number like `u8` or `BE<u16>`. Varints have no fixed size.
+fn number_size(name: &str) -> Option<usize> {
+    let inner = match name.split_once('<') {
+        Some(("LE" | "BE", inner)) => inner.trim_end_matches('>'),
+        Some(_) => return None,
+        None => name,
+    };
+    match inner {
+        "u8" | "i8" => Some(1),
+        "u16" | "i16" => Some(2),
+        "u32" | "i32" | "f32" => Some(4),
+        "u64" | "i64" | "f64" => Some(8),
+        "u128" | "i128" => Some(16),
+        _ => None,
+    }
+}
+
+fn display_size(size: Option<usize>) -> String {
+    size.map_or_else(|| String::from("var"), |size| size.to_string())
+}
+
+/// A short name of an encoding, like `Vec<BE<u16>>`.
+pub(crate) fn display_encoding(encoding: &Encoding) -> String {
+    match encoding {
+        Encoding::Number { name } => name.clone(),
+        Encoding::Bool => String::from("bool"),
+        Encoding::String { .. } => String::from("String"),
+        Encoding::List { item, .. } => format!("Vec<{}>", display_encoding(item)),
+        Encoding::Array { len, item } => format!("[{}; {}]", display_encoding(item), len),
+        Encoding::Tuple { items } => format!(
+            "({})",
+            items
+                .iter()
+                .map(display_encoding)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        Encoding::Map { key, value, .. } => format!(
+            "Map<{}, {}>",
+            display_encoding(key),
+            display_encoding(value)
+        ),
+        Encoding::Optional { value } => format!("Option<{}>", display_encoding(value)),
+        Encoding::Constant(constant) => display_encoding(&constant.encoding),
+        Encoding::Type { name } | Encoding::Untagged { name, .. } => name.clone(),
+        Encoding::SizePrefixed { value, .. } => display_encoding(value),
+        Encoding::Custom { .. } => String::from("custom"),
+    }
+}
+
+/// Notes on how an encoding is written, like its length prefix.
+fn encoding_notes(encoding: &Encoding) -> Vec<String> {
+    match encoding {
+        Encoding::Number { name } if name.starts_with("Var<i") => {
+            vec![String::from("zigzag varint")]
+        }
+        Encoding::Number { name } if name.starts_with("Var<") => vec![String::from("varint")],
+        Encoding::String { length } => vec![length_note(length)],
+        Encoding::List { length, item } => {
+            let mut notes = vec![length_note(length)];
+            notes.extend(encoding_notes(item));
+            notes
+        }
+        Encoding::Map { length, .. } => {
+            vec![format!("count prefix `{}`", display_encoding(length))]
+        }
+        Encoding::Optional { .. } => vec![String::from("`bool` presence prefix")],
+        Encoding::Constant(constant) => vec![format!("always `{}`", constant.value)],
+        Encoding::Untagged { tag, .. } => vec![format!("tag in `{}`", tag)],
+        Encoding::SizePrefixed { length, value } => {
+            let mut notes = vec![format!("size prefix `{}`", display_encoding(length))];
+            notes.extend(encoding_notes(value));
+            notes
+        }
+        Encoding::Custom {
+            read_with,
+            write_with,
+        } => {
+            let mut notes = vec![];
+            if let Some(read_with) = read_with {
+                notes.push(format!("read with `{}`", read_with));
+            }
+            if let Some(write_with) = write_with {
+                notes.push(format!("written with `{}`", write_with));
+            }
+            notes
+        }
+        _ => vec![],
+    }
+}
+
+fn length_note(length: &Length) -> String {
+    match length {
+        Length::Prefix { encoding } => format!("length prefix `{}`", display_encoding(encoding)),
+        Length::CountFrom { field } => format!("count in `{}`", field),
+        Length::ByteLenFrom { field } => format!("size in bytes in `{}`", field),
+        Length::Terminator { value } => format!("ends with `{}`", value),
+        Length::Predicate { predicate } => format!("ends after the item matching `{}`", predicate),
+        Length::Eof => String::from("runs to the end"),
+    }
+}
+
+fn version_notes(since: &Option<String>, until: &Option<String>) -> Vec<String> {
+    let mut notes = vec![];
+    if let Some(since) = since {
+        notes.push(format!("since version `{}`", since));
+    }
+    if let Some(until) = until {
+        notes.push(format!("until version `{}`", until));
+    }
+    notes
+}
+
+/// Escapes text and turns backtick spans into `<code>`.
+fn html_inline(text: &str) -> String {
+    let escaped = text
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;");
+
+    let mut out = String::new();
+    for (i, part) in escaped.split('`').enumerate() {
+        if i % 2 == 1 {
+            out.push_str(&format!("<code>{}</code>", part));
+        } else {
+            out.push_str(part);
+        }
+    }
+    out
+}
@@ -0,0 +1,758 @@
+//! Kaitai Struct (`.ksy`) definitions of schemas, for its visualizer and Web IDE.
+//!
+//! `LE`/`BE` numbers become types like `u2le`, `Var` numbers become the `varint` and
+//! `zigzag_varint` helper types whose `value` is the number, and enums `switch-on` their tag.
+//! Rust expressions like `#[when]` conditions are converted only where Kaitai has the same
+//! operators, and version checks are kept as docs since Kaitai has no protocol version.
+
+use std::collections::BTreeSet;
+
+use proto_rw::schema::{Encoding, Field, Layout, Length, Schema, Variant};
+
+use crate::yaml::Yaml;
+
+/// Renders a `.ksy` file whose top-level type is `root`. Every other schema becomes one of its
+/// `types`, so they can be referenced by name.
+pub fn to_ksy(root: &Schema, schemas: &[Schema]) -> String {
+    let mut ksy = Ksy {
+        schemas,
+        types: vec![],
+        helpers: BTreeSet::new(),
+    };
+
+    let root_type = ksy.build_schema(root);
+    for schema in schemas.iter().filter(|schema| schema.name != root.name) {
+        // Types built for its fields come after it
+        let index = ksy.types.len();
+        let ty = ksy.build_schema(schema);
+        ksy.types
+            .insert(index, (type_id(&schema.name), Yaml::Map(ty)));
+    }
+
+    let mut types = ksy.types;
+    for helper in &ksy.helpers {
+        types.push((String::from(*helper), helper_type(helper)));
+    }
+
+    let mut file = vec![(
+        String::from("meta"),
+        Yaml::Map(vec![(
+            String::from("id"),
+            Yaml::string(type_id(&root.name)),
+        )]),
+    )];
+    file.extend(root_type);
+    if !types.is_empty() {
+        file.push((String::from("types"), Yaml::Map(types)));
+    }
+    Yaml::Map(file).to_string()
+}
+
+struct Ksy<'a> {
+    schemas: &'a [Schema],
+    types: Vec<(String, Yaml)>,
+    helpers: BTreeSet<&'static str>,
+}
+
+/// The attributes of the type being built, to know which of them are varints.
+struct Scope {
+    name: String,
+    varints: BTreeSet<String>,
+}
+
+type Attrs = Vec<(String, Yaml)>;
+
+impl Ksy<'_> {
+    /// Builds the keys of a type, like its `seq`.
+    fn build_schema(&mut self, schema: &Schema) -> Attrs {
+        let mut scope = Scope {
+            name: type_id(&schema.name),
+            varints: BTreeSet::new(),
+        };
+        let mut seq = vec![];
+
+        if let Some(magic) = &schema.magic {
+            let mut attrs = vec![(String::from("id"), Yaml::string("magic"))];
+            match parse_byte_string(&magic.value) {
+                Some(bytes) => attrs.push((String::from("contents"), byte_list(&bytes))),
+                None => {
+                    attrs.extend(self.item_attrs(&magic.encoding, &mut scope, "magic"));
+                    attrs.push((String::from("valid"), Yaml::string(expr(&magic.value))));
+                }
+            }
+            seq.push(Yaml::Map(attrs));
+        }
+        if let Some(size_prefix) = &schema.size_prefix {
+            let doc = (
+                String::from("doc"),
+                Yaml::string("Size in bytes of the rest."),
+            );
+            self.push_entries(&mut seq, "len", size_prefix, &mut scope, vec![doc]);
+        }
+
+        let mut doc = None;
+        match &schema.layout {
+            Layout::Struct { fields } => {
+                for field in fields {
+                    self.push_field(&mut seq, field, &mut scope);
+                }
+            }
+            Layout::Enum { tag, variants } => {
+                self.push_entries(&mut seq, "tag", tag, &mut scope, vec![]);
+                let tag_ref = scope.reference("tag");
+                let cases = self.build_cases(&schema.name, variants);
+                seq.push(Yaml::Map(vec![
+                    (String::from("id"), Yaml::string("body")),
+                    (String::from("type"), switch_on(&tag_ref, cases)),
+                ]));
+            }
+            Layout::Message { fields } | Layout::Oneof { fields } => {
+                self.helpers
+                    .extend(["protobuf_field", "protobuf_len", "varint"]);
+                seq.push(Yaml::Map(vec![
+                    (String::from("id"), Yaml::string("fields")),
+                    (String::from("type"), Yaml::string("protobuf_field")),
+                    (String::from("repeat"), Yaml::string("eos")),
+                ]));
+                let numbers = fields
+                    .iter()
+                    .map(|field| {
+                        let numbers = field.numbers.iter().map(u32::to_string).collect::<Vec<_>>();
+                        format!("{} = {} ({})", field.name, numbers.join(", "), field.ty)
+                    })
+                    .collect::<Vec<_>>();
+                doc = Some(format!("Protobuf fields: {}.", numbers.join("; ")));
+            }
+            Layout::MessageEnum { values } => {
+                self.helpers.insert("varint");
+                seq.push(Yaml::Map(vec![
+                    (String::from("id"), Yaml::string("value")),
+                    (String::from("type"), Yaml::string("varint")),
+                ]));
+                let values = values
+                    .iter()
+                    .map(|value| format!("{} = {}", value.name, value.value))
+                    .collect::<Vec<_>>();
+                doc = Some(format!("Protobuf enum: {}.", values.join(", ")));
+            }
+        }
+
+        let mut attrs = vec![];
+        if let Some(doc) = doc {
+            attrs.push((String::from("doc"), Yaml::string(doc)));
+        }
+        attrs.push((String::from("seq"), Yaml::List(seq)));
+        attrs
+    }
+
+    /// Builds a type for each variant with fields, and the cases of the tag values.
+    fn build_cases(&mut self, enum_name: &str, variants: &[Variant]) -> Attrs {
+        let mut cases = vec![];
+        for variant in variants {
+            if variant.fields.is_empty() && variant.size_prefix.is_none() {
+                continue;
+            }
+
+            let name = format!("{}_{}", type_id(enum_name), type_id(&variant.name));
+            let mut scope = Scope {
+                name: name.clone(),
+                varints: BTreeSet::new(),
+            };
+            let mut seq = vec![];
+            if let Some(size_prefix) = &variant.size_prefix {
+                let doc = (
+                    String::from("doc"),
+                    Yaml::string("Size in bytes of the fields."),
+                );
+                self.push_entries(&mut seq, "len", size_prefix, &mut scope, vec![doc]);
+            }
+            for field in &variant.fields {
+                self.push_field(&mut seq, field, &mut scope);
+            }
+            // Enums used with `#[tag]` fields build their variants again
+            if !self.types.iter().any(|(built, _)| *built == name) {
+                self.types.push((
+                    name.clone(),
+                    Yaml::Map(vec![(String::from("seq"), Yaml::List(seq))]),
+                ));
+            }
+
+            for id in &variant.ids {
+                for pattern in id.pattern.split('|') {
+                    let pattern = pattern.trim();
+                    if pattern != "_" && !pattern.contains("..") {
+                        cases.push((pattern.replace(' ', ""), Yaml::string(name.clone())));
+                    }
+                }
+            }
+        }
+        cases
+    }
+
+    fn push_field(&mut self, seq: &mut Vec<Yaml>, field: &Field, scope: &mut Scope) {
+        let id = attr_id(&field.name);
+
+        if let Some(pad) = &field.pad {
+            seq.push(Yaml::Map(vec![
+                (String::from("id"), Yaml::string(format!("{}_pad", id))),
+                (String::from("size"), Yaml::string(expr(pad))),
+            ]));
+        }
+        if let Some(align) = &field.align {
+            let align = expr(align);
+            seq.push(Yaml::Map(vec![
+                (String::from("id"), Yaml::string(format!("{}_align", id))),
+                (
+                    String::from("size"),
+                    Yaml::string(format!("({0} - _io.pos % {0}) % {0}", align)),
+                ),
+            ]));
+        }
+
+        let mut extra = vec![];
+        if let Some(when) = &field.when {
+            extra.push((String::from("if"), Yaml::string(expr(when))));
+        }
+        let mut docs = vec![];
+        if let Some(since) = &field.since {
+            docs.push(format!("Since version {}.", since));
+        }
+        if let Some(until) = &field.until {
+            docs.push(format!("Until version {}.", until));
+        }
+        if !docs.is_empty() {
+            extra.push((String::from("doc"), Yaml::string(docs.join(" "))));
+        }
+
+        self.push_entries(seq, &id, &field.encoding, scope, extra);
+    }
+
+    /// Pushes the attributes of a value, which may need more than one, like a length prefix
+    /// and the items. `extra` keys, like `if`, are added to each of them.
+    fn push_entries(
+        &mut self,
+        seq: &mut Vec<Yaml>,
+        id: &str,
+        encoding: &Encoding,
+        scope: &mut Scope,
+        extra: Attrs,
+    ) {
+        let entry = |attrs: Attrs| {
+            let mut map = vec![(String::from("id"), Yaml::string(id))];
+            map.extend(attrs);
+            map.extend(extra.iter().cloned());
+            Yaml::Map(map)
+        };
+
+        match encoding {
+            Encoding::String { length } => {
+                let mut attrs = vec![
+                    (String::from("type"), Yaml::string("str")),
+                    (String::from("encoding"), Yaml::string("UTF-8")),
+                ];
+                attrs.extend(self.length_attrs(seq, id, length, scope, &extra, true));
+                seq.push(entry(attrs));
+            }
+            Encoding::List { length, item } => {
+                let is_bytes = is_u8(item);
+                let mut attrs = vec![];
+                let length_attrs = self.length_attrs(seq, id, length, scope, &extra, is_bytes);
+                match length {
+                    Length::ByteLenFrom { .. } if !is_bytes => {
+                        // The items are read from a substream of that size
+                        let items = format!("{}_{}_items", scope.name, id);
+                        let mut items_scope = Scope {
+                            name: items.clone(),
+                            varints: BTreeSet::new(),
+                        };
+                        let mut items_seq = vec![];
+                        let mut item_attrs = vec![(String::from("id"), Yaml::string("items"))];
+                        item_attrs.extend(self.item_attrs(item, &mut items_scope, "items"));
+                        item_attrs.push((String::from("repeat"), Yaml::string("eos")));
+                        items_seq.push(Yaml::Map(item_attrs));
+                        self.types.push((
+                            items.clone(),
+                            Yaml::Map(vec![(String::from("seq"), Yaml::List(items_seq))]),
+                        ));
+                        attrs.push((String::from("type"), Yaml::string(items)));
+                    }
+                    _ if is_bytes => {}
+                    _ => attrs.extend(self.item_attrs(item, scope, id)),
+                }
+                attrs.extend(length_attrs);
+                seq.push(entry(attrs));
+            }
+            Encoding::Array { len, item } => {
+                let mut attrs = vec![];
+                if is_u8(item) {
+                    attrs.push((String::from("size"), Yaml::raw(len.to_string())));
+                } else {
+                    attrs.extend(self.item_attrs(item, scope, id));
+                    attrs.push((String::from("repeat"), Yaml::string("expr")));
+                    attrs.push((String::from("repeat-expr"), Yaml::raw(len.to_string())));
+                }
+                seq.push(entry(attrs));
+            }
+            Encoding::Tuple { items } => {
+                for (i, item) in items.iter().enumerate() {
+                    self.push_entries(seq, &format!("{}_{}", id, i), item, scope, extra.clone());
+                }
+            }
+            Encoding::Map { length, key, value } => {
+                let len_id = format!("{}_len", id);
+                self.push_entries(seq, &len_id, length, scope, extra.clone());
+                let len_ref = scope.reference(&len_id);
+
+                let entry_type = format!("{}_{}_entry", scope.name, id);
+                let mut entry_scope = Scope {
+                    name: entry_type.clone(),
+                    varints: BTreeSet::new(),
+                };
+                let mut entry_seq = vec![];
+                self.push_entries(&mut entry_seq, "key", key, &mut entry_scope, vec![]);
+                self.push_entries(&mut entry_seq, "value", value, &mut entry_scope, vec![]);
+                self.types.push((
+                    entry_type.clone(),
+                    Yaml::Map(vec![(String::from("seq"), Yaml::List(entry_seq))]),
+                ));
+
+                seq.push(entry(vec![
+                    (String::from("type"), Yaml::string(entry_type)),
+                    (String::from("repeat"), Yaml::string("expr")),
+                    (String::from("repeat-expr"), Yaml::string(len_ref)),
+                ]));
+            }
+            Encoding::Optional { value } => {
+                let present = format!("{}_present", id);
+                let mut present_attrs = vec![
+                    (String::from("id"), Yaml::string(present.clone())),
+                    (String::from("type"), Yaml::string("u1")),
+                ];
+                present_attrs.extend(extra.iter().cloned());
+                seq.push(Yaml::Map(present_attrs));
+
+                let condition = format!("{} == 1", present);
+                let mut extra = extra.clone();
+                match extra.iter_mut().find(|(key, _)| key == "if") {
+                    Some((_, Yaml::Scalar(when))) => {
+                        *when = format!("({}) and {}", when, condition)
+                    }
+                    _ => extra.push((String::from("if"), Yaml::string(condition))),
+                }
+                self.push_entries(seq, id, value, scope, extra);
+            }
+            Encoding::SizePrefixed { length, value } => {
+                let len_id = format!("{}_len", id);
+                self.push_entries(seq, &len_id, length, scope, extra.clone());
+                let len_ref = scope.reference(&len_id);
+
+                let mut attrs = self.item_attrs(value, scope, id);
+                attrs.push((String::from("size"), Yaml::string(len_ref)));
+                seq.push(entry(attrs));
+            }
+            Encoding::Custom {
+                read_with,
+                write_with,
+            } => {
+                let with = read_with.as_ref().or(write_with.as_ref());
+                seq.push(entry(vec![
+                    (String::from("size-eos"), Yaml::raw("true")),
+                    (
+                        String::from("doc"),
+                        Yaml::string(format!(
+                            "Read by {}, which can't be described here.",
+                            with.map_or("a custom function", String::as_str)
+                        )),
+                    ),
+                ]));
+            }
+            Encoding::Number { name } if name.starts_with("Var<") => {
+                scope.varints.insert(String::from(id));
+                let attrs = self.item_attrs(encoding, scope, id);
+                seq.push(entry(attrs));
+            }
+            _ => {
+                let attrs = self.item_attrs(encoding, scope, id);
+                seq.push(entry(attrs));
+            }
+        }
+    }
+
+    /// Keys of the length of a string or list, pushing the attribute of its length prefix.
+    /// Strings and byte lists use `size`, other lists repeat their items.
+    fn length_attrs(
+        &mut self,
+        seq: &mut Vec<Yaml>,
+        id: &str,
+        length: &Length,
+        scope: &mut Scope,
+        extra: &Attrs,
+        is_bytes: bool,
+    ) -> Attrs {
+        let count = |count: String| {
+            if is_bytes {
+                vec![(String::from("size"), Yaml::string(count))]
+            } else {
+                vec![
+                    (String::from("repeat"), Yaml::string("expr")),
+                    (String::from("repeat-expr"), Yaml::string(count)),
+                ]
+            }
+        };
+
+        match length {
+            Length::Prefix { encoding } => {
+                let len_id = format!("{}_len", id);
+                self.push_entries(seq, &len_id, encoding, scope, extra.clone());
+                count(scope.reference(&len_id))
+            }
+            Length::CountFrom { field } => count(scope.reference(&attr_id(field))),
+            Length::ByteLenFrom { field } => vec![(
+                String::from("size"),
+                Yaml::string(scope.reference(&attr_id(field))),
+            )],
+            Length::Terminator { value } if is_bytes => match parse_byte_string(value) {
+                Some(bytes) if bytes.len() == 1 => {
+                    vec![(String::from("terminator"), Yaml::raw(bytes[0].to_string()))]
+                }
+                Some(_) => vec![
+                    (String::from("size-eos"), Yaml::raw("true")),
+                    (
+                        String::from("doc"),
+                        Yaml::string(format!("Ends with {}.", value)),
+                    ),
+                ],
+                None => vec![(String::from("terminator"), Yaml::string(expr(value)))],
+            },
+            Length::Terminator { value } => vec![
+                (String::from("repeat"), Yaml::string("eos")),
+                (
+                    String::from("doc"),
+                    Yaml::string(format!("Ends with {}.", value)),
+                ),
+            ],
+            Length::Predicate { predicate } => vec![
+                (String::from("repeat"), Yaml::string("eos")),
+                (
+                    String::from("doc"),
+                    Yaml::string(format!("Ends after the item matching {}.", predicate)),
+                ),
+            ],
+            Length::Eof if is_bytes => vec![(String::from("size-eos"), Yaml::raw("true"))],
+            Length::Eof => vec![(String::from("repeat"), Yaml::string("eos"))],
+        }
+    }
+
+    /// Keys reading a single value, like `type: u2be`. Values that need more than one
+    /// attribute get a type of their own.
+    fn item_attrs(&mut self, encoding: &Encoding, scope: &mut Scope, id: &str) -> Attrs {
+        match encoding {
+            Encoding::Number { name } => match number_type(name) {
+                Some(ty) => {
+                    if let Some(helper) = HELPERS.iter().find(|helper| **helper == ty) {
+                        self.helpers.insert(helper);
+                        if ty == "zigzag_varint" {
+                            self.helpers.insert("varint");
+                        }
+                    }
+                    vec![(String::from("type"), Yaml::string(ty))]
+                }
+                None => vec![
+                    (String::from("size"), Yaml::raw("16")),
+                    (String::from("doc"), Yaml::string(name.clone())),
+                ],
+            },
+            Encoding::Bool => vec![(String::from("type"), Yaml::string("u1"))],
+            Encoding::Type { name } => vec![(String::from("type"), Yaml::string(type_id(name)))],
+            Encoding::Untagged { name, tag } => {
+                let tag_ref = scope.reference(&attr_id(tag));
+                match self.schemas.iter().find(|schema| schema.name == *name) {
+                    Some(Schema {
+                        layout: Layout::Enum { variants, .. },
+                        ..
+                    }) => {
+                        let cases = self.build_cases(name, variants);
+                        vec![(String::from("type"), switch_on(&tag_ref, cases))]
+                    }
+                    _ => vec![
+                        (String::from("type"), Yaml::string(type_id(name))),
+                        (
+                            String::from("doc"),
+                            Yaml::string(format!("Variant of {} with the tag {}.", name, tag)),
+                        ),
+                    ],
+                }
+            }
+            Encoding::Constant(constant) => {
+                let mut attrs = self.item_attrs(&constant.encoding, scope, id);
+                attrs.push((String::from("valid"), Yaml::string(expr(&constant.value))));
+                attrs
+            }
+            _ => {
+                let item = format!("{}_{}_item", scope.name, id);
+                let mut item_scope = Scope {
+                    name: item.clone(),
+                    varints: BTreeSet::new(),
+                };
+                let mut item_seq = vec![];
+                self.push_entries(&mut item_seq, "value", encoding, &mut item_scope, vec![]);
+                self.types.push((
+                    item.clone(),
+                    Yaml::Map(vec![(String::from("seq"), Yaml::List(item_seq))]),
+                ));
+                vec![(String::from("type"), Yaml::string(item))]
+            }
+        }
+    }
+}
+
+impl Scope {
+    /// An expression of the number in an attribute, which is its `value` for varints.
+    fn reference(&self, id: &str) -> String {
+        if self.varints.contains(id) {
+            format!("{}.value", id)
+        } else {
+            String::from(id)
+        }
+    }
+}
+
+fn switch_on(on: &str, cases: Attrs) -> Yaml {
+    Yaml::Map(vec![
+        (String::from("switch-on"), Yaml::string(on)),
+        (String::from("cases"), Yaml::Map(cases)),
+    ])
+}
+
+const HELPERS: &[&str] = &["varint", "zigzag_varint"];
+
+/// The Kaitai type of a number like `BE<u16>` or `Var<i32>`.
+fn number_type(name: &str) -> Option<String> {
+    let (wrapper, inner) = match name.split_once('<') {
+        Some((wrapper, inner)) => (wrapper, inner.trim_end_matches('>')),
+        None => ("", name),
+    };
+    if wrapper == "Var" {
+        return Some(String::from(if inner.starts_with('i') {
+            "zigzag_varint"
+        } else {
+            "varint"
+        }));
+    }
+
+    let (kind, bits) = inner.split_at(1);
+    let kind = match kind {
+        "u" => "u",
+        "i" => "s",
+        "f" => "f",
+        _ => return None,
+    };
+    let bytes = bits.parse::<usize>().ok()? / 8;
+    let endian = match (wrapper, bytes) {
+        (_, 1) => "",
+        ("LE", _) => "le",
+        ("BE", _) => "be",
+        _ => return None,
+    };
+    (bytes <= 8).then(|| format!("{}{}{}", kind, bytes, endian))
+}
+
+fn is_u8(encoding: &Encoding) -> bool {
+    matches!(encoding, Encoding::Number { name } if name == "u8")
+}
+
+fn helper_type(name: &str) -> Yaml {
+    let keys = |keys: &[(&str, Yaml)]| {
+        Yaml::Map(
+            keys.iter()
+                .map(|(key, value)| (String::from(*key), value.clone()))
+                .collect(),
+        )
+    };
+    let value = |expr: String| keys(&[("value", Yaml::string(expr))]);
+
+    match name {
+        "varint" => {
+            let groups = (0..10)
+                .map(|i| match i {
+                    0 => String::from("(groups[0].b & 0x7f)"),
+                    _ => format!(
+                        "(groups.size > {0} ? (groups[{0}].b & 0x7f) << {1} : 0)",
+                        i,
+                        i * 7
+                    ),
+                })
+                .collect::<Vec<_>>();
+            keys(&[
+                ("doc", Yaml::string("Unsigned LEB128 varint.")),
+                (
+                    "seq",
+                    Yaml::List(vec![keys(&[
+                        ("id", Yaml::string("groups")),
+                        ("type", Yaml::string("varint_group")),
+                        ("repeat", Yaml::string("until")),
+                        ("repeat-until", Yaml::string("(_.b & 0x80) == 0")),
+                    ])]),
+                ),
+                ("instances", keys(&[("value", value(groups.join(" | ")))])),
+                (
+                    "types",
+                    keys(&[(
+                        "varint_group",
+                        keys(&[(
+                            "seq",
+                            Yaml::List(vec![keys(&[
+                                ("id", Yaml::string("b")),
+                                ("type", Yaml::string("u1")),
+                            ])]),
+                        )]),
+                    )]),
+                ),
+            ])
+        }
+        "zigzag_varint" => keys(&[
+            ("doc", Yaml::string("Signed varint, zigzag encoded.")),
+            (
+                "seq",
+                Yaml::List(vec![keys(&[
+                    ("id", Yaml::string("raw")),
+                    ("type", Yaml::string("varint")),
+                ])]),
+            ),
+            (
+                "instances",
+                keys(&[(
+                    "value",
+                    value(String::from("(raw.value >> 1) ^ -(raw.value & 1)")),
+                )]),
+            ),
+        ]),
+        "protobuf_field" => keys(&[
+            ("doc", Yaml::string("Protobuf field key and value.")),
+            (
+                "seq",
+                Yaml::List(vec![
+                    keys(&[
+                        ("id", Yaml::string("key")),
+                        ("type", Yaml::string("varint")),
+                    ]),
+                    keys(&[
+                        ("id", Yaml::string("value")),
+                        (
+                            "type",
+                            switch_on(
+                                "key.value & 7",
+                                vec![
+                                    (String::from("0"), Yaml::string("varint")),
+                                    (String::from("1"), Yaml::string("u8le")),
+                                    (String::from("2"), Yaml::string("protobuf_len")),
+                                    (String::from("5"), Yaml::string("u4le")),
+                                ],
+                            ),
+                        ),
+                    ]),
+                ]),
+            ),
+            (
+                "instances",
+                keys(&[("number", value(String::from("key.value >> 3")))]),
+            ),
+        ]),
+        "protobuf_len" => keys(&[(
+            "seq",
+            Yaml::List(vec![
+                keys(&[
+                    ("id", Yaml::string("len")),
+                    ("type", Yaml::string("varint")),
+                ]),
+                keys(&[
+                    ("id", Yaml::string("data")),
+                    ("size", Yaml::string("len.value")),
+                ]),
+            ]),
+        )]),
+        _ => unreachable!("Unknown helper type {}", name),
+    }
+}
+
+/// Converts a type name like `LoginStart` to an identifier like `login_start`.
+pub(crate) fn type_id(name: &str) -> String {
+    let name = name.split('<').next().unwrap_or(name);
+    let mut id = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 && !id.ends_with('_') {
+                id.push('_');
+            }
+            id.extend(c.to_lowercase());
+        } else if c.is_alphanumeric() {
+            id.push(c);
+        } else if !id.ends_with('_') {
+            id.push('_');
+        }
+    }
+    id
+}
+
+/// Field names, or `field_0` for tuple fields.
+pub(crate) fn attr_id(name: &str) -> String {
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("field_{}", name)
+    } else {
+        type_id(name)
+    }
+}
+
+/// Converts a Rust expression to a Kaitai one, dropping dereferences and spelling out the
+/// logical operators.
+pub(crate) fn expr(rust: &str) -> String {
+    let mut tokens: Vec<&str> = vec![];
+    for token in rust.split_whitespace() {
+        let unary = tokens.last().is_none_or(|last| {
+            !last.ends_with(|c: char| c.is_alphanumeric() || c == ')' || c == '_')
+        });
+        match token {
+            "*" | "&" if unary => {}
+            "&&" => tokens.push("and"),
+            "||" => tokens.push("or"),
+            "!" => tokens.push("not"),
+            _ => tokens.push(token),
+        }
+    }
+    tokens
+        .join(" ")
+        .replace(" . ", ".")
+        .replace("( ", "(")
+        .replace(" )", ")")
+}
+
+/// Parses a byte string literal like `b"PT\x01"`.
+fn parse_byte_string(literal: &str) -> Option<Vec<u8>> {
+    let inner = literal.strip_prefix("b\"")?.strip_suffix('"')?;
+    let mut bytes = vec![];
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+        bytes.push(match chars.next()? {
+            'x' => u8::from_str_radix(&chars.by_ref().take(2).collect::<String>(), 16).ok()?,
+            'n' => b'\n',
+            'r' => b'\r',
+            't' => b'\t',
+            '0' => 0,
+            c => c as u8,
+        });
+    }
+    Some(bytes)
+}
+
+fn byte_list(bytes: &[u8]) -> Yaml {
+    let bytes = bytes
+        .iter()
+        .map(|byte| format!("0x{:02x}", byte))
+        .collect::<Vec<_>>();
+    Yaml::raw(format!("[{}]", bytes.join(", ")))
+}
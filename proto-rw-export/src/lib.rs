@@ -0,0 +1,9 @@
+//! Generators turning the schemas of `#[proto_rw]` types into documentation and other tools.
+//!
+//! Schemas come from `ProtoSchema::schema()`, or from JSON written with the `serde` feature of
+//! `proto-rw`. Types are referenced by name, so pass every schema the others refer to.
+
+pub mod docs;
+pub mod ksy;
+pub mod wireshark;
+mod yaml;
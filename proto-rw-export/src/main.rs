@@ -0,0 +1,69 @@
+//! Reads a JSON file of schemas and prints them in another format.
+//!
+//! ```text
+//! proto-rw-export <markdown|html|ksy|lua> <schemas.json>
+//! ```
+//!
+//! The file holds one schema or an array of them, like `serde_json::to_string` of
+//! `vec![Handshake::schema(), Login::schema()]`. For `ksy` and `lua`, the first schema is the
+//! top-level type, and names the protocol.
+
+use std::process::ExitCode;
+
+use proto_rw::schema::Schema;
+use proto_rw_export::{docs, ksy, wireshark};
+
+const USAGE: &str = "Usage: proto-rw-export <markdown|html|ksy|lua> <schemas.json>";
+
+fn main() -> ExitCode {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let [format, path] = &args[..] else {
+        eprintln!("{}", USAGE);
+        return ExitCode::FAILURE;
+    };
+
+    match export(format, path) {
+        Ok(output) => {
+            print!("{}", output);
+            ExitCode::SUCCESS
+        }
+        Err(message) => {
+            eprintln!("{}", message);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn export(format: &str, path: &str) -> Result<String, String> {
+    if !["markdown", "html", "ksy", "lua"].contains(&format) {
+        return Err(format!("Unknown format {}\n{}", format, USAGE));
+    }
+
+    let schemas = read_schemas(path)?;
+    Ok(match format {
+        "markdown" => docs::to_markdown(&schemas),
+        "html" => docs::to_html(&schemas),
+        _ => {
+            let root = schemas
+                .first()
+                .ok_or_else(|| format!("{}: No schemas found", path))?;
+            match format {
+                "ksy" => ksy::to_ksy(root, &schemas),
+                _ => wireshark::to_lua(&root.name, root, &schemas),
+            }
+        }
+    })
+}
+
+fn read_schemas(path: &str) -> Result<Vec<Schema>, String> {
+    let json = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let value =
+        serde_json::from_str::<serde_json::Value>(&json).map_err(|e| format!("{}: {}", path, e))?;
+
+    let schemas = if value.is_array() {
+        serde_json::from_value(value)
+    } else {
+        serde_json::from_value(value).map(|schema| vec![schema])
+    };
+    schemas.map_err(|e| format!("{}: {}", path, e))
+}
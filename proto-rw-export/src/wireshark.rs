@@ -0,0 +1,848 @@
+//! Wireshark Lua dissectors of schemas.
+//!
+//! Each schema becomes a function adding a subtree with its fields, and enums dispatch on their
+//! tag, so a packet id enum like `#[proto_rw(Var<i32>)]` shows the packet it read in the info
+//! column. The dissector is registered for "Decode As" on TCP and UDP ports, and its
+//! "Protocol version" preference is checked by `#[since]` and `#[until]`.
+
+use proto_rw::schema::{Encoding, Field, Label, Layout, Length, MessageField, Schema, Variant};
+
+use crate::ksy::{attr_id, type_id};
+
+/// Renders a dissector for the protocol `name`, whose packets are read as `root`. Every other
+/// schema gets a function, so they can be referenced by name.
+pub fn to_lua(name: &str, root: &Schema, schemas: &[Schema]) -> String {
+    let mut lua = Lua {
+        protocol: type_id(name),
+        schemas,
+        fields: vec![],
+        protobuf: false,
+        out: Writer::default(),
+    };
+
+    for schema in std::iter::once(root).chain(schemas.iter().filter(|s| s.name != root.name)) {
+        lua.build_schema(schema);
+    }
+
+    let protocol = &lua.protocol;
+    let mut file = Writer::default();
+    file.line("-- Generated by proto-rw-export. Do not edit.");
+    file.line(&format!(
+        "local proto = Proto({:?}, {:?})",
+        protocol,
+        format!("{} protocol", name)
+    ));
+    file.line("proto.prefs.version = Pref.uint(\"Protocol version\", 4294967295, \"Version checked by fields added or removed in a version, the latest by default\")");
+    file.line("");
+    file.line("local f = {}");
+    for (_, declaration) in &lua.fields {
+        file.line(declaration);
+    }
+    file.line("proto.fields = f");
+    file.line("");
+    for line in HELPERS.lines() {
+        file.line(line);
+    }
+    file.line("");
+    file.line("local dissect = {}");
+    file.lines.extend(lua.out.lines);
+    file.line("");
+    file.line("function proto.dissector(tvb, pinfo, tree)");
+    file.line(&format!(
+        "  pinfo.cols.protocol = {:?}",
+        protocol.to_uppercase()
+    ));
+    file.line("  pinfo.cols.info:clear()");
+    file.line(&format!(
+        "  local subtree = tree:add(proto, tvb(), {:?})",
+        format!("{} protocol", name)
+    ));
+    file.line(&format!(
+        "  dissect.{}(tvb, pinfo, 0, subtree)",
+        type_id(&root.name)
+    ));
+    file.line("end");
+    file.line("");
+    file.line("DissectorTable.get(\"tcp.port\"):add_for_decode_as(proto)");
+    file.line("DissectorTable.get(\"udp.port\"):add_for_decode_as(proto)");
+    file.lines.join("\n") + "\n"
+}
+
+/// Reads varints, which Wireshark has no field type for. Lua numbers are doubles, so varints wider
+/// than 53 bits are read as `UInt64`, and zigzag decoded as `Int64`, which `to_number` rounds for
+/// expressions.
+const HELPERS: &str = r#"local function to_number(value)
+  if type(value) == "number" then
+    return value
+  end
+  return value:tonumber()
+end
+
+local function read_varint(tvb, offset)
+  local value, size, scale = 0, 0, 1
+  repeat
+    local byte = tvb(offset + size, 1):uint()
+    if size < 7 then
+      value = value + (byte % 128) * scale
+      scale = scale * 128
+    else
+      value = UInt64(value) + UInt64(byte % 128):lshift(7 * size)
+    end
+    size = size + 1
+  until byte < 128
+  if type(value) ~= "number" and value:higher() < 0x200000 then
+    value = value:tonumber()
+  end
+  return value, size
+end
+
+local function zigzag(value)
+  if type(value) ~= "number" then
+    local half = Int64(value:rshift(1))
+    if value:lower() % 2 == 0 then
+      return half
+    end
+    return -half - 1
+  end
+  if value % 2 == 0 then
+    return value / 2
+  end
+  return -(value + 1) / 2
+end"#;
+
+struct Lua<'a> {
+    protocol: String,
+    schemas: &'a [Schema],
+    /// `ProtoField` declarations by key, in order.
+    fields: Vec<(String, String)>,
+    /// Whether the protobuf helper was written.
+    protobuf: bool,
+    out: Writer,
+}
+
+/// Lines of code and the current indentation.
+#[derive(Default)]
+struct Writer {
+    lines: Vec<String>,
+    indent: usize,
+}
+
+impl Writer {
+    fn line(&mut self, line: &str) {
+        if line.is_empty() {
+            self.lines.push(String::new());
+        }
+        for line in line.lines() {
+            let indent = self.indent * 2;
+            self.lines.push(format!("{:indent$}{}", "", line));
+        }
+    }
+
+    fn open(&mut self, line: &str) {
+        self.line(line);
+        self.indent += 1;
+    }
+
+    fn close(&mut self, line: &str) {
+        self.indent -= 1;
+        self.line(line);
+    }
+}
+
+/// The fields of the type being built, which expressions refer to through `v`.
+struct Scope {
+    name: String,
+    fields: Vec<String>,
+}
+
+impl Lua<'_> {
+    fn build_schema(&mut self, schema: &Schema) {
+        let id = type_id(&schema.name);
+        let mut scope = Scope {
+            name: id.clone(),
+            fields: vec![],
+        };
+
+        self.out.line("");
+        self.out.open(&format!(
+            "dissect.{} = function(tvb, pinfo, offset, tree)",
+            id
+        ));
+        self.out.line("local start = offset");
+        self.out.line("local v = {}");
+        self.out.line(&format!(
+            "local subtree = tree:add(tvb(offset, 0), {:?})",
+            schema.name
+        ));
+
+        if let Some(magic) = &schema.magic {
+            self.build_value("subtree", "magic", &magic.encoding, &mut scope, None);
+        }
+        if let Some(size_prefix) = &schema.size_prefix {
+            self.build_value("subtree", "size", size_prefix, &mut scope, None);
+        }
+
+        match &schema.layout {
+            Layout::Struct { fields } => {
+                for field in fields {
+                    self.build_field("subtree", field, &mut scope);
+                }
+            }
+            Layout::Enum { tag, .. } => {
+                self.build_value("subtree", "tag", tag, &mut scope, Some("v.tag"));
+                self.out.line(&format!(
+                    "offset = dissect.{}_body(tvb, pinfo, offset, subtree, v.tag)",
+                    id
+                ));
+            }
+            Layout::Message { fields } => {
+                self.out
+                    .line("offset = dissect.protobuf(tvb, offset, tvb:len(), subtree, {");
+                self.build_message_names(fields);
+                self.out.line("})");
+            }
+            Layout::Oneof { fields } => {
+                self.out
+                    .line("offset = dissect.protobuf(tvb, offset, tvb:len(), subtree, {");
+                self.build_message_names(fields);
+                self.out.line("}, 1)");
+            }
+            Layout::MessageEnum { values } => {
+                self.out
+                    .line("local value, size = read_varint(tvb, offset)");
+                self.out.line("local names = {");
+                for value in values {
+                    self.out
+                        .line(&format!("  [{}] = {:?},", value.value, value.name));
+                }
+                self.out.line("}");
+                self.out
+                    .line("subtree:add(tvb(offset, size), \"value: \" .. (names[value] or value))");
+                self.out.line("offset = offset + size");
+            }
+        }
+
+        self.out.line("subtree:set_len(offset - start)");
+        self.out.line("return offset");
+        self.out.close("end");
+
+        if let Layout::Enum { variants, .. } = &schema.layout {
+            self.build_enum_body(&id, variants);
+        }
+        if matches!(schema.layout, Layout::Message { .. } | Layout::Oneof { .. }) && !self.protobuf
+        {
+            self.protobuf = true;
+            self.out.line("");
+            for line in PROTOBUF.lines() {
+                self.out.line(line);
+            }
+        }
+    }
+
+    /// Dispatches on the tag of an enum, read by the enum itself or by an earlier field.
+    fn build_enum_body(&mut self, id: &str, variants: &[Variant]) {
+        self.out.line("");
+        self.out.open(&format!(
+            "dissect.{}_body = function(tvb, pinfo, offset, tree, tag)",
+            id
+        ));
+
+        let mut first = true;
+        for variant in variants {
+            let mut conditions = vec![];
+            for variant_id in &variant.ids {
+                let mut checks = variant_id
+                    .pattern
+                    .split('|')
+                    .map(pattern_check)
+                    .collect::<Vec<_>>();
+                if checks.len() > 1 {
+                    checks = vec![format!("({})", checks.join(" or "))];
+                }
+                checks.extend(version_checks(&variant_id.since, &variant_id.until));
+                conditions.push(checks.join(" and "));
+            }
+            let mut condition = match &conditions[..] {
+                [condition] => condition.clone(),
+                _ => format!("({})", conditions.join(") or (")),
+            };
+            let versions = version_checks(&variant.since, &variant.until);
+            if !versions.is_empty() {
+                condition = format!("({}) and {}", condition, versions.join(" and "));
+            }
+
+            if !first {
+                self.out.indent -= 1;
+            }
+            self.out.open(&format!(
+                "{} {} then",
+                if first { "if" } else { "elseif" },
+                condition
+            ));
+            first = false;
+
+            self.out
+                .line(&format!("tree:append_text(\": {}\")", variant.name));
+            self.out
+                .line(&format!("pinfo.cols.info:append(\" {}\")", variant.name));
+            if variant.fields.is_empty() && variant.size_prefix.is_none() {
+                continue;
+            }
+            self.out.line("local start = offset");
+            self.out.line("local v = {}");
+            let mut scope = Scope {
+                name: format!("{}_{}", id, type_id(&variant.name)),
+                fields: vec![],
+            };
+            if let Some(size_prefix) = &variant.size_prefix {
+                self.build_value("tree", "size", size_prefix, &mut scope, None);
+            }
+            for field in &variant.fields {
+                self.build_field("tree", field, &mut scope);
+            }
+        }
+
+        if first {
+            self.out
+                .line("tree:add_expert_info(PI_MALFORMED, PI_ERROR, \"No variants\")");
+        } else {
+            self.out.close("else");
+            self.out.line(
+                "  tree:add_expert_info(PI_MALFORMED, PI_ERROR, \"Unknown id \" .. tostring(tag))",
+            );
+            self.out.line("  return tvb:len()");
+            self.out.line("end");
+        }
+        self.out.line("return offset");
+        self.out.close("end");
+    }
+
+    fn build_message_names(&mut self, fields: &[MessageField]) {
+        for field in fields {
+            for number in &field.numbers {
+                let label = match field.label {
+                    Label::Repeated => "repeated ",
+                    Label::Optional => "optional ",
+                    _ => "",
+                };
+                self.out.line(&format!(
+                    "  [{}] = {:?},",
+                    number,
+                    format!("{} ({}{})", field.name, label, field.ty)
+                ));
+            }
+        }
+    }
+
+    fn build_field(&mut self, tree: &str, field: &Field, scope: &mut Scope) {
+        let name = attr_id(&field.name);
+
+        if let Some(pad) = &field.pad {
+            self.out
+                .line(&format!("offset = offset + {}", expr(pad, scope)));
+        }
+        if let Some(align) = &field.align {
+            let align = expr(align, scope);
+            self.out.line(&format!(
+                "offset = offset + ({0} - (offset - start) % {0}) % {0}",
+                align
+            ));
+        }
+
+        let mut conditions = version_checks(&field.since, &field.until);
+        if let Some(when) = &field.when {
+            conditions.push(format!("({})", expr(when, scope)));
+        }
+        if !conditions.is_empty() {
+            self.out
+                .open(&format!("if {} then", conditions.join(" and ")));
+        }
+        self.build_value(
+            tree,
+            &field.name,
+            &field.encoding,
+            scope,
+            Some(&format!("v.{}", name)),
+        );
+        if !conditions.is_empty() {
+            self.out.close("end");
+        }
+        scope.fields.push(field.name.clone());
+    }
+
+    /// Adds a value to `tree` and moves `offset` after it. Numbers, bools and strings are also
+    /// stored in `store`, for the expressions of later fields.
+    fn build_value(
+        &mut self,
+        tree: &str,
+        label: &str,
+        encoding: &Encoding,
+        scope: &mut Scope,
+        store: Option<&str>,
+    ) {
+        match encoding {
+            Encoding::Number { name } => {
+                let Some(number) = Number::parse(name) else {
+                    self.out.line(&format!(
+                        "{}:add(tvb(offset, 16), {:?})",
+                        tree,
+                        format!("{}: {}", label, name)
+                    ));
+                    self.out.line("offset = offset + 16");
+                    return;
+                };
+                let field = self.field(scope, label, number.field_type(), "base.DEC");
+                self.out.open("do");
+                self.out.line(&number.read("value"));
+                self.out.line(&format!(
+                    "{}:add({}, tvb(offset, size), value)",
+                    tree, field
+                ));
+                if let Some(store) = store {
+                    self.out
+                        .line(&format!("{} = {}", store, number.to_number("value")));
+                }
+                self.out.line("offset = offset + size");
+                self.out.close("end");
+            }
+            Encoding::Bool => {
+                let field = self.field(scope, label, "bool", "base.NONE");
+                self.out
+                    .line(&format!("{}:add({}, tvb(offset, 1))", tree, field));
+                if let Some(store) = store {
+                    self.out
+                        .line(&format!("{} = tvb(offset, 1):uint() ~= 0", store));
+                }
+                self.out.line("offset = offset + 1");
+            }
+            Encoding::String { length } => {
+                let field = self.field(scope, label, "string", "base.ASCII");
+                self.out.open("do");
+                self.build_length(tree, label, length);
+                self.out
+                    .line(&format!("{}:add({}, tvb(offset, len))", tree, field));
+                if let Some(store) = store {
+                    self.out
+                        .line(&format!("{} = tvb(offset, len):string()", store));
+                }
+                self.out.line("offset = offset + len");
+                self.out.close("end");
+            }
+            Encoding::List { length, item } if is_u8(item) => {
+                let field = self.field(scope, label, "bytes", "base.NONE");
+                self.out.open("do");
+                self.build_length(tree, label, length);
+                self.out
+                    .line(&format!("{}:add({}, tvb(offset, len))", tree, field));
+                self.out.line("offset = offset + len");
+                self.out.close("end");
+            }
+            Encoding::List { length, item } => {
+                self.out.open("do");
+                self.out.line(&format!(
+                    "local list = {}:add(tvb(offset, 0), {:?})",
+                    tree, label
+                ));
+                self.out.line("local list_start = offset");
+                match length {
+                    Length::Prefix { .. } | Length::CountFrom { .. } => {
+                        self.build_length("list", label, length);
+                        self.out.open("for i = 1, len do");
+                    }
+                    Length::ByteLenFrom { .. } => {
+                        self.build_length("list", label, length);
+                        self.out.line("local stop = offset + len");
+                        self.out.open("while offset < stop do");
+                    }
+                    Length::Terminator { value } => {
+                        let (check, len) = terminator_check(value);
+                        self.out.open(&format!("while {} do", check));
+                        self.build_value("list", label, item, scope, None);
+                        self.out.close("end");
+                        self.out.line(&format!("offset = offset + {}", len));
+                        self.close_list();
+                        return;
+                    }
+                    Length::Predicate { .. } | Length::Eof => {
+                        self.out.open("while offset < tvb:len() do");
+                    }
+                }
+                self.build_value("list", label, item, scope, None);
+                self.out.close("end");
+                self.close_list();
+            }
+            Encoding::Array { len, item } => {
+                self.out.open("do");
+                self.out.line(&format!(
+                    "local list = {}:add(tvb(offset, 0), {:?})",
+                    tree, label
+                ));
+                self.out.line("local list_start = offset");
+                self.out.open(&format!("for i = 1, {} do", len));
+                self.build_value("list", label, item, scope, None);
+                self.out.close("end");
+                self.close_list();
+            }
+            Encoding::Tuple { items } => {
+                for (i, item) in items.iter().enumerate() {
+                    self.build_value(tree, &format!("{}.{}", label, i), item, scope, None);
+                }
+            }
+            Encoding::Map { length, key, value } => {
+                self.out.open("do");
+                self.out.line(&format!(
+                    "local list = {}:add(tvb(offset, 0), {:?})",
+                    tree, label
+                ));
+                self.out.line("local list_start = offset");
+                self.build_length(
+                    "list",
+                    label,
+                    &Length::Prefix {
+                        encoding: length.clone(),
+                    },
+                );
+                self.out.open("for i = 1, len do");
+                self.build_value("list", &format!("{}.key", label), key, scope, None);
+                self.build_value("list", &format!("{}.value", label), value, scope, None);
+                self.out.close("end");
+                self.close_list();
+            }
+            Encoding::Optional { value } => {
+                self.out.line(&format!(
+                    "{}:add(tvb(offset, 1), {:?} .. (tvb(offset, 1):uint() ~= 0 and \"present\" or \"absent\"))",
+                    tree,
+                    format!("{}: ", label)
+                ));
+                self.out.line("offset = offset + 1");
+                self.out.open("if tvb(offset - 1, 1):uint() ~= 0 then");
+                self.build_value(tree, label, value, scope, store);
+                self.out.close("end");
+            }
+            Encoding::Constant(constant) => {
+                self.build_value(tree, label, &constant.encoding, scope, store);
+            }
+            Encoding::Type { name } => {
+                if self.schemas.iter().any(|schema| schema.name == *name) {
+                    self.out.line(&format!(
+                        "offset = dissect.{}(tvb, pinfo, offset, {})",
+                        type_id(name),
+                        tree
+                    ));
+                } else {
+                    self.build_unknown(tree, &format!("{}: {}", label, name));
+                }
+            }
+            Encoding::Untagged { name, tag } => {
+                let is_enum = self.schemas.iter().any(|schema| {
+                    schema.name == *name && matches!(schema.layout, Layout::Enum { .. })
+                });
+                if is_enum {
+                    self.out.open("do");
+                    self.out.line(&format!(
+                        "local variant = {}:add(tvb(offset, 0), {:?})",
+                        tree, label
+                    ));
+                    self.out.line("local variant_start = offset");
+                    self.out.line(&format!(
+                        "offset = dissect.{}_body(tvb, pinfo, offset, variant, v.{})",
+                        type_id(name),
+                        attr_id(tag)
+                    ));
+                    self.out.line("variant:set_len(offset - variant_start)");
+                    self.out.close("end");
+                } else {
+                    self.build_unknown(tree, &format!("{}: {}", label, name));
+                }
+            }
+            Encoding::SizePrefixed { length, value } => {
+                self.out.open("do");
+                self.build_length(
+                    tree,
+                    label,
+                    &Length::Prefix {
+                        encoding: length.clone(),
+                    },
+                );
+                self.out.line("local value_start = offset");
+                self.build_value(tree, label, value, scope, store);
+                self.out.line("offset = value_start + len");
+                self.out.close("end");
+            }
+            Encoding::Custom { .. } => {
+                self.build_unknown(tree, &format!("{}: custom encoding", label));
+            }
+        }
+    }
+
+    /// Reads the length of a string or list into `len`.
+    fn build_length(&mut self, tree: &str, label: &str, length: &Length) {
+        match length {
+            Length::Prefix { encoding } => {
+                let number = match &**encoding {
+                    Encoding::Number { name } => Number::parse(name),
+                    _ => None,
+                };
+                match number {
+                    Some(number) => {
+                        self.out.line(&number.read("len"));
+                        if number.wide() {
+                            self.out.line("len = to_number(len)");
+                        }
+                        self.out.line(&format!(
+                            "{}:add(tvb(offset, size), {:?} .. len)",
+                            tree,
+                            format!("{} length: ", label)
+                        ));
+                        self.out.line("offset = offset + size");
+                    }
+                    None => self.out.line("local len = 0"),
+                }
+            }
+            Length::CountFrom { field } | Length::ByteLenFrom { field } => {
+                self.out.line(&format!("local len = v.{}", attr_id(field)));
+            }
+            Length::Terminator { value } => {
+                let (check, _) = terminator_check(value);
+                self.out.line("local len = 0");
+                self.out.line(&format!(
+                    "while {} do len = len + 1 end",
+                    check.replace("offset", "offset + len")
+                ));
+            }
+            Length::Predicate { .. } | Length::Eof => {
+                self.out.line("local len = tvb:len() - offset");
+            }
+        }
+    }
+
+    fn close_list(&mut self) {
+        self.out.line("list:set_len(offset - list_start)");
+        self.out.close("end");
+    }
+
+    /// Values that can't be read from a schema end the dissection.
+    fn build_unknown(&mut self, tree: &str, text: &str) {
+        self.out.line(&format!(
+            "{}:add(tvb(offset), {:?})",
+            tree,
+            format!("{} (not described)", text)
+        ));
+        self.out.line("offset = tvb:len()");
+    }
+
+    /// Declares a `ProtoField` and returns the Lua expression of it.
+    fn field(&mut self, scope: &Scope, label: &str, ty: &str, base: &str) -> String {
+        let key = format!("{}_{}", scope.name, attr_id(&label.replace('.', "_")));
+        if !self.fields.iter().any(|(existing, _)| *existing == key) {
+            let abbr = format!("{}.{}.{}", self.protocol, scope.name, attr_id(label));
+            let declaration = match ty {
+                "bool" | "string" | "bytes" => {
+                    format!("f.{} = ProtoField.{}({:?}, {:?})", key, ty, abbr, label)
+                }
+                _ => format!(
+                    "f.{} = ProtoField.{}({:?}, {:?}, {})",
+                    key, ty, abbr, label, base
+                ),
+            };
+            self.fields.push((key.clone(), declaration));
+        }
+        format!("f.{}", key)
+    }
+}
+
+/// A number like `BE<u16>` or `Var<i32>`.
+struct Number {
+    varint: bool,
+    signed: bool,
+    float: bool,
+    little_endian: bool,
+    bytes: usize,
+}
+
+impl Number {
+    fn parse(name: &str) -> Option<Self> {
+        let (wrapper, inner) = match name.split_once('<') {
+            Some((wrapper, inner)) => (wrapper, inner.trim_end_matches('>')),
+            None => ("", name),
+        };
+        let (kind, bits) = inner.split_at(1);
+        let bytes = bits.parse::<usize>().ok()? / 8;
+        if !matches!(kind, "u" | "i" | "f") || bytes > 8 || (bytes > 1 && wrapper.is_empty()) {
+            return None;
+        }
+
+        Some(Number {
+            varint: wrapper == "Var",
+            signed: kind == "i",
+            float: kind == "f",
+            little_endian: wrapper == "LE",
+            bytes,
+        })
+    }
+
+    fn field_type(&self) -> &'static str {
+        match (self.float, self.signed, self.bytes) {
+            (true, _, 4) => "float",
+            (true, _, _) => "double",
+            (_, false, 1) => "uint8",
+            (_, false, 2) => "uint16",
+            (_, false, 4) => "uint32",
+            (_, false, _) => "uint64",
+            (_, true, 1) => "int8",
+            (_, true, 2) => "int16",
+            (_, true, 4) => "int32",
+            (_, true, _) => "int64",
+        }
+    }
+
+    /// Reads the number at `offset` into `var` and its size in bytes into `size`. 64-bit integers
+    /// are read as a `UInt64` or `Int64` so they are shown exactly.
+    fn read(&self, var: &str) -> String {
+        if self.varint {
+            let value = if self.signed {
+                format!(
+                    "local {0}, size = read_varint(tvb, offset)\n{0} = zigzag({0})",
+                    var
+                )
+            } else {
+                format!("local {}, size = read_varint(tvb, offset)", var)
+            };
+            return value;
+        }
+
+        let method = match (self.float, self.signed, self.bytes) {
+            (true, _, _) => "float",
+            (_, false, 8) => "uint64",
+            (_, true, 8) => "int64",
+            (_, false, _) => "uint",
+            (_, true, _) => "int",
+        };
+        let prefix = if self.little_endian { "le_" } else { "" };
+        format!(
+            "local size = {}\nlocal {} = tvb(offset, size):{}{}()",
+            self.bytes, var, prefix, method
+        )
+    }
+
+    /// Whether values can be a `UInt64` or `Int64`, which expressions can't compute with.
+    fn wide(&self) -> bool {
+        self.bytes == 8 && !self.float
+    }
+
+    /// The value of `var` as a Lua number, for expressions and lengths.
+    fn to_number(&self, var: &str) -> String {
+        if self.wide() {
+            format!("to_number({})", var)
+        } else {
+            var.to_string()
+        }
+    }
+}
+
+fn is_u8(encoding: &Encoding) -> bool {
+    matches!(encoding, Encoding::Number { name } if name == "u8")
+}
+
+/// A check that the bytes at `offset` aren't the terminator, and the terminator's length.
+fn terminator_check(value: &str) -> (String, usize) {
+    let bytes = match value.strip_prefix("b\"") {
+        Some(_) => value
+            .trim_start_matches("b\"")
+            .trim_end_matches('"')
+            .bytes()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<String>(),
+        None => format!("{:02x}", value.parse::<u8>().unwrap_or(0)),
+    };
+    let len = bytes.len() / 2;
+    (
+        format!("tvb:bytes(offset, {}):tohex():lower() ~= {:?}", len, bytes),
+        len,
+    )
+}
+
+/// A Lua condition matching a tag against one alternative of a pattern.
+fn pattern_check(pattern: &str) -> String {
+    let pattern = pattern.trim();
+    if pattern == "_" {
+        return String::from("true");
+    }
+    if let Some((low, high)) = pattern.split_once("..=") {
+        return format!("(tag >= {} and tag <= {})", low.trim(), high.trim());
+    }
+    if let Some((low, high)) = pattern.split_once("..") {
+        return format!("(tag >= {} and tag < {})", low.trim(), high.trim());
+    }
+    format!("tag == {}", pattern)
+}
+
+fn version_checks(since: &Option<String>, until: &Option<String>) -> Vec<String> {
+    let mut checks = vec![];
+    if let Some(since) = since {
+        checks.push(format!("proto.prefs.version >= {}", since));
+    }
+    if let Some(until) = until {
+        checks.push(format!("proto.prefs.version < {}", until));
+    }
+    checks
+}
+
+/// Converts a Rust expression to Lua, reading earlier fields from `v`.
+fn expr(rust: &str, scope: &Scope) -> String {
+    let mut tokens: Vec<String> = vec![];
+    for token in rust.split_whitespace() {
+        let unary = tokens.last().is_none_or(|last| {
+            !last.ends_with(|c: char| c.is_alphanumeric() || c == ')' || c == '_')
+        });
+        match token {
+            "*" | "&" if unary => {}
+            "&&" => tokens.push(String::from("and")),
+            "||" => tokens.push(String::from("or")),
+            "!=" => tokens.push(String::from("~=")),
+            "!" => tokens.push(String::from("not")),
+            _ if scope.fields.iter().any(|field| field == token) => {
+                tokens.push(format!("v.{}", attr_id(token)));
+            }
+            _ => tokens.push(String::from(token)),
+        }
+    }
+    tokens
+        .join(" ")
+        .replace(" . ", ".")
+        .replace("( ", "(")
+        .replace(" )", ")")
+}
+
+/// Reads protobuf fields until `stop`, naming them with `names`, or only `limit` of them.
+const PROTOBUF: &str = r#"dissect.protobuf = function(tvb, offset, stop, tree, names, limit)
+  local count = 0
+  while offset < stop and (limit == nil or count < limit) do
+    local key, key_size = read_varint(tvb, offset)
+    local number, wire_type = math.floor(key / 8), key % 8
+    local start = offset
+    offset = offset + key_size
+    local value
+    if wire_type == 0 then
+      local size
+      value, size = read_varint(tvb, offset)
+      offset = offset + size
+    elseif wire_type == 1 then
+      value = tvb(offset, 8):le_uint64()
+      offset = offset + 8
+    elseif wire_type == 2 then
+      local len, size = read_varint(tvb, offset)
+      value = tvb(offset + size, len)
+      offset = offset + size + len
+    elseif wire_type == 5 then
+      value = tvb(offset, 4):le_uint()
+      offset = offset + 4
+    else
+      tree:add_expert_info(PI_MALFORMED, PI_ERROR, "Unknown wire type " .. wire_type)
+      return stop
+    end
+    tree:add(tvb(start, offset - start), (names[number] or ("field " .. number)) .. ": " .. tostring(value))
+    count = count + 1
+  end
+  return offset
+end"#;
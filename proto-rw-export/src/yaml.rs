@@ -0,0 +1,95 @@
+//! A small YAML writer for the generated files, which only need block maps and lists.
+
+use std::fmt;
+
+#[derive(Clone)]
+pub(crate) enum Yaml {
+    /// A value written as is, like a number or a flow list.
+    Scalar(String),
+    List(Vec<Yaml>),
+    Map(Vec<(String, Yaml)>),
+}
+
+impl Yaml {
+    /// A string, quoted when YAML would read it as something else.
+    pub(crate) fn string(value: impl Into<String>) -> Self {
+        Yaml::Scalar(quote(&value.into()))
+    }
+
+    pub(crate) fn raw(value: impl Into<String>) -> Self {
+        Yaml::Scalar(value.into())
+    }
+
+    fn write(&self, f: &mut fmt::Formatter, indent: usize) -> fmt::Result {
+        match self {
+            Yaml::Scalar(value) => writeln!(f, " {}", value),
+            Yaml::List(items) if items.is_empty() => writeln!(f, " []"),
+            Yaml::Map(entries) if entries.is_empty() => writeln!(f, " {{}}"),
+            Yaml::List(items) => {
+                writeln!(f)?;
+                for item in items {
+                    write!(f, "{:indent$}-", "", indent = indent)?;
+                    match item {
+                        // The first key of a map goes on the line of its dash
+                        Yaml::Map(entries) => {
+                            for (i, (key, value)) in entries.iter().enumerate() {
+                                if i == 0 {
+                                    write!(f, " {}:", quote(key))?;
+                                } else {
+                                    write!(
+                                        f,
+                                        "{:indent$}{}:",
+                                        "",
+                                        quote(key),
+                                        indent = indent + 2
+                                    )?;
+                                }
+                                value.write(f, indent + 4)?;
+                            }
+                        }
+                        _ => item.write(f, indent + 2)?,
+                    }
+                }
+                Ok(())
+            }
+            Yaml::Map(entries) => {
+                writeln!(f)?;
+                for (key, value) in entries {
+                    write!(f, "{:indent$}{}:", "", quote(key), indent = indent)?;
+                    value.write(f, indent + 2)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Writes a map or list as a document.
+impl fmt::Display for Yaml {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Yaml::Map(entries) => {
+                for (key, value) in entries {
+                    write!(f, "{}:", quote(key))?;
+                    value.write(f, 2)?;
+                }
+                Ok(())
+            }
+            _ => self.write(f, 0),
+        }
+    }
+}
+
+fn quote(value: &str) -> String {
+    let special = value.is_empty()
+        || value.starts_with(|c: char| c.is_whitespace() || "-?:,[]{}#&*!|>'\"%@`".contains(c))
+        || value.ends_with(char::is_whitespace)
+        || value.contains(": ")
+        || value.contains(" #")
+        || matches!(value, "true" | "false" | "null" | "yes" | "no" | "~");
+    if special {
+        format!("'{}'", value.replace('\'', "''"))
+    } else {
+        String::from(value)
+    }
+}
@@ -0,0 +1,83 @@
+use std::cell::Cell;
+
+use crate::error::ProtoRwError;
+
+/// State passed down while reading or writing a value.
+///
+/// Reading tracks how deep it is nested in the context, so a context isn't `Sync`: clone it to
+/// read on several threads. Clones start at depth zero, and only the version and `max_depth` are
+/// compared.
+#[derive(Debug)]
+pub struct Context {
+    /// Negotiated protocol version, checked by `#[since]` and `#[until]`.
+    pub version: u32,
+    /// How deep `#[proto_rw]` types can be nested while reading.
+    pub max_depth: usize,
+    depth: Cell<usize>,
+}
+
+impl Context {
+    pub fn new(version: u32) -> Self {
+        Context {
+            version,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Enters a nested value while reading. The depth goes back down when the guard is dropped.
+    pub fn enter(&self) -> Result<DepthGuard<'_>, ProtoRwError> {
+        let depth = self.depth.get() + 1;
+        if depth > self.max_depth {
+            return Err(ProtoRwError::DepthLimit(self.max_depth));
+        }
+
+        self.depth.set(depth);
+        Ok(DepthGuard(self))
+    }
+}
+
+impl Clone for Context {
+    fn clone(&self) -> Self {
+        Context {
+            version: self.version,
+            max_depth: self.max_depth,
+            depth: Cell::new(0),
+        }
+    }
+}
+
+impl PartialEq for Context {
+    fn eq(&self, other: &Self) -> bool {
+        self.version == other.version && self.max_depth == other.max_depth
+    }
+}
+
+impl Eq for Context {}
+
+impl Default for Context {
+    /// Uses the latest version, `u32::MAX`, so every `#[since]` field is present and every
+    /// `#[until]` field is absent. This is the context of `read_proto` and `write_proto`: they
+    /// never read `#[until]` fields, and fail to write them unless they are `None` or the default.
+    /// Use `Context::new` with the negotiated version to read or write older layouts.
+    fn default() -> Self {
+        Context {
+            version: u32::MAX,
+            max_depth: 128,
+            depth: Cell::new(0),
+        }
+    }
+}
+
+/// Returned by `Context::enter`.
+pub struct DepthGuard<'a>(&'a Context);
+
+impl Drop for DepthGuard<'_> {
+    fn drop(&mut self) {
+        self.0.depth.set(self.0.depth.get() - 1);
+    }
+}
@@ -3,16 +3,22 @@ pub enum ProtoRwError {
     FromUtf8Error(std::string::FromUtf8Error),
     UnexpectedEof,
     Error(String),
+    Validation {
+        field: &'static str,
+        message: String,
+    },
+    UnexpectedValue {
+        expected: String,
+        found: String,
+    },
+    DepthLimit(usize),
 }
 
 impl ProtoRwError {
+    /// Kept for callers of the inherent method, it gives the same text as `Display`.
+    #[allow(clippy::inherent_to_string_shadow_display)]
     pub fn to_string(&self) -> String {
-        match self {
-            ProtoRwError::IoError(e) => format!("Io error: {}", e),
-            ProtoRwError::FromUtf8Error(e) => format!("FromUtf8 error: {}", e),
-            ProtoRwError::UnexpectedEof => "Unexpected EOF".to_string(),
-            ProtoRwError::Error(e) => format!("{}", e),
-        }
+        format!("{}", self)
     }
 }
 
@@ -32,12 +38,26 @@ impl From<std::string::FromUtf8Error> for ProtoRwError {
 
 impl std::fmt::Display for ProtoRwError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{}", self.to_string())
+        match self {
+            ProtoRwError::IoError(e) => write!(f, "Io error: {}", e),
+            ProtoRwError::FromUtf8Error(e) => write!(f, "FromUtf8 error: {}", e),
+            ProtoRwError::UnexpectedEof => write!(f, "Unexpected EOF"),
+            ProtoRwError::Error(e) => write!(f, "{}", e),
+            ProtoRwError::Validation { field, message } => {
+                write!(f, "Invalid {}: {}", field, message)
+            }
+            ProtoRwError::UnexpectedValue { expected, found } => {
+                write!(f, "Expected {} but found {}", expected, found)
+            }
+            ProtoRwError::DepthLimit(max_depth) => {
+                write!(f, "Nested deeper than the maximum depth of {}", max_depth)
+            }
+        }
     }
 }
 
 impl std::fmt::Debug for ProtoRwError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{}", self.to_string())
+        write!(f, "{}", self)
     }
 }
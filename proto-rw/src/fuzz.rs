@@ -0,0 +1,149 @@
+//! Checks for fuzz targets decoding untrusted input, enabled by the `fuzz` feature.
+//!
+//! ```ignore
+//! #![no_main]
+//!
+//! #[global_allocator]
+//! static ALLOCATOR: proto_rw::fuzz::TrackingAllocator = proto_rw::fuzz::TrackingAllocator;
+//!
+//! libfuzzer_sys::fuzz_target!(|data: &[u8]| {
+//!     let _ = proto_rw::fuzz::fuzz_decode::<String>(data);
+//! });
+//! ```
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use bytes::{Bytes, BytesMut};
+
+use crate::{error::ProtoRwError, ProtoRw};
+
+/// Bytes that decoding any input may allocate, on top of `MAX_ALLOC_PER_BYTE`.
+pub const MAX_ALLOC_BASE: usize = 1 << 20;
+/// Bytes that decoding may allocate for each byte of input.
+pub const MAX_ALLOC_PER_BYTE: usize = 256;
+
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+
+// Counted per thread, so decoding on other threads doesn't count against the limit
+thread_local! {
+    static ALLOCATED: Cell<isize> = const { Cell::new(0) };
+    static PEAK: Cell<isize> = const { Cell::new(0) };
+}
+
+/// Global allocator keeping track of the most bytes allocated at once by each thread, so
+/// `fuzz_decode` can check what decoding allocated. Allocations aren't checked unless it is the
+/// global allocator.
+pub struct TrackingAllocator;
+
+impl TrackingAllocator {
+    fn add(size: usize) {
+        INSTALLED.store(true, Ordering::Relaxed);
+        // Thread locals are gone while a thread is torn down
+        let _ = ALLOCATED.try_with(|allocated| {
+            let new = allocated.get().saturating_add_unsigned(size);
+            allocated.set(new);
+            let _ = PEAK.try_with(|peak| peak.set(peak.get().max(new)));
+        });
+    }
+
+    fn sub(size: usize) {
+        // Goes below zero when a thread frees memory allocated by another one
+        let _ = ALLOCATED
+            .try_with(|allocated| allocated.set(allocated.get().saturating_sub_unsigned(size)));
+    }
+}
+
+unsafe impl GlobalAlloc for TrackingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            Self::add(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            Self::add(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        Self::sub(layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            Self::add(new_size);
+            Self::sub(layout.size());
+        }
+        new_ptr
+    }
+}
+
+/// Decodes `data` as `T` and panics if decoding misbehaves:
+///
+/// - decoding allocates more than `MAX_ALLOC_BASE` plus `MAX_ALLOC_PER_BYTE` for each byte, when
+///   `TrackingAllocator` is the global allocator,
+/// - a decoded value can't be encoded, or its encoding can't be decoded back,
+/// - encoding the value decoded back gives different bytes.
+///
+/// The encoding doesn't have to match `data`, which can hold ignored bytes or non-minimal
+/// varints. Decoding errors are returned, as untrusted input is expected to fail.
+pub fn fuzz_decode<T: ProtoRw>(data: &[u8]) -> Result<T, ProtoRwError> {
+    let mut buf = Bytes::copy_from_slice(data);
+
+    let start = ALLOCATED.with(Cell::get);
+    PEAK.with(|peak| peak.set(start));
+    let value = T::read_proto(&mut buf);
+    let allocated = PEAK.with(Cell::get).abs_diff(start);
+    if INSTALLED.load(Ordering::Relaxed) {
+        let max = MAX_ALLOC_BASE.saturating_add(data.len().saturating_mul(MAX_ALLOC_PER_BYTE));
+        assert!(
+            allocated <= max,
+            "Decoding {} bytes allocated {} bytes, more than the limit of {}",
+            data.len(),
+            allocated,
+            max
+        );
+    }
+    let value = value?;
+
+    let encoded = encode(&value, "decoded from the input");
+    let mut buf = encoded.clone().freeze();
+    let decoded = match T::read_proto(&mut buf) {
+        Ok(decoded) => decoded,
+        Err(error) => panic!(
+            "The encoding of a decoded value can't be decoded: {}",
+            error
+        ),
+    };
+    assert!(
+        buf.is_empty(),
+        "{} bytes were left after decoding the encoding of a decoded value",
+        buf.len()
+    );
+    assert_eq!(
+        encoded,
+        encode(&decoded, "decoded from its encoding"),
+        "Encoding a value decoded from its encoding gave different bytes"
+    );
+
+    Ok(value)
+}
+
+fn encode<T: ProtoRw>(value: &T, from: &str) -> BytesMut {
+    let mut buf = BytesMut::new();
+    if let Err(error) = value.write_proto(&mut buf) {
+        panic!("A value {} can't be encoded: {}", from, error);
+    }
+    buf
+}
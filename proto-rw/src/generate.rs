@@ -0,0 +1,217 @@
+//! Support for values generated by the `arbitrary`, `proptest` and `roundtrip_test` options of
+//! `#[proto_rw]`, which only create values that can be written and read back.
+
+use std::{
+    cell::Cell,
+    ops::{Bound, RangeBounds},
+};
+
+use crate::types::{Const, Var, BE, LE};
+
+/// Most items in the collections of proptest values, unless their length prefix allows less.
+pub const MAX_COLLECTION_LEN: usize = 16;
+
+/// How deep generated values can be nested in values of their own type, like `Box<Self>`.
+pub const MAX_DEPTH: usize = 8;
+
+thread_local! {
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Whether a generated value can hold another value of its type, which needs input left and
+/// fewer than `MAX_DEPTH` values around it. Otherwise its `Option`s and collections holding
+/// itself are left empty, and variants always holding itself aren't picked.
+pub fn can_recurse(input_left: bool) -> bool {
+    input_left && DEPTH.with(Cell::get) < MAX_DEPTH
+}
+
+/// Generates a value nested in a value of its own type.
+pub fn recurse<T>(generate: impl FnOnce() -> T) -> T {
+    struct Guard;
+
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            DEPTH.with(|depth| depth.set(depth.get() - 1));
+        }
+    }
+
+    DEPTH.with(|depth| depth.set(depth.get() + 1));
+    let _guard = Guard;
+    generate()
+}
+
+/// Length prefixes, which limit the number of items of a generated collection.
+pub trait MaxLen {
+    const MAX_LEN: usize;
+}
+
+macro_rules! impl_max_len {
+    ($($t:ty),*) => {
+        $(
+            impl MaxLen for $t {
+                const MAX_LEN: usize = if <$t>::MAX as u128 > usize::MAX as u128 {
+                    usize::MAX
+                } else {
+                    <$t>::MAX as usize
+                };
+            }
+        )*
+    };
+}
+
+impl_max_len!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+
+impl<T: MaxLen> MaxLen for LE<T> {
+    const MAX_LEN: usize = T::MAX_LEN;
+}
+
+impl<T: MaxLen> MaxLen for BE<T> {
+    const MAX_LEN: usize = T::MAX_LEN;
+}
+
+impl<T: MaxLen> MaxLen for Var<T> {
+    const MAX_LEN: usize = T::MAX_LEN;
+}
+
+/// Integers generated directly from the range of `#[validate(range = ...)]`.
+pub trait Bounded: Copy + PartialOrd {
+    fn checked_next(self) -> Option<Self>;
+    fn checked_previous(self) -> Option<Self>;
+    fn min_value() -> Self;
+    fn max_value() -> Self;
+}
+
+macro_rules! impl_bounded {
+    ($($t:ty),*) => {
+        $(
+            impl Bounded for $t {
+                fn checked_next(self) -> Option<Self> {
+                    self.checked_add(1)
+                }
+
+                fn checked_previous(self) -> Option<Self> {
+                    self.checked_sub(1)
+                }
+
+                fn min_value() -> Self {
+                    <$t>::MIN
+                }
+
+                fn max_value() -> Self {
+                    <$t>::MAX
+                }
+            }
+        )*
+    };
+}
+
+impl_bounded!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+
+/// Returns the lowest and highest values of a range, or `None` when it is empty.
+pub fn bounds<T: Bounded>(range: &impl RangeBounds<T>) -> Option<(T, T)> {
+    let low = match range.start_bound() {
+        Bound::Included(low) => *low,
+        Bound::Excluded(low) => low.checked_next()?,
+        Bound::Unbounded => T::min_value(),
+    };
+    let high = match range.end_bound() {
+        Bound::Included(high) => *high,
+        Bound::Excluded(high) => high.checked_previous()?,
+        Bound::Unbounded => T::max_value(),
+    };
+
+    (low <= high).then_some((low, high))
+}
+
+/// Values cut to the length of `#[validate(max_len = ...)]`.
+pub trait Truncate {
+    fn truncate_len(&mut self, len: usize);
+}
+
+impl<T> Truncate for Vec<T> {
+    fn truncate_len(&mut self, len: usize) {
+        self.truncate(len);
+    }
+}
+
+impl Truncate for String {
+    /// Keeps the first `len` characters, as `max_len` counts characters of strings.
+    fn truncate_len(&mut self, len: usize) {
+        if let Some((end, _)) = self.char_indices().nth(len) {
+            self.truncate(end);
+        }
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+mod arbitrary_impls {
+    use arbitrary::{Arbitrary, Result, Unstructured};
+
+    use super::*;
+
+    macro_rules! impl_wrapper {
+        ($($wrapper:ident),*) => {
+            $(
+                impl<'a, T: Arbitrary<'a>> Arbitrary<'a> for $wrapper<T> {
+                    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+                        T::arbitrary(u).map($wrapper)
+                    }
+
+                    fn size_hint(depth: usize) -> (usize, Option<usize>) {
+                        T::size_hint(depth)
+                    }
+                }
+            )*
+        };
+    }
+
+    impl_wrapper!(LE, BE, Var);
+
+    impl<'a, T, const V: i128> Arbitrary<'a> for Const<T, V> {
+        fn arbitrary(_u: &mut Unstructured<'a>) -> Result<Self> {
+            Ok(Const::new())
+        }
+
+        fn size_hint(_depth: usize) -> (usize, Option<usize>) {
+            (0, Some(0))
+        }
+    }
+}
+
+#[cfg(feature = "proptest")]
+mod proptest_impls {
+    use std::fmt::Debug;
+
+    use proptest::{
+        arbitrary::{Arbitrary, StrategyFor},
+        strategy::{Just, Map, Strategy},
+    };
+
+    use super::*;
+
+    macro_rules! impl_wrapper {
+        ($($wrapper:ident),*) => {
+            $(
+                impl<T: Arbitrary> Arbitrary for $wrapper<T> {
+                    type Parameters = T::Parameters;
+                    type Strategy = Map<StrategyFor<T>, fn(T) -> Self>;
+
+                    fn arbitrary_with(args: Self::Parameters) -> Self::Strategy {
+                        T::arbitrary_with(args).prop_map($wrapper)
+                    }
+                }
+            )*
+        };
+    }
+
+    impl_wrapper!(LE, BE, Var);
+
+    impl<T: Clone + Debug, const V: i128> Arbitrary for Const<T, V> {
+        type Parameters = ();
+        type Strategy = Just<Self>;
+
+        fn arbitrary_with(_args: Self::Parameters) -> Self::Strategy {
+            Just(Const::new())
+        }
+    }
+}
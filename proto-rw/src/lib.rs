@@ -1,12 +1,72 @@
 use bytes::{Bytes, BytesMut};
+use context::Context;
 use error::ProtoRwError;
 
+pub mod context;
 pub mod error;
+#[cfg(feature = "fuzz")]
+pub mod fuzz;
+#[cfg(any(feature = "arbitrary", feature = "proptest"))]
+pub mod generate;
+pub mod protobuf;
+pub mod schema;
 pub mod types;
 
 pub extern crate macros;
 
+#[cfg(feature = "arbitrary")]
+pub use arbitrary;
+#[cfg(feature = "proptest")]
+pub use proptest;
+
 pub trait ProtoRw: Sized {
     fn read_proto(buf: &mut Bytes) -> Result<Self, ProtoRwError>;
     fn write_proto(&self, buf: &mut BytesMut) -> Result<(), ProtoRwError>;
+
+    /// Reads with the given context. Types that don't depend on it just call `read_proto`.
+    fn read_proto_ctx(buf: &mut Bytes, _ctx: &Context) -> Result<Self, ProtoRwError> {
+        Self::read_proto(buf)
+    }
+
+    /// Writes with the given context. Types that don't depend on it just call `write_proto`.
+    fn write_proto_ctx(&self, buf: &mut BytesMut, _ctx: &Context) -> Result<(), ProtoRwError> {
+        self.write_proto(buf)
+    }
+}
+
+/// Enums whose tag can be read and written separately from the variant body.
+pub trait ProtoRwTagged: ProtoRw {
+    type Tag;
+
+    /// Fails if the variant has no id in the context's version.
+    fn tag(&self, ctx: &Context) -> Result<Self::Tag, ProtoRwError>;
+    fn read_proto_tagged(
+        tag: &Self::Tag,
+        buf: &mut Bytes,
+        ctx: &Context,
+    ) -> Result<Self, ProtoRwError>;
+    fn write_proto_untagged(&self, buf: &mut BytesMut, ctx: &Context) -> Result<(), ProtoRwError>;
+}
+
+/// Types written as a wrapper around a plain value, like `LE`, `BE` and `Var`.
+///
+/// Fields marked `#[transparent]` hold `Inner` and are converted on read and write.
+pub trait ProtoRwWrapper: ProtoRw {
+    type Inner;
+
+    fn from_inner(inner: Self::Inner) -> Self;
+    fn into_inner(self) -> Self::Inner;
+}
+
+/// Types that can describe their wire layout, implemented by `#[proto_rw]`.
+pub trait ProtoSchema {
+    fn schema() -> schema::Schema;
+}
+
+/// Packets registered with `packets!` in the `Registry` enum of a state and direction, which
+/// knows their id there. A packet registered in several of them has an id in each.
+pub trait Packet<Registry>: ProtoRw {
+    type Id;
+
+    const ID: Self::Id;
 }
@@ -0,0 +1,399 @@
+//! The Protocol Buffers wire format, used by `#[proto_rw(protobuf)]` messages.
+
+use std::borrow::Borrow;
+
+use bytes::{Buf, Bytes, BytesMut};
+
+use crate::{
+    context::Context,
+    error::ProtoRwError,
+    types::{Var, LE},
+    ProtoRw,
+};
+
+/// How the value of a field is encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WireType {
+    Varint = 0,
+    I64 = 1,
+    Len = 2,
+    I32 = 5,
+}
+
+impl TryFrom<u64> for WireType {
+    type Error = ProtoRwError;
+
+    fn try_from(value: u64) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(WireType::Varint),
+            1 => Ok(WireType::I64),
+            2 => Ok(WireType::Len),
+            5 => Ok(WireType::I32),
+            _ => Err(ProtoRwError::Error(format!(
+                "Unsupported wire type {}",
+                value
+            ))),
+        }
+    }
+}
+
+/// Types that can be the value of a protobuf field.
+///
+/// `read_field` and `write_field` handle the value after the key, including the length of
+/// `WireType::Len` values.
+pub trait ProtoField: Sized {
+    const WIRE_TYPE: WireType;
+
+    fn read_field(buf: &mut Bytes, ctx: &Context) -> Result<Self, ProtoRwError>;
+    fn write_field(&self, buf: &mut BytesMut, ctx: &Context) -> Result<(), ProtoRwError>;
+
+    /// Whether the value is the default of its type, which fields without `Option` don't write.
+    fn is_default(&self) -> bool {
+        false
+    }
+}
+
+/// Enums in `#[oneof(...)]` fields, with a variant for each field number.
+pub trait ProtoOneof: Sized {
+    fn read_oneof(
+        number: u32,
+        wire_type: WireType,
+        buf: &mut Bytes,
+        ctx: &Context,
+    ) -> Result<Self, ProtoRwError>;
+    fn write_oneof(&self, buf: &mut BytesMut, ctx: &Context) -> Result<(), ProtoRwError>;
+}
+
+/// Reads the number and wire type of the next field.
+pub fn read_key(buf: &mut Bytes) -> Result<(u32, WireType), ProtoRwError> {
+    let key = Var::<u64>::read_proto(buf)?.0;
+    let number = u32::try_from(key >> 3)
+        .map_err(|_| ProtoRwError::Error(format!("Invalid field number {}", key >> 3)))?;
+    Ok((number, WireType::try_from(key & 0x07)?))
+}
+
+pub fn write_key(number: u32, wire_type: WireType, buf: &mut BytesMut) -> Result<(), ProtoRwError> {
+    Var(((number as u64) << 3) | wire_type as u64).write_proto(buf)
+}
+
+/// Skips the value of a field that the message doesn't know.
+pub fn skip_field(wire_type: WireType, buf: &mut Bytes) -> Result<(), ProtoRwError> {
+    let len = match wire_type {
+        WireType::Varint => {
+            Var::<u64>::read_proto(buf)?;
+            return Ok(());
+        }
+        WireType::I64 => 8,
+        WireType::Len => read_len(buf)?,
+        WireType::I32 => 4,
+    };
+
+    if buf.remaining() < len {
+        return Err(ProtoRwError::UnexpectedEof);
+    }
+    buf.advance(len);
+    Ok(())
+}
+
+/// Reads the value of a field, checking its wire type.
+pub fn read_field<T: ProtoField>(
+    wire_type: WireType,
+    buf: &mut Bytes,
+    ctx: &Context,
+) -> Result<T, ProtoRwError> {
+    check_wire_type(T::WIRE_TYPE, wire_type)?;
+    T::read_field(buf, ctx)
+}
+
+/// Reads one item of a repeated field, or all of them if the field is packed.
+pub fn read_repeated<T: ProtoField>(
+    wire_type: WireType,
+    buf: &mut Bytes,
+    ctx: &Context,
+    mut push: impl FnMut(T),
+) -> Result<(), ProtoRwError> {
+    if wire_type == WireType::Len && T::WIRE_TYPE != WireType::Len {
+        let mut packed = read_len_delimited(buf)?;
+        while packed.has_remaining() {
+            push(T::read_field(&mut packed, ctx)?);
+        }
+        return Ok(());
+    }
+
+    push(read_field(wire_type, buf, ctx)?);
+    Ok(())
+}
+
+pub fn write_field<T: ProtoField>(
+    number: u32,
+    value: &T,
+    buf: &mut BytesMut,
+    ctx: &Context,
+) -> Result<(), ProtoRwError> {
+    write_key(number, T::WIRE_TYPE, buf)?;
+    value.write_field(buf, ctx)
+}
+
+/// Writes a repeated field. Scalars are packed, other values get a key each.
+pub fn write_repeated<T, I>(
+    number: u32,
+    items: I,
+    buf: &mut BytesMut,
+    ctx: &Context,
+) -> Result<(), ProtoRwError>
+where
+    T: ProtoField,
+    I: IntoIterator,
+    I::Item: Borrow<T>,
+{
+    if T::WIRE_TYPE == WireType::Len {
+        for item in items {
+            write_field(number, item.borrow(), buf, ctx)?;
+        }
+        return Ok(());
+    }
+
+    let mut packed = BytesMut::new();
+    for item in items {
+        item.borrow().write_field(&mut packed, ctx)?;
+    }
+    if !packed.is_empty() {
+        write_key(number, WireType::Len, buf)?;
+        write_len_delimited(&packed, buf)?;
+    }
+    Ok(())
+}
+
+fn check_wire_type(expected: WireType, found: WireType) -> Result<(), ProtoRwError> {
+    if expected != found {
+        return Err(ProtoRwError::UnexpectedValue {
+            expected: format!("wire type {:?}", expected),
+            found: format!("wire type {:?}", found),
+        });
+    }
+    Ok(())
+}
+
+fn read_len(buf: &mut Bytes) -> Result<usize, ProtoRwError> {
+    let len = Var::<u64>::read_proto(buf)?.0;
+    usize::try_from(len).map_err(|_| ProtoRwError::UnexpectedEof)
+}
+
+/// Reads a length-delimited value, returning its bytes.
+pub fn read_len_delimited(buf: &mut Bytes) -> Result<Bytes, ProtoRwError> {
+    let len = read_len(buf)?;
+    if buf.remaining() < len {
+        return Err(ProtoRwError::UnexpectedEof);
+    }
+    Ok(buf.split_to(len))
+}
+
+pub fn write_len_delimited(data: &[u8], buf: &mut BytesMut) -> Result<(), ProtoRwError> {
+    Var(data.len() as u64).write_proto(buf)?;
+    buf.extend_from_slice(data);
+    Ok(())
+}
+
+/// `uint32` and `uint64`.
+macro_rules! impl_varuint_field {
+    ($($ty:ty),*) => {
+        $(
+            impl ProtoField for $ty {
+                const WIRE_TYPE: WireType = WireType::Varint;
+
+                fn read_field(buf: &mut Bytes, _ctx: &Context) -> Result<Self, ProtoRwError> {
+                    Ok(Var::<$ty>::read_proto(buf)?.0)
+                }
+
+                fn write_field(&self, buf: &mut BytesMut, _ctx: &Context) -> Result<(), ProtoRwError> {
+                    Var(*self).write_proto(buf)
+                }
+
+                fn is_default(&self) -> bool {
+                    *self == 0
+                }
+            }
+
+            impl ProtoField for Var<$ty> {
+                const WIRE_TYPE: WireType = WireType::Varint;
+
+                fn read_field(buf: &mut Bytes, _ctx: &Context) -> Result<Self, ProtoRwError> {
+                    Var::<$ty>::read_proto(buf)
+                }
+
+                fn write_field(&self, buf: &mut BytesMut, _ctx: &Context) -> Result<(), ProtoRwError> {
+                    self.write_proto(buf)
+                }
+
+                fn is_default(&self) -> bool {
+                    self.0 == 0
+                }
+            }
+        )*
+    };
+}
+
+/// `int32` and `int64` are written as 64-bit two's complement, `sint32` and `sint64` (`Var`)
+/// are zigzag encoded.
+macro_rules! impl_varint_field {
+    ($($ty:ty),*) => {
+        $(
+            impl ProtoField for $ty {
+                const WIRE_TYPE: WireType = WireType::Varint;
+
+                fn read_field(buf: &mut Bytes, _ctx: &Context) -> Result<Self, ProtoRwError> {
+                    Ok(Var::<u64>::read_proto(buf)?.0 as $ty)
+                }
+
+                fn write_field(&self, buf: &mut BytesMut, _ctx: &Context) -> Result<(), ProtoRwError> {
+                    Var(*self as i64 as u64).write_proto(buf)
+                }
+
+                fn is_default(&self) -> bool {
+                    *self == 0
+                }
+            }
+
+            impl ProtoField for Var<$ty> {
+                const WIRE_TYPE: WireType = WireType::Varint;
+
+                fn read_field(buf: &mut Bytes, _ctx: &Context) -> Result<Self, ProtoRwError> {
+                    Var::<$ty>::read_proto(buf)
+                }
+
+                fn write_field(&self, buf: &mut BytesMut, _ctx: &Context) -> Result<(), ProtoRwError> {
+                    self.write_proto(buf)
+                }
+
+                fn is_default(&self) -> bool {
+                    self.0 == 0
+                }
+            }
+        )*
+    };
+}
+
+/// `fixed32`, `sfixed32`, `float` and their 64-bit versions.
+macro_rules! impl_fixed_field {
+    ($($ty:ty => $wire_type:ident),*) => {
+        $(
+            impl ProtoField for LE<$ty> {
+                const WIRE_TYPE: WireType = WireType::$wire_type;
+
+                fn read_field(buf: &mut Bytes, _ctx: &Context) -> Result<Self, ProtoRwError> {
+                    LE::<$ty>::read_proto(buf)
+                }
+
+                fn write_field(&self, buf: &mut BytesMut, _ctx: &Context) -> Result<(), ProtoRwError> {
+                    self.write_proto(buf)
+                }
+
+                // `-0.0` isn't the default, as in other protobuf encoders
+                fn is_default(&self) -> bool {
+                    self.0.to_le_bytes().iter().all(|byte| *byte == 0)
+                }
+            }
+        )*
+    };
+}
+
+impl_varuint_field!(u32, u64);
+impl_varint_field!(i32, i64);
+impl_fixed_field!(u32 => I32, i32 => I32, f32 => I32, u64 => I64, i64 => I64, f64 => I64);
+
+impl ProtoField for f32 {
+    const WIRE_TYPE: WireType = WireType::I32;
+
+    fn read_field(buf: &mut Bytes, _ctx: &Context) -> Result<Self, ProtoRwError> {
+        Ok(LE::<f32>::read_proto(buf)?.0)
+    }
+
+    fn write_field(&self, buf: &mut BytesMut, _ctx: &Context) -> Result<(), ProtoRwError> {
+        LE(*self).write_proto(buf)
+    }
+
+    fn is_default(&self) -> bool {
+        self.to_bits() == 0
+    }
+}
+
+impl ProtoField for f64 {
+    const WIRE_TYPE: WireType = WireType::I64;
+
+    fn read_field(buf: &mut Bytes, _ctx: &Context) -> Result<Self, ProtoRwError> {
+        Ok(LE::<f64>::read_proto(buf)?.0)
+    }
+
+    fn write_field(&self, buf: &mut BytesMut, _ctx: &Context) -> Result<(), ProtoRwError> {
+        LE(*self).write_proto(buf)
+    }
+
+    fn is_default(&self) -> bool {
+        self.to_bits() == 0
+    }
+}
+
+impl ProtoField for bool {
+    const WIRE_TYPE: WireType = WireType::Varint;
+
+    fn read_field(buf: &mut Bytes, _ctx: &Context) -> Result<Self, ProtoRwError> {
+        Ok(Var::<u64>::read_proto(buf)?.0 != 0)
+    }
+
+    fn write_field(&self, buf: &mut BytesMut, _ctx: &Context) -> Result<(), ProtoRwError> {
+        Var(*self as u64).write_proto(buf)
+    }
+
+    fn is_default(&self) -> bool {
+        !*self
+    }
+}
+
+impl ProtoField for String {
+    const WIRE_TYPE: WireType = WireType::Len;
+
+    fn read_field(buf: &mut Bytes, _ctx: &Context) -> Result<Self, ProtoRwError> {
+        Ok(String::from_utf8(read_len_delimited(buf)?.to_vec())?)
+    }
+
+    fn write_field(&self, buf: &mut BytesMut, _ctx: &Context) -> Result<(), ProtoRwError> {
+        write_len_delimited(self.as_bytes(), buf)
+    }
+
+    fn is_default(&self) -> bool {
+        self.is_empty()
+    }
+}
+
+impl ProtoField for Vec<u8> {
+    const WIRE_TYPE: WireType = WireType::Len;
+
+    fn read_field(buf: &mut Bytes, _ctx: &Context) -> Result<Self, ProtoRwError> {
+        Ok(read_len_delimited(buf)?.to_vec())
+    }
+
+    fn write_field(&self, buf: &mut BytesMut, _ctx: &Context) -> Result<(), ProtoRwError> {
+        write_len_delimited(self, buf)
+    }
+
+    fn is_default(&self) -> bool {
+        self.is_empty()
+    }
+}
+
+/// Lets messages contain themselves.
+impl<T: ProtoField> ProtoField for Box<T> {
+    const WIRE_TYPE: WireType = T::WIRE_TYPE;
+
+    fn read_field(buf: &mut Bytes, ctx: &Context) -> Result<Self, ProtoRwError> {
+        Ok(Box::new(T::read_field(buf, ctx)?))
+    }
+
+    fn write_field(&self, buf: &mut BytesMut, ctx: &Context) -> Result<(), ProtoRwError> {
+        (**self).write_field(buf, ctx)
+    }
+
+    fn is_default(&self) -> bool {
+        (**self).is_default()
+    }
+}
@@ -0,0 +1,187 @@
+//! Descriptions of the wire layout of `#[proto_rw]` types, returned by `ProtoSchema::schema`.
+//!
+//! Expressions from attributes, like `#[when]` conditions and enum ids, are kept as written.
+//! With the `serde` feature, schemas can be serialized, for example to JSON.
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Schema {
+    pub name: String,
+    /// Value written before everything else, from `#[magic]`.
+    pub magic: Option<Constant>,
+    /// Length prefix of the whole type, from `#[size_prefix]`.
+    pub size_prefix: Option<Encoding>,
+    pub layout: Layout,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", rename_all = "snake_case"))]
+pub enum Layout {
+    /// Fields written one after another.
+    Struct { fields: Vec<Field> },
+    /// A tag followed by the fields of the variant with that id.
+    Enum {
+        tag: Encoding,
+        variants: Vec<Variant>,
+    },
+    /// A `#[proto_rw(protobuf)]` struct.
+    Message { fields: Vec<MessageField> },
+    /// A `#[proto_rw(protobuf)]` enum, written as an `int32`.
+    MessageEnum { values: Vec<EnumValue> },
+    /// A `#[proto_rw(protobuf)]` oneof, with one field for each variant.
+    Oneof { fields: Vec<MessageField> },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Field {
+    /// Name of the field, or its index in tuple structs.
+    pub name: String,
+    pub encoding: Encoding,
+    /// The field is only on the wire when this is true, from `#[when]`.
+    pub when: Option<String>,
+    pub since: Option<String>,
+    pub until: Option<String>,
+    /// Zero bytes before the field, from `#[pad]`.
+    pub pad: Option<String>,
+    /// Zero bytes up to a multiple of this before the field, from `#[align]`.
+    pub align: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Variant {
+    pub name: String,
+    pub ids: Vec<VariantId>,
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub size_prefix: Option<Encoding>,
+    pub fields: Vec<Field>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct VariantId {
+    /// Pattern of the tags read as this variant.
+    pub pattern: String,
+    /// Tag written for this variant.
+    pub value: String,
+    pub since: Option<String>,
+    pub until: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MessageField {
+    pub name: String,
+    /// The field number, or every number of a oneof.
+    pub numbers: Vec<u32>,
+    pub label: Label,
+    /// Type of the value without its label, like `Var<i32>` or `String`.
+    pub ty: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Label {
+    Singular,
+    Optional,
+    Repeated,
+    Oneof,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct EnumValue {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Constant {
+    pub encoding: Encoding,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", rename_all = "snake_case"))]
+pub enum Encoding {
+    /// A number, like `u8`, `BE<u16>` or `Var<u32>`.
+    Number {
+        name: String,
+    },
+    /// A byte that is 0 or 1.
+    Bool,
+    /// UTF-8 bytes.
+    String {
+        length: Length,
+    },
+    List {
+        length: Length,
+        item: Box<Encoding>,
+    },
+    Array {
+        len: usize,
+        item: Box<Encoding>,
+    },
+    Tuple {
+        items: Vec<Encoding>,
+    },
+    /// Key-value pairs.
+    Map {
+        length: Box<Encoding>,
+        key: Box<Encoding>,
+        value: Box<Encoding>,
+    },
+    /// A `bool` followed by the value if it's true.
+    Optional {
+        value: Box<Encoding>,
+    },
+    /// A value that is always the same, from `Const`.
+    Constant(Box<Constant>),
+    /// Another type, described by its own schema.
+    Type {
+        name: String,
+    },
+    /// An enum without its tag, which is the value of an earlier field, from `#[tag]`.
+    Untagged {
+        name: String,
+        tag: String,
+    },
+    /// A value with its length in bytes before it, from `#[size_prefix]`.
+    SizePrefixed {
+        length: Box<Encoding>,
+        value: Box<Encoding>,
+    },
+    /// Read and written by functions, from `#[with]`, `#[read_with]` and `#[write_with]`.
+    Custom {
+        read_with: Option<String>,
+        write_with: Option<String>,
+    },
+}
+
+/// How the number of items of a list or string is known.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", rename_all = "snake_case"))]
+pub enum Length {
+    /// A number of items before them.
+    Prefix { encoding: Box<Encoding> },
+    /// The number of items is an earlier field, from `#[count_from]`.
+    CountFrom { field: String },
+    /// The size in bytes is an earlier field, from `#[byte_len_from]`.
+    ByteLenFrom { field: String },
+    /// The items end with a byte string, from `#[terminator(...)]`.
+    Terminator { value: String },
+    /// The last item is the first one matching a predicate, from `#[until_item(...)]`.
+    Predicate { predicate: String },
+    /// The items run until the end of the buffer, from `#[until_eof]`.
+    Eof,
+}
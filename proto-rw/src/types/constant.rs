@@ -0,0 +1,101 @@
+use std::marker::PhantomData;
+
+use bytes::{Bytes, BytesMut};
+
+use crate::{
+    error::ProtoRwError,
+    types::{Var, BE, LE},
+    ProtoRw,
+};
+
+/// A field that always holds `V`, stored on the wire as `T`.
+///
+/// Reading fails with `ProtoRwError::UnexpectedValue` when the value on the wire is different.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Const<T, const V: i128>(PhantomData<T>);
+
+impl<T, const V: i128> Const<T, V> {
+    pub const VALUE: i128 = V;
+
+    pub fn new() -> Self {
+        Const(PhantomData)
+    }
+}
+
+impl<T, const V: i128> Default for Const<T, V> {
+    fn default() -> Self {
+        Const::new()
+    }
+}
+
+/// Integer wire types that can hold the value of a `Const`.
+pub trait ConstValue: ProtoRw {
+    /// Returns `None` when the value does not fit in the type.
+    fn from_const(value: i128) -> Option<Self>;
+    fn to_const(&self) -> i128;
+}
+
+impl<T: ConstValue, const V: i128> ProtoRw for Const<T, V> {
+    fn read_proto(buf: &mut Bytes) -> Result<Self, ProtoRwError> {
+        let found = T::read_proto(buf)?.to_const();
+        if found != V {
+            return Err(ProtoRwError::UnexpectedValue {
+                expected: V.to_string(),
+                found: found.to_string(),
+            });
+        }
+
+        Ok(Const::new())
+    }
+
+    fn write_proto(&self, buf: &mut BytesMut) -> Result<(), ProtoRwError> {
+        let value = T::from_const(V).ok_or_else(|| {
+            ProtoRwError::Error(format!(
+                "Constant {} does not fit in {}",
+                V,
+                std::any::type_name::<T>()
+            ))
+        })?;
+        value.write_proto(buf)
+    }
+}
+
+macro_rules! impl_const_value {
+    ($ty:ty) => {
+        impl ConstValue for $ty {
+            fn from_const(value: i128) -> Option<Self> {
+                <$ty>::try_from(value).ok()
+            }
+
+            fn to_const(&self) -> i128 {
+                *self as i128
+            }
+        }
+    };
+    ($wrapper:ident, $ty:ty) => {
+        impl ConstValue for $wrapper<$ty> {
+            fn from_const(value: i128) -> Option<Self> {
+                <$ty>::try_from(value).ok().map($wrapper)
+            }
+
+            fn to_const(&self) -> i128 {
+                self.0 as i128
+            }
+        }
+    };
+}
+
+impl_const_value!(u8);
+impl_const_value!(i8);
+
+macro_rules! impl_const_wrappers {
+    ($($ty:ty),*) => {
+        $(
+            impl_const_value!(LE, $ty);
+            impl_const_value!(BE, $ty);
+            impl_const_value!(Var, $ty);
+        )*
+    };
+}
+
+impl_const_wrappers!(u16, u32, u64, i16, i32, i64, i128);
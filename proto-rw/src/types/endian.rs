@@ -1,6 +1,6 @@
 use bytes::{Buf, BufMut, Bytes, BytesMut};
 
-use crate::{error::ProtoRwError, ProtoRw};
+use crate::{error::ProtoRwError, ProtoRw, ProtoRwWrapper};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct LE<T>(pub T);
@@ -10,6 +10,10 @@ pub struct BE<T>(pub T);
 
 impl ProtoRw for u8 {
     fn read_proto(buf: &mut Bytes) -> Result<Self, ProtoRwError> {
+        if !buf.has_remaining() {
+            return Err(ProtoRwError::UnexpectedEof);
+        }
+
         Ok(buf.get_u8())
     }
 
@@ -21,6 +25,10 @@ impl ProtoRw for u8 {
 
 impl ProtoRw for i8 {
     fn read_proto(buf: &mut Bytes) -> Result<Self, ProtoRwError> {
+        if !buf.has_remaining() {
+            return Err(ProtoRwError::UnexpectedEof);
+        }
+
         Ok(buf.get_i8())
     }
 
@@ -102,3 +110,33 @@ impl_endian!(i64);
 impl_endian!(i128);
 impl_endian!(f32);
 impl_endian!(f64);
+
+impl<T> ProtoRwWrapper for LE<T>
+where
+    LE<T>: ProtoRw,
+{
+    type Inner = T;
+
+    fn from_inner(inner: T) -> Self {
+        LE(inner)
+    }
+
+    fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> ProtoRwWrapper for BE<T>
+where
+    BE<T>: ProtoRw,
+{
+    type Inner = T;
+
+    fn from_inner(inner: T) -> Self {
+        BE(inner)
+    }
+
+    fn into_inner(self) -> T {
+        self.0
+    }
+}
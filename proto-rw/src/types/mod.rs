@@ -1,7 +1,9 @@
 mod bool;
+mod constant;
 mod endian;
 mod string;
 mod var;
 
+pub use constant::*;
 pub use endian::*;
 pub use var::*;
@@ -1,7 +1,8 @@
 use bytes::{Bytes, BytesMut};
 
-use crate::{error::ProtoRwError, ProtoRw};
+use crate::{error::ProtoRwError, ProtoRw, ProtoRwWrapper};
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Var<T>(pub T);
 
 macro_rules! read_varuint {
@@ -15,6 +16,13 @@ macro_rules! read_varuint {
                 break;
             }
             shift += 7;
+            // Only the bits that fit are kept, but the varint can't go on forever
+            if shift >= <$ty>::BITS {
+                return Err(ProtoRwError::Error(format!(
+                    "Varint is too long for {}",
+                    stringify!($ty)
+                )));
+            }
         }
         value
     }};
@@ -36,16 +44,15 @@ macro_rules! write_varuint {
 }
 
 macro_rules! read_varint {
-    ($buf:ident, $ty:ty) => {{
-        let value = read_varuint!($buf, $ty);
+    ($buf:ident, $ty:ty, $uty:ty) => {{
+        let value = read_varuint!($buf, $uty);
         ((value >> 1) as $ty) ^ (-((value & 1) as $ty))
     }};
 }
 
 macro_rules! write_varint {
-    ($buf:ident, $ty:ty, $value:expr) => {{
-        let value = (($value << 1) as $ty)
-            .wrapping_sub(($value >> (std::mem::size_of::<$ty>() * 8 - 1)) as $ty);
+    ($buf:ident, $ty:ty, $uty:ty, $value:expr) => {{
+        let value = (($value << 1) ^ ($value >> (std::mem::size_of::<$ty>() * 8 - 1))) as $uty;
         write_varuint!($buf, value);
     }};
 }
@@ -78,14 +85,14 @@ macro_rules! impl_varuint {
 }
 
 macro_rules! impl_varint {
-    ($ty:ty) => {
+    ($ty:ty, $uty:ty) => {
         impl ProtoRw for Var<$ty> {
             fn read_proto(buf: &mut Bytes) -> Result<Self, ProtoRwError> {
-                Ok(Var(read_varint!(buf, $ty)))
+                Ok(Var(read_varint!(buf, $ty, $uty)))
             }
 
             fn write_proto(&self, buf: &mut BytesMut) -> Result<(), ProtoRwError> {
-                write_varint!(buf, $ty, self.0);
+                write_varint!(buf, $ty, $uty, self.0);
                 Ok(())
             }
         }
@@ -108,7 +115,22 @@ impl_varuint!(u16);
 impl_varuint!(u32);
 impl_varuint!(u64);
 impl_varuint!(u128);
-impl_varint!(i16);
-impl_varint!(i32);
-impl_varint!(i64);
-impl_varint!(i128);
+impl_varint!(i16, u16);
+impl_varint!(i32, u32);
+impl_varint!(i64, u64);
+impl_varint!(i128, u128);
+
+impl<T> ProtoRwWrapper for Var<T>
+where
+    Var<T>: ProtoRw,
+{
+    type Inner = T;
+
+    fn from_inner(inner: T) -> Self {
+        Var(inner)
+    }
+
+    fn into_inner(self) -> T {
+        self.0
+    }
+}
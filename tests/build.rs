@@ -0,0 +1,3 @@
+fn main() {
+    proto_rw_build::compile_protos(&["protos/chat.proto"]).unwrap();
+}
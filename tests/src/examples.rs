@@ -0,0 +1,71 @@
+//! Example types covering most of what `#[proto_rw]` generates, shared by the tests and the
+//! fuzz targets.
+
+use macros::proto_rw;
+use proto_rw::{
+    types::{Var, BE, LE},
+    ProtoRw,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+#[proto_rw]
+pub struct ExampleStruct {
+    pub a: u8,
+    pub b: String,
+    pub c: BE<i16>,
+    pub d: LE<u32>,
+    pub e: Var<u64>,
+    pub f: ExampleEnum,
+    #[length(Var<u32>)]
+    pub g: Vec<u8>,
+    #[length(BE<u16>)]
+    pub h: Vec<ExampleEnum>,
+    #[length(Var<u32>, Var<u16>)]
+    pub i: Vec<Vec<u8>>,
+    pub j: (LE<i32>, bool),
+    #[length(Var<u32>)]
+    pub k: (Vec<u8>, BE<u16>),
+    pub l: (bool, (bool, String)),
+    pub m: [u8; 4],
+    #[convert(bool[0])]
+    pub n: ExampleConvert,
+    #[length(Var<u32>)]
+    #[convert(bool[1], bool[2])]
+    pub o: (String, ExampleConvert, Vec<ExampleConvert>),
+    #[convert(bool[0])]
+    pub p: [ExampleConvert; 3],
+    pub q: AdvancedEnum,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[proto_rw(BE<i32>)]
+pub enum ExampleEnum {
+    A = 0,
+    B = 1,
+    C = 2,
+}
+
+#[derive(Clone)]
+#[proto_rw]
+pub struct ExampleConvert(pub bool);
+
+impl From<ExampleConvert> for bool {
+    fn from(data: ExampleConvert) -> Self {
+        data.0
+    }
+}
+
+impl From<bool> for ExampleConvert {
+    fn from(data: bool) -> Self {
+        ExampleConvert(data)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[proto_rw(Var<i32>)]
+pub enum AdvancedEnum {
+    A(u8) = 0,
+    B(LE<u16>, LE<u32>) = 1,
+    C(String) = 2,
+    D { a: u8, b: BE<u16> } = 3,
+}
@@ -1 +1 @@
-
+pub mod examples;
@@ -0,0 +1,123 @@
+use bytes::{Bytes, BytesMut};
+use macros::proto_rw;
+use proto_rw::{
+    types::{Var, BE},
+    ProtoRw,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+#[proto_rw]
+struct Entity {
+    id: Var<u32>,
+    has_position: bool,
+    #[when(*has_position)]
+    position: Option<(BE<f64>, BE<f64>, BE<f64>)>,
+    version: u8,
+    #[when(*version >= 2)]
+    name: String,
+    #[when(*version >= 3 && *has_position)]
+    #[length(Var<u32>)]
+    path: Option<Vec<(BE<f64>, BE<f64>)>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[proto_rw(u8)]
+enum Event {
+    Move(bool, #[when(*index_0)] Option<BE<i32>>) = 0,
+    Rename {
+        flags: u8,
+        #[when(*flags & 1 != 0)]
+        name: String,
+    } = 1,
+}
+
+fn round_trip<T: ProtoRw>(value: &T) -> (Vec<u8>, T) {
+    let mut buf = BytesMut::new();
+    value.write_proto(&mut buf).unwrap();
+    let data = buf.to_vec();
+
+    let mut buf = buf.freeze();
+    (data, T::read_proto(&mut buf).unwrap())
+}
+
+#[test]
+fn conditional_fields() {
+    let entity = Entity {
+        id: 1,
+        has_position: true,
+        position: Some((1.0, 2.0, 3.0)),
+        version: 3,
+        name: "zombie".to_string(),
+        path: Some(vec![(1.0, 2.0), (3.0, 4.0)]),
+    };
+    assert_eq!(round_trip(&entity).1, entity);
+
+    let entity = Entity {
+        id: 2,
+        has_position: false,
+        position: None,
+        version: 1,
+        name: String::new(),
+        path: None,
+    };
+    let (data, entity2) = round_trip(&entity);
+    assert_eq!(data, vec![2, 0, 1]);
+    assert_eq!(entity2, entity);
+}
+
+#[test]
+fn conditional_fields_default() {
+    let mut buf = Bytes::from_static(&[2, 0, 1]);
+    let entity = Entity::read_proto(&mut buf).unwrap();
+    assert_eq!(entity.name, "");
+    assert_eq!(entity.path, None);
+
+    let entity = Entity {
+        id: 2,
+        has_position: false,
+        position: None,
+        version: 1,
+        name: "lost".to_string(),
+        path: None,
+    };
+    // The name wouldn't be read back, so it can't be written
+    let err = entity.write_proto(&mut BytesMut::new()).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Field name is not the default value but its condition is false"
+    );
+}
+
+#[test]
+fn conditional_fields_mismatch() {
+    let entity = Entity {
+        id: 1,
+        has_position: false,
+        position: Some((1.0, 2.0, 3.0)),
+        version: 1,
+        name: String::new(),
+        path: None,
+    };
+
+    let mut buf = BytesMut::new();
+    let err = entity.write_proto(&mut buf).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Field position is Some but its condition is false"
+    );
+}
+
+#[test]
+fn conditional_variant_fields() {
+    let event = Event::Move(true, Some(-5));
+    assert_eq!(round_trip(&event).1, event);
+
+    let event = Event::Move(false, None);
+    assert_eq!(round_trip(&event), (vec![0, 0], event));
+
+    let event = Event::Rename {
+        flags: 1,
+        name: "a".to_string(),
+    };
+    assert_eq!(round_trip(&event), (vec![1, 1, 1, b'a'], event));
+}
@@ -0,0 +1,91 @@
+use bytes::{Bytes, BytesMut};
+use macros::proto_rw;
+use proto_rw::{
+    types::{Const, BE, LE},
+    ProtoRw,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+#[proto_rw]
+#[magic(b"\x89PNG")]
+struct Png {
+    width: BE<u32>,
+    #[pad(2)]
+    depth: u8,
+    #[align(4)]
+    height: BE<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[proto_rw(endian = "be")]
+#[magic(u32 = 0xCAFEBABE)]
+struct Class {
+    version: Const<BE<u16>, 52>,
+    flags: u16,
+    #[pad(3)]
+    #[skip]
+    reserved: (),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[proto_rw(u8)]
+#[magic(LE<u16> = 0x1234)]
+enum Chunk {
+    Empty = 0,
+}
+
+#[test]
+fn magic_and_padding() {
+    let png = Png {
+        width: 1,
+        depth: 8,
+        height: 2,
+    };
+
+    let mut buf = BytesMut::new();
+    png.write_proto(&mut buf).unwrap();
+    assert_eq!(
+        &buf[..],
+        b"\x89PNG\x00\x00\x00\x01\x00\x00\x08\x00\x00\x00\x00\x02"
+    );
+    assert_eq!(Png::read_proto(&mut buf.freeze()).unwrap(), png);
+
+    let err = Png::read_proto(&mut Bytes::from_static(b"GIF8\x00\x00\x00\x01")).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Expected b\"\\x89PNG\" but found b\"GIF8\""
+    );
+
+    let data = b"\x89PNG\x00\x00\x00\x01\x00\x01\x08\x00\x00\x00\x00\x02";
+    let err = Png::read_proto(&mut Bytes::from_static(data)).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Expected 2 zero bytes but found b\"\\0\\x01\""
+    );
+}
+
+#[test]
+fn constants() {
+    let class = Class {
+        version: Const::new(),
+        flags: 0x0021,
+        reserved: (),
+    };
+
+    let mut buf = BytesMut::new();
+    class.write_proto(&mut buf).unwrap();
+    assert_eq!(&buf[..], &[0xCA, 0xFE, 0xBA, 0xBE, 0, 52, 0, 0x21, 0, 0, 0]);
+    assert_eq!(Class::read_proto(&mut buf.freeze()).unwrap(), class);
+
+    let data = [0xCA, 0xFE, 0xBA, 0xBE, 0, 51, 0, 0x21, 0, 0, 0];
+    let err = Class::read_proto(&mut Bytes::copy_from_slice(&data)).unwrap_err();
+    assert_eq!(err.to_string(), "Expected 52 but found 51");
+
+    let err = Class::read_proto(&mut Bytes::from_static(&[0xCA, 0xFE, 0, 0])).unwrap_err();
+    assert_eq!(err.to_string(), "Expected 3405691582 but found 3405643776");
+
+    let mut buf = BytesMut::new();
+    Chunk::Empty.write_proto(&mut buf).unwrap();
+    assert_eq!(&buf[..], &[0x34, 0x12, 0]);
+    assert_eq!(Chunk::read_proto(&mut buf.freeze()).unwrap(), Chunk::Empty);
+}
@@ -0,0 +1,72 @@
+use bytes::{Bytes, BytesMut};
+use macros::proto_rw;
+use proto_rw::{
+    types::{Var, LE},
+    ProtoRw,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+#[proto_rw(endian = "be", length = "Var<u32>")]
+struct Header {
+    id: u16,
+    offset: i32,
+    scale: f64,
+    flags: u8,
+    little: LE<u32>,
+    items: Vec<u16>,
+    #[length(u16)]
+    pairs: Vec<(u16, i16)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[proto_rw(u16, endian = "le")]
+enum Record {
+    Point {
+        x: i32,
+        y: i32,
+    } = 1,
+    #[size_prefix(u16)]
+    Name(String) = 2,
+}
+
+#[test]
+fn container_defaults() {
+    let header = Header {
+        id: 0x0102,
+        offset: -2,
+        scale: 1.5,
+        flags: 7,
+        little: 0x0A0B0C0D,
+        items: vec![0x0304, 0x0506],
+        pairs: vec![(1, -1)],
+    };
+
+    let mut buf = BytesMut::new();
+    header.write_proto(&mut buf).unwrap();
+
+    let mut expected = vec![0x01, 0x02, 0xFF, 0xFF, 0xFF, 0xFE];
+    expected.extend_from_slice(&1.5f64.to_be_bytes());
+    expected.extend_from_slice(&[7, 0x0D, 0x0C, 0x0B, 0x0A]);
+    expected.extend_from_slice(&[2, 0x03, 0x04, 0x05, 0x06]);
+    expected.extend_from_slice(&[0x00, 0x01, 0x00, 0x01, 0xFF, 0xFF]);
+    assert_eq!(&buf[..], &expected[..]);
+
+    let mut buf = buf.freeze();
+    assert_eq!(Header::read_proto(&mut buf).unwrap(), header);
+    assert!(buf.is_empty());
+}
+
+#[test]
+fn container_defaults_enum() {
+    let record = Record::Point { x: 1, y: -1 };
+    let mut buf = BytesMut::new();
+    record.write_proto(&mut buf).unwrap();
+    assert_eq!(&buf[..], &[1, 0, 1, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF]);
+    assert_eq!(Record::read_proto(&mut buf.freeze()).unwrap(), record);
+
+    let data = Bytes::from_static(&[2, 0, 2, 0, 1, b'a']);
+    assert_eq!(
+        Record::read_proto(&mut data.clone()).unwrap(),
+        Record::Name("a".to_string())
+    );
+}
@@ -0,0 +1,89 @@
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use macros::proto_rw;
+use proto_rw::{error::ProtoRwError, types::BE, ProtoRw};
+
+mod cstring {
+    use super::*;
+
+    pub fn read(buf: &mut Bytes) -> Result<String, ProtoRwError> {
+        let end = buf
+            .iter()
+            .position(|b| *b == 0)
+            .ok_or(ProtoRwError::UnexpectedEof)?;
+        let data = buf.split_to(end).to_vec();
+        buf.advance(1);
+        Ok(String::from_utf8(data)?)
+    }
+
+    pub fn write(value: &str, buf: &mut BytesMut) -> Result<(), ProtoRwError> {
+        if value.contains('\0') {
+            return Err(ProtoRwError::Error("Nul byte in string".to_string()));
+        }
+        buf.put_slice(value.as_bytes());
+        buf.put_u8(0);
+        Ok(())
+    }
+}
+
+fn read_percent(buf: &mut Bytes) -> Result<f32, ProtoRwError> {
+    let value = u8::read_proto(buf)?;
+    if value > 100 {
+        return Err(ProtoRwError::Error(format!("Invalid percentage {}", value)));
+    }
+    Ok(value as f32 / 100.0)
+}
+
+fn write_percent(value: &f32, buf: &mut BytesMut) -> Result<(), ProtoRwError> {
+    ((value * 100.0).round() as u8).write_proto(buf)
+}
+
+fn write_doubled(value: &u16, buf: &mut BytesMut) -> Result<(), ProtoRwError> {
+    BE(value * 2).write_proto(buf)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[proto_rw]
+struct Custom {
+    #[with(cstring)]
+    name: String,
+    #[read_with(read_percent)]
+    #[write_with(write_percent)]
+    volume: f32,
+    #[write_with(write_doubled)]
+    doubled: BE<u16>,
+}
+
+#[test]
+fn custom_rw() {
+    let custom = Custom {
+        name: "abc".to_string(),
+        volume: 0.5,
+        doubled: 3,
+    };
+
+    let mut buf = BytesMut::new();
+    custom.write_proto(&mut buf).unwrap();
+    assert_eq!(&buf[..], &[b'a', b'b', b'c', 0, 50, 0, 6]);
+
+    let mut buf = buf.freeze();
+    let custom2 = Custom::read_proto(&mut buf).unwrap();
+    assert_eq!(custom2.name, "abc");
+    assert_eq!(custom2.volume, 0.5);
+    assert_eq!(custom2.doubled, 6);
+}
+
+#[test]
+fn custom_rw_errors() {
+    let mut buf = Bytes::from_static(&[b'a', 0, 101, 0, 0]);
+    let err = Custom::read_proto(&mut buf).unwrap_err();
+    assert_eq!(err.to_string(), "Invalid percentage 101");
+
+    let custom = Custom {
+        name: "a\0b".to_string(),
+        volume: 0.0,
+        doubled: 0,
+    };
+    let mut buf = BytesMut::new();
+    let err = custom.write_proto(&mut buf).unwrap_err();
+    assert_eq!(err.to_string(), "Nul byte in string");
+}
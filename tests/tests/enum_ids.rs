@@ -0,0 +1,70 @@
+use bytes::{Bytes, BytesMut};
+use macros::proto_rw;
+use proto_rw::{types::Var, ProtoRw};
+
+mod ids {
+    pub const PING: u8 = 0x01;
+    pub const RESERVED_START: u8 = 0x10;
+    pub const RESERVED_END: u8 = 0x1F;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[proto_rw(u8)]
+enum Message {
+    #[id(ids::PING)]
+    Ping,
+    #[id(0x02 | 0x03, write = 0x02)]
+    Text(String),
+    #[id(0x04)]
+    Number {
+        value: Var<i32>,
+    },
+    #[id(ids::RESERVED_START..=ids::RESERVED_END, write = ids::RESERVED_START)]
+    Reserved,
+    Legacy = 0x20,
+    #[id(0x30..=0x3F, write = 0x40)]
+    Misfiled,
+}
+
+fn read(data: &'static [u8]) -> Message {
+    Message::read_proto(&mut Bytes::from_static(data)).unwrap()
+}
+
+fn write(message: Message) -> Vec<u8> {
+    let mut buf = BytesMut::new();
+    message.write_proto(&mut buf).unwrap();
+    buf.to_vec()
+}
+
+#[test]
+fn enum_ids_read() {
+    assert_eq!(read(&[0x01]), Message::Ping);
+    assert_eq!(read(&[0x02, 1, b'a']), Message::Text("a".to_string()));
+    assert_eq!(read(&[0x03, 1, b'b']), Message::Text("b".to_string()));
+    assert_eq!(read(&[0x04, 0x05]), Message::Number { value: -3 });
+    assert_eq!(read(&[0x10]), Message::Reserved);
+    assert_eq!(read(&[0x1A]), Message::Reserved);
+    assert_eq!(read(&[0x1F]), Message::Reserved);
+    assert_eq!(read(&[0x20]), Message::Legacy);
+
+    let err = Message::read_proto(&mut Bytes::from_static(&[0x05])).unwrap_err();
+    assert_eq!(err.to_string(), "Get 5 while reading Message");
+}
+
+#[test]
+fn enum_ids_write() {
+    assert_eq!(write(Message::Ping), vec![0x01]);
+    assert_eq!(write(Message::Text("a".to_string())), vec![0x02, 1, b'a']);
+    assert_eq!(write(Message::Number { value: -3 }), vec![0x04, 0x05]);
+    assert_eq!(write(Message::Reserved), vec![0x10]);
+    assert_eq!(write(Message::Legacy), vec![0x20]);
+
+    // 0x40 would be read back as something else
+    let err = Message::Misfiled
+        .write_proto(&mut BytesMut::new())
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Message::Misfiled writes id 64, which doesn't match its pattern"
+    );
+}
@@ -0,0 +1,98 @@
+use bytes::BytesMut;
+use macros::proto_rw;
+use proto_rw::{
+    types::{Var, BE, LE},
+    ProtoRw,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+#[proto_rw(i8)]
+enum SignedEnum {
+    A = -1,
+    B = 0,
+    C = 1,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[proto_rw(bool)]
+enum BoolEnum {
+    Off = false,
+    On(LE<u16>) = true,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[proto_rw([u8; 4])]
+enum ChunkEnum {
+    Riff(BE<u32>) = b"RIFF",
+    List { count: Var<u32> } = b"LIST",
+    Data = [0, 1, 2, 3],
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[proto_rw(String)]
+enum StringEnum {
+    Text(String) = "text",
+    Number(Var<i64>) = "number",
+    Null = "null",
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[proto_rw]
+struct TagStruct {
+    a: SignedEnum,
+    b: BoolEnum,
+    c: BoolEnum,
+    d: ChunkEnum,
+    e: ChunkEnum,
+    f: ChunkEnum,
+    g: StringEnum,
+    h: StringEnum,
+    i: StringEnum,
+}
+
+#[test]
+fn enum_tags() {
+    let example = TagStruct {
+        a: SignedEnum::A,
+        b: BoolEnum::Off,
+        c: BoolEnum::On(1000),
+        d: ChunkEnum::Riff(123456),
+        e: ChunkEnum::List { count: 42 },
+        f: ChunkEnum::Data,
+        g: StringEnum::Text("hello".to_string()),
+        h: StringEnum::Number(-123),
+        i: StringEnum::Null,
+    };
+
+    let mut buf = BytesMut::new();
+    example.write_proto(&mut buf).unwrap();
+
+    let mut buf = buf.freeze();
+    let example2 = TagStruct::read_proto(&mut buf).unwrap();
+
+    assert_eq!(example, example2);
+}
+
+#[test]
+fn enum_tag_encoding() {
+    let mut buf = BytesMut::new();
+    SignedEnum::A.write_proto(&mut buf).unwrap();
+    ChunkEnum::Riff(1).write_proto(&mut buf).unwrap();
+    StringEnum::Null.write_proto(&mut buf).unwrap();
+
+    assert_eq!(
+        &buf[..],
+        &[0xFF, b'R', b'I', b'F', b'F', 0, 0, 0, 1, 4, b'n', b'u', b'l', b'l']
+    );
+}
+
+#[test]
+fn enum_unknown_tag() {
+    let mut buf = BytesMut::new();
+    "missing".to_string().write_proto(&mut buf).unwrap();
+
+    let mut buf = buf.freeze();
+    let err = StringEnum::read_proto(&mut buf).unwrap_err();
+
+    assert_eq!(err.to_string(), "Get \"missing\" while reading StringEnum");
+}
@@ -0,0 +1,137 @@
+use bytes::{Bytes, BytesMut};
+use macros::proto_rw;
+use proto_rw::{
+    error::ProtoRwError,
+    fuzz::{fuzz_decode, TrackingAllocator, MAX_ALLOC_BASE},
+    types::{Var, BE, LE},
+    ProtoRw,
+};
+
+#[global_allocator]
+static ALLOCATOR: TrackingAllocator = TrackingAllocator;
+
+#[derive(Debug, Clone, PartialEq)]
+#[proto_rw]
+struct Entry {
+    id: Var<u32>,
+    name: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[proto_rw]
+struct Table {
+    count: BE<u32>,
+    #[count_from(count)]
+    entries: Vec<Entry>,
+    #[length(Var<u32>)]
+    values: Vec<LE<u64>>,
+    flag: bool,
+}
+
+/// Allocates more than any input may while reading.
+#[derive(Debug)]
+struct Greedy;
+
+impl ProtoRw for Greedy {
+    fn read_proto(_buf: &mut Bytes) -> Result<Self, ProtoRwError> {
+        let bytes = vec![1u8; MAX_ALLOC_BASE * 2];
+        assert_eq!(bytes[MAX_ALLOC_BASE], 1);
+        Ok(Greedy)
+    }
+
+    fn write_proto(&self, _buf: &mut BytesMut) -> Result<(), ProtoRwError> {
+        Ok(())
+    }
+}
+
+#[test]
+fn empty_input() {
+    assert!(matches!(
+        fuzz_decode::<u8>(&[]),
+        Err(ProtoRwError::UnexpectedEof)
+    ));
+    assert!(matches!(
+        fuzz_decode::<i8>(&[]),
+        Err(ProtoRwError::UnexpectedEof)
+    ));
+    assert!(matches!(
+        fuzz_decode::<bool>(&[]),
+        Err(ProtoRwError::UnexpectedEof)
+    ));
+}
+
+#[test]
+fn long_varints() {
+    assert!(fuzz_decode::<Var<u16>>(&[0xff, 0xff, 0xff, 0x01]).is_err());
+    assert!(fuzz_decode::<Var<u32>>(&[0xff; 5]).is_err());
+    assert!(fuzz_decode::<Var<i64>>(&[0xff; 10]).is_err());
+    assert!(fuzz_decode::<String>(&[0x80; 64]).is_err());
+
+    // The longest encodings still decode
+    assert_eq!(
+        fuzz_decode::<Var<u64>>(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01])
+            .unwrap(),
+        Var(u64::MAX)
+    );
+    // Non-minimal encodings decode, and are written minimally
+    assert_eq!(fuzz_decode::<Var<u32>>(&[0x81, 0x00]).unwrap(), Var(1));
+}
+
+#[test]
+fn untrusted_lengths() {
+    // Counts and lengths of u32::MAX items with only a few bytes after them
+    assert!(matches!(
+        fuzz_decode::<Table>(&[0xff, 0xff, 0xff, 0xff, 0x01, 0x00]),
+        Err(ProtoRwError::UnexpectedEof)
+    ));
+    assert!(matches!(
+        fuzz_decode::<Table>(&[0, 0, 0, 0, 0xff, 0xff, 0xff, 0xff, 0x0f, 0x00]),
+        Err(ProtoRwError::UnexpectedEof)
+    ));
+    assert!(matches!(
+        fuzz_decode::<String>(&[0xff, 0xff, 0xff, 0xff, 0x0f]),
+        Err(ProtoRwError::UnexpectedEof)
+    ));
+}
+
+#[test]
+#[should_panic(expected = "more than the limit")]
+fn allocation_limit() {
+    let _ = fuzz_decode::<Greedy>(&[]);
+}
+
+#[test]
+fn decoded_values() {
+    let data = [
+        0, 0, 0, 1, 0x81, 0x00, 2, b'h', b'i', 1, 1, 2, 3, 4, 5, 6, 7, 8, 1, 0xaa,
+    ];
+    let table = fuzz_decode::<Table>(&data).unwrap();
+    assert_eq!(
+        table,
+        Table {
+            count: 1,
+            entries: vec![Entry {
+                id: 1,
+                name: "hi".to_string(),
+            }],
+            values: vec![0x0807060504030201],
+            flag: true,
+        }
+    );
+
+    // Pseudo-random inputs only have to decode without panicking
+    let mut state = 0x2545f491u32;
+    for len in 0..512 {
+        let data: Vec<u8> = (0..len % 64)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect();
+        let _ = fuzz_decode::<Table>(&data);
+        let _ = fuzz_decode::<String>(&data);
+        let _ = fuzz_decode::<Var<i128>>(&data);
+    }
+}
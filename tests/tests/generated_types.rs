@@ -0,0 +1,104 @@
+use bytes::BytesMut;
+use proto_rw::ProtoRw;
+
+include!(concat!(env!("OUT_DIR"), "/chat.rs"));
+
+#[test]
+fn generated_types() {
+    let message = Message {
+        author: Some(User {
+            id: 1,
+            name: String::from("ann"),
+            karma: Some(-3),
+            role: UserRole::Admin,
+        }),
+        reactions: vec![1, 2],
+        tags: vec![String::from("a")],
+        sent_at: 5,
+        reply_to: Some(Box::new(Message {
+            body: Some(MessageBody::Text(String::from("hi"))),
+            ..Default::default()
+        })),
+        attachments: vec![MessageAttachment {
+            r#type: String::from("png"),
+            size: 1.5,
+        }],
+        body: Some(MessageBody::Sticker(MessageAttachment::default())),
+    };
+
+    let mut buf = BytesMut::new();
+    message.write_proto(&mut buf).unwrap();
+    assert_eq!(Message::read_proto(&mut buf.freeze()).unwrap(), message);
+
+    let mut buf = BytesMut::new();
+    Status::Delivered.write_proto(&mut buf).unwrap();
+    assert_eq!(&buf[..], &[2]);
+}
+
+#[test]
+fn mutually_recursive_types() {
+    let thread = Thread {
+        first: Some(Box::new(Post {
+            text: String::from("hi"),
+            replies: Some(Box::new(Thread::default())),
+        })),
+    };
+
+    let mut buf = BytesMut::new();
+    thread.write_proto(&mut buf).unwrap();
+    assert_eq!(Thread::read_proto(&mut buf.freeze()).unwrap(), thread);
+
+    // Only fields on a cycle are boxed
+    let source = "syntax = \"proto3\";
+message A {
+  B b = 1;
+  C c = 2;
+}
+message B {
+  A a = 1;
+}
+message C {
+  A a = 1;
+}
+message D {
+  A a = 1;
+}";
+    let code = proto_rw_build::generate(source).unwrap();
+    assert!(code.contains("pub b: Option<Box<B>>,"));
+    assert!(code.contains("pub c: Option<Box<C>>,"));
+    assert!(code.contains("pub struct B {\n    #[field(1)]\n    pub a: Option<Box<A>>,"));
+    assert!(code.contains("pub struct D {\n    #[field(1)]\n    pub a: Option<A>,"));
+}
+
+#[test]
+fn generated_wire_format() {
+    let user = User {
+        id: 150,
+        role: UserRole::Member,
+        ..Default::default()
+    };
+
+    let mut buf = BytesMut::new();
+    user.write_proto(&mut buf).unwrap();
+    // The empty name isn't written
+    assert_eq!(&buf[..], &[0x08, 0x96, 0x01, 0x20, 0x01]);
+}
+
+#[test]
+fn schema_errors() {
+    let error = |source: &str| proto_rw_build::generate(source).unwrap_err().to_string();
+
+    assert_eq!(
+        error("syntax = \"proto2\";"),
+        "Line 1: Only proto3 files are supported"
+    );
+    assert_eq!(
+        error("syntax = \"proto3\";\nmessage A {\n  map<string, int32> b = 1;\n}"),
+        "Line 3: Map fields are not supported"
+    );
+    assert_eq!(error("message A {\n  B b = 1;\n}"), "Unknown type B of A.b");
+    assert_eq!(
+        error("enum E {\n  A = 1;\n}"),
+        "Line 3: The first value of E must be zero in proto3"
+    );
+}
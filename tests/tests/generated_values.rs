@@ -0,0 +1,201 @@
+use bytes::{Bytes, BytesMut};
+use macros::proto_rw;
+use proto_rw::{
+    arbitrary::{Arbitrary, Unstructured},
+    proptest::prelude::*,
+    types::{Var, BE, LE},
+    ProtoRw,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+#[proto_rw(u8, arbitrary, roundtrip_test)]
+enum Body {
+    Ping(BE<u64>) = 0,
+    Data(#[length(u8)] Vec<u8>) = 1,
+    Empty = 2,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[proto_rw(arbitrary, roundtrip_test)]
+struct Frame {
+    kind: u8,
+    count: u8,
+    #[validate(range = 1..=1000)]
+    port: BE<u16>,
+    #[count_from(count)]
+    items: Vec<LE<i32>>,
+    #[tag(kind)]
+    body: Body,
+    has_extra: bool,
+    #[when(*has_extra)]
+    extra: Option<Var<u32>>,
+    #[length(u8)]
+    #[validate(max_len = 4)]
+    bytes: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[proto_rw(u8, arbitrary, roundtrip_test)]
+enum Packet {
+    #[id(0x01)]
+    Ping,
+    #[id(0x02, until = 3)]
+    #[id(0x12, since = 3)]
+    Chat(String),
+    #[id(0x03, until = 2)]
+    Legacy,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+#[proto_rw(protobuf, arbitrary, roundtrip_test)]
+enum Kind {
+    #[default]
+    Unknown = 0,
+    Text = 1,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[proto_rw(protobuf, arbitrary, roundtrip_test)]
+enum Value {
+    #[field(3)]
+    Number(i64),
+    #[field(4)]
+    Name(String),
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+#[proto_rw(protobuf, arbitrary, proptest)]
+struct Message {
+    #[field(1)]
+    id: u32,
+    #[field(2)]
+    kind: Kind,
+    #[oneof(3, 4)]
+    value: Option<Value>,
+    #[field(6)]
+    tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[proto_rw(u8, arbitrary, roundtrip_test)]
+enum Expr {
+    #[id(1)]
+    Number(u8),
+    #[id(0)]
+    Add(Box<Self>, Box<Self>),
+}
+
+impl Expr {
+    fn depth(&self) -> usize {
+        match self {
+            Expr::Number(_) => 0,
+            Expr::Add(left, right) => 1 + left.depth().max(right.depth()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[proto_rw(arbitrary, roundtrip_test)]
+struct Tree {
+    label: u8,
+    #[length(u8)]
+    children: Vec<Tree>,
+}
+
+impl Tree {
+    fn depth(&self) -> usize {
+        self.children
+            .iter()
+            .map(|child| 1 + child.depth())
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+fn roundtrip<T: ProtoRw>(value: &T) -> T {
+    let mut buf = BytesMut::new();
+    value.write_proto(&mut buf).unwrap();
+    let mut buf: Bytes = buf.freeze();
+    let read = T::read_proto(&mut buf).unwrap();
+    assert!(buf.is_empty());
+    read
+}
+
+#[test]
+fn arbitrary_values() {
+    let mut generated = 0;
+    for seed in 0..200u32 {
+        let data: Vec<u8> = (0..256u32)
+            .map(|i| (i.wrapping_mul(seed * 2 + 1) ^ (seed * 31 + i * 7)) as u8)
+            .collect();
+        let mut u = Unstructured::new(&data);
+        // Values whose fields can't be written are rejected
+        let Ok(frame) = Frame::arbitrary(&mut u) else {
+            continue;
+        };
+        generated += 1;
+
+        assert_eq!(frame.count as usize, frame.items.len());
+        assert_eq!(frame.has_extra, frame.extra.is_some());
+        assert!((1..=1000).contains(&frame.port));
+        assert!(frame.bytes.len() <= 4);
+        assert_eq!(roundtrip(&frame), frame);
+    }
+    assert!(generated > 0);
+}
+
+#[test]
+fn arbitrary_latest_ids() {
+    let mut u = Unstructured::new(&[0xff; 64]);
+    for _ in 0..16 {
+        // Legacy has no id in the latest version
+        let packet = Packet::arbitrary(&mut u).unwrap();
+        assert_ne!(packet, Packet::Legacy);
+        assert_eq!(roundtrip(&packet), packet);
+    }
+}
+
+#[test]
+fn arbitrary_length_bound() {
+    // Enough bytes to keep adding items past what the u8 length prefix allows
+    let data = [1; 4096];
+    let mut u = Unstructured::new(&data);
+    let body = loop {
+        if let Body::Data(data) = Body::arbitrary(&mut u).unwrap() {
+            break data;
+        }
+    };
+    assert_eq!(body.len(), u8::MAX as usize);
+}
+
+#[test]
+fn arbitrary_recursive_values() {
+    // Running out of input picks the variant without Self
+    let mut u = Unstructured::new(&[0; 8]);
+    let expr = Expr::arbitrary(&mut u).unwrap();
+    assert_eq!(roundtrip(&expr), expr);
+
+    // Input always picking Add or another child still stops at the maximum depth
+    let data = [0xff; 4096];
+    let expr = Expr::arbitrary(&mut Unstructured::new(&data)).unwrap();
+    assert!(expr.depth() > 0 && expr.depth() <= proto_rw::generate::MAX_DEPTH);
+    assert_eq!(roundtrip(&expr), expr);
+
+    let data = [1; 4096];
+    let tree = Tree::arbitrary(&mut Unstructured::new(&data)).unwrap();
+    assert!(tree.depth() > 0 && tree.depth() <= proto_rw::generate::MAX_DEPTH);
+    assert_eq!(roundtrip(&tree), tree);
+}
+
+proptest! {
+    #[test]
+    fn proptest_messages(message in any::<Message>()) {
+        prop_assert_eq!(roundtrip(&message), message);
+    }
+
+    #[test]
+    fn proptest_recursive_values(expr in any::<Expr>()) {
+        prop_assert!(expr.depth() <= proto_rw::generate::MAX_DEPTH);
+        prop_assert_eq!(roundtrip(&expr), expr);
+    }
+}
@@ -0,0 +1,94 @@
+use macros::proto_rw;
+use proto_rw::{
+    types::{Var, BE, LE},
+    ProtoRw, ProtoSchema,
+};
+
+#[proto_rw]
+struct Position {
+    x: BE<i32>,
+    y: LE<f32>,
+}
+
+#[proto_rw]
+#[magic(b"MV")]
+struct Move {
+    entity: Var<u32>,
+    #[length(Var<u32>)]
+    path: Vec<Position>,
+    name: String,
+    #[when(*entity > 0)]
+    speed: Option<Var<i32>>,
+}
+
+#[proto_rw(u8)]
+enum Packet {
+    Ping = 0,
+    #[id(1 | 2, write = 1)]
+    Move(Move),
+}
+
+#[test]
+fn ksy_types() {
+    assert_eq!(
+        proto_rw_export::ksy::to_ksy(&Position::schema(), &[]),
+        "meta:
+  id: position
+seq:
+  - id: x
+    type: s4be
+  - id: y
+    type: f4le
+"
+    );
+}
+
+#[test]
+fn ksy_enums_and_varints() {
+    let schemas = [Packet::schema(), Move::schema(), Position::schema()];
+    let ksy = proto_rw_export::ksy::to_ksy(&schemas[0], &schemas);
+
+    assert!(ksy.starts_with(
+        "meta:
+  id: packet
+seq:
+  - id: tag
+    type: u1
+  - id: body
+    type:
+      switch-on: tag
+      cases:
+        1: packet_move
+        2: packet_move
+types:
+  packet_move:
+    seq:
+      - id: field_0
+        type: move
+  move:
+    seq:
+      - id: magic
+        contents: [0x4d, 0x56]
+      - id: entity
+        type: varint
+      - id: path_len
+        type: varint
+      - id: path
+        type: position
+        repeat: expr
+        repeat-expr: path_len.value
+      - id: name_len
+        type: varint
+      - id: name
+        type: str
+        encoding: UTF-8
+        size: name_len.value
+      - id: speed
+        type: zigzag_varint
+        if: entity > 0
+  position:
+"
+    ));
+    assert!(ksy.contains("\n  varint:\n    doc: Unsigned LEB128 varint.\n"));
+    assert!(ksy.contains("\n  zigzag_varint:\n    doc: Signed varint, zigzag encoded.\n"));
+}
@@ -0,0 +1,103 @@
+use macros::proto_rw;
+use proto_rw::{
+    types::{Var, BE},
+    ProtoRw, ProtoSchema,
+};
+
+#[proto_rw]
+struct Position {
+    x: BE<i32>,
+    y: BE<i32>,
+}
+
+#[proto_rw]
+#[magic(b"MV")]
+struct Move {
+    entity: Var<u32>,
+    position: Position,
+    #[length(u8)]
+    path: Vec<Position>,
+    on_ground: bool,
+}
+
+#[proto_rw(u8)]
+enum Packet {
+    Ping = 0,
+    #[id(1 | 2, write = 1)]
+    Move(Position, #[pad(2)] BE<u16>),
+    Chat {
+        #[since(2)]
+        text: String,
+    } = 3,
+}
+
+#[test]
+fn markdown_tables() {
+    let schemas = [Position::schema(), Move::schema(), Packet::schema()];
+
+    assert_eq!(
+        proto_rw_export::docs::to_markdown(&schemas[1..]),
+        r#"## Move
+
+| Offset | Field | Wire type | Size | Notes |
+| --- | --- | --- | --- | --- |
+| 0 | (magic) | `[u8; 2]` | 2 | always `b"MV"` |
+| 2 | entity | `Var<u32>` | var | varint |
+| var | position | `Position` | var |  |
+| var | path | `Vec<Position>` | var | length prefix `u8` |
+| var | on_ground | `bool` | 1 |  |
+
+## Packet
+
+| Offset | Field | Wire type | Size | Notes |
+| --- | --- | --- | --- | --- |
+| 0 | (tag) | `u8` | 1 | id of the variant |
+
+### Variants
+
+| Id | Variant | Size | Notes |
+| --- | --- | --- | --- |
+| `0` | Ping | 0 |  |
+| `1 \| 2` | Move | var | written as `1` |
+| `3` | Chat | var |  |
+
+### Packet::Move
+
+| Offset | Field | Wire type | Size | Notes |
+| --- | --- | --- | --- | --- |
+| 1 | 0 | `Position` | var |  |
+| var | 1 | `BE<u16>` | 2 | after `2` zero bytes |
+
+### Packet::Chat
+
+| Offset | Field | Wire type | Size | Notes |
+| --- | --- | --- | --- | --- |
+| 1 | text | `String` | var | length prefix `Var<u32>`, since version `2` |
+
+"#
+    );
+
+    // Sizes of other types are known when their schemas are passed too
+    let markdown = proto_rw_export::docs::to_markdown(&schemas);
+    assert!(markdown.contains("| var | position | `Position` | 8 |  |"));
+    assert!(markdown.contains("| `1 \\| 2` | Move | 12 | written as `1` |"));
+    assert!(markdown.contains("| 11 | 1 | `BE<u16>` | 2 | after `2` zero bytes |"));
+}
+
+#[test]
+fn html_tables() {
+    assert_eq!(
+        proto_rw_export::docs::to_html(&[Position::schema()]),
+        "<h2>Position</h2>
+<table>
+<thead>
+<tr><th>Offset</th><th>Field</th><th>Wire type</th><th>Size</th><th>Notes</th></tr>
+</thead>
+<tbody>
+<tr><td>0</td><td>x</td><td><code>BE&lt;i32&gt;</code></td><td>4</td><td></td></tr>
+<tr><td>4</td><td>y</td><td><code>BE&lt;i32&gt;</code></td><td>4</td><td></td></tr>
+</tbody>
+</table>
+"
+    );
+}
@@ -0,0 +1,123 @@
+use bytes::BytesMut;
+use macros::{packets, proto_rw};
+use proto_rw::{context::Context, types::Var, Packet, ProtoRw};
+
+#[derive(Debug, PartialEq)]
+#[proto_rw]
+struct Handshake {
+    protocol: Var<i32>,
+    next_state: u8,
+}
+
+#[derive(Debug, PartialEq)]
+#[proto_rw]
+struct KeepAlive {
+    id: u8,
+}
+
+#[derive(Debug, PartialEq)]
+#[proto_rw]
+struct Pong {
+    id: u8,
+}
+
+mod chat {
+    use macros::proto_rw;
+
+    #[derive(Debug, PartialEq)]
+    #[proto_rw]
+    pub struct Message {
+        #[length(u8)]
+        pub text: String,
+    }
+}
+
+packets! {
+    id = Var<i32>;
+
+    #[derive(Debug, PartialEq)]
+    Handshaking {
+        Serverbound {
+            0x00 => Handshake,
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    Login {
+        Clientbound {
+            0x04 => KeepAlive,
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    Play {
+        Clientbound {
+            0x00 => KeepAlive,
+            0x7F => chat::Message,
+        }
+        Serverbound {
+            0x00 => Pong,
+        }
+    }
+}
+
+#[test]
+fn packet_ids() {
+    assert_eq!(Handshake::ID, 0x00);
+    assert_eq!(chat::Message::ID, 0x7F);
+
+    let packet = PlayClientbound::from(chat::Message {
+        text: String::from("hi"),
+    });
+    assert_eq!(packet.id(), 0x7F);
+
+    // The same packet has an id in each registry
+    assert_eq!(<KeepAlive as Packet<LoginClientbound>>::ID, 0x04);
+    assert_eq!(<KeepAlive as Packet<PlayClientbound>>::ID, 0x00);
+    let mut buf = BytesMut::new();
+    LoginClientbound::from(KeepAlive { id: 9 })
+        .write_proto(&mut buf)
+        .unwrap();
+    assert_eq!(&buf[..], &[0x08, 9]);
+}
+
+#[test]
+fn packet_rw() {
+    let packet = PlayClientbound::Message(chat::Message {
+        text: String::from("hi"),
+    });
+
+    let mut buf = BytesMut::new();
+    packet.encode(&mut buf, &Context::default()).unwrap();
+    assert_eq!(&buf[..], &[0xFE, 0x01, 2, b'h', b'i']);
+    assert_eq!(
+        PlayClientbound::read_proto(&mut buf.freeze()).unwrap(),
+        packet
+    );
+
+    let mut buf = BytesMut::new();
+    HandshakingServerbound::from(Handshake {
+        protocol: 300,
+        next_state: 2,
+    })
+    .write_proto(&mut buf)
+    .unwrap();
+    assert_eq!(&buf[..], &[0, 0xD8, 0x04, 2]);
+}
+
+#[test]
+fn decode_by_id() {
+    let ctx = Context::default();
+
+    let mut buf = bytes::Bytes::from_static(&[5]);
+    assert_eq!(
+        PlayServerbound::decode(0x00, &mut buf, &ctx).unwrap(),
+        PlayServerbound::Pong(Pong { id: 5 })
+    );
+    assert_eq!(
+        PlayClientbound::decode(0x01, &mut buf, &ctx)
+            .unwrap_err()
+            .to_string(),
+        "Unknown packet id 1 in PlayClientbound"
+    );
+}
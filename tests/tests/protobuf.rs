@@ -0,0 +1,146 @@
+use bytes::{Bytes, BytesMut};
+use macros::proto_rw;
+use proto_rw::{
+    types::{Var, LE},
+    ProtoRw,
+};
+
+#[derive(Debug, Default, PartialEq)]
+#[proto_rw(protobuf)]
+enum Kind {
+    #[default]
+    Unknown = 0,
+    Text = 1,
+    Binary = 2,
+}
+
+#[derive(Debug, Default, PartialEq)]
+#[proto_rw(protobuf)]
+struct Inner {
+    #[field(1)]
+    id: u32,
+}
+
+#[derive(Debug, Default, PartialEq)]
+#[proto_rw(protobuf)]
+struct Message {
+    #[field(1)]
+    a: i32,
+    #[field(2)]
+    b: String,
+    #[field(3)]
+    inner: Option<Inner>,
+    #[field(4)]
+    packed: Vec<i32>,
+    #[field(5)]
+    zigzag: Var<i64>,
+    #[field(6)]
+    fixed: LE<u32>,
+    #[field(7)]
+    ratio: f64,
+    #[field(8)]
+    data: Vec<u8>,
+    #[field(9)]
+    names: Vec<String>,
+    #[field(10)]
+    kind: Kind,
+    #[field(11)]
+    flag: Option<bool>,
+}
+
+#[test]
+fn wire_format() {
+    // The examples of the protobuf encoding guide, where fields at their default aren't written
+    let message = Message {
+        a: 150,
+        b: String::from("testing"),
+        packed: vec![3, 270, 86942],
+        ..Default::default()
+    };
+
+    let mut buf = BytesMut::new();
+    message.write_proto(&mut buf).unwrap();
+    assert_eq!(
+        &buf[..],
+        &[
+            0x08, 0x96, 0x01, // a
+            0x12, 0x07, b't', b'e', b's', b't', b'i', b'n', b'g', // b
+            0x22, 0x06, 0x03, 0x8E, 0x02, 0x9E, 0xA7, 0x05, // packed
+        ]
+    );
+    assert_eq!(Message::read_proto(&mut buf.freeze()).unwrap(), message);
+}
+
+#[test]
+fn default_fields() {
+    // Optional fields are written when present, and -0.0 isn't the default
+    let message = Message {
+        ratio: -0.0,
+        flag: Some(false),
+        ..Default::default()
+    };
+
+    let mut buf = BytesMut::new();
+    message.write_proto(&mut buf).unwrap();
+    assert_eq!(&buf[..], &[0x39, 0, 0, 0, 0, 0, 0, 0, 0x80, 0x58, 0x00]);
+}
+
+#[test]
+fn protobuf_rw() {
+    let message = Message {
+        a: -1,
+        b: String::from("hi"),
+        inner: Some(Inner { id: 7 }),
+        packed: vec![],
+        zigzag: -2,
+        fixed: 0x01020304,
+        ratio: 0.5,
+        data: vec![1, 2],
+        names: vec![String::from("x"), String::from("y")],
+        kind: Kind::Binary,
+        flag: Some(true),
+    };
+
+    let mut buf = BytesMut::new();
+    message.write_proto(&mut buf).unwrap();
+    // Negative int32 values take 10 bytes
+    assert_eq!(
+        &buf[..12],
+        &[0x08, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x01, 0x12]
+    );
+    assert_eq!(Message::read_proto(&mut buf.freeze()).unwrap(), message);
+}
+
+#[test]
+fn unpacked_and_unknown_fields() {
+    let mut buf = Bytes::from_static(&[
+        0x20, 0x01, // packed, unpacked
+        0x98, 0x06, 0x05, // unknown varint field 99
+        0x22, 0x02, 0x02, 0x03, // packed
+        0x9A, 0x06, 0x01, 0xFF, // unknown length-delimited field 99
+        0x9D, 0x06, 0, 0, 0, 0, // unknown 32-bit field 99
+        0x1A, 0x02, 0x08, 0x09, // inner
+    ]);
+    let message = Message::read_proto(&mut buf).unwrap();
+    assert_eq!(message.packed, vec![1, 2, 3]);
+    assert_eq!(message.inner, Some(Inner { id: 9 }));
+    assert_eq!(message.b, "");
+}
+
+#[test]
+fn unknown_enum_values() {
+    // Kind 7 is not declared, and reads as the zero value without failing the message
+    let mut buf = Bytes::from_static(&[0x50, 0x07, 0x08, 0x05]);
+    let message = Message::read_proto(&mut buf).unwrap();
+    assert_eq!(message.kind, Kind::Unknown);
+    assert_eq!(message.a, 5);
+}
+
+#[test]
+fn wrong_wire_type() {
+    let mut buf = Bytes::from_static(&[0x0A, 0x00]);
+    assert_eq!(
+        Message::read_proto(&mut buf).unwrap_err().to_string(),
+        "Expected wire type Varint but found wire type Len"
+    );
+}
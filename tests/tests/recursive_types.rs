@@ -0,0 +1,177 @@
+use std::{collections::BTreeMap, rc::Rc, sync::Arc};
+
+use bytes::BytesMut;
+use macros::proto_rw;
+use proto_rw::{
+    context::Context,
+    schema::{Encoding, Layout},
+    types::{Var, BE},
+    ProtoRw, ProtoSchema,
+};
+
+#[derive(Debug, PartialEq)]
+#[proto_rw(u8)]
+enum Expr {
+    #[id(0)]
+    Number(u8),
+    #[id(1)]
+    Negate(Box<Self>),
+    #[id(2)]
+    Add(Box<Self>, Box<Self>),
+}
+
+#[derive(Debug, PartialEq)]
+#[proto_rw]
+struct Node {
+    value: u8,
+    next: Option<Box<Self>>,
+    #[length(u8)]
+    children: Vec<Self>,
+}
+
+#[derive(Debug, PartialEq)]
+#[proto_rw]
+struct Wrappers {
+    boxed: Box<BE<u16>>,
+    #[length(u8)]
+    optional: Vec<Option<Var<u32>>>,
+    #[length(u8)]
+    map: BTreeMap<u8, BE<u16>>,
+    shared: Rc<u8>,
+    synced: Arc<Var<u32>>,
+}
+
+#[test]
+fn boxed_enum() {
+    let expr = Expr::Add(
+        Box::new(Expr::Number(1)),
+        Box::new(Expr::Negate(Box::new(Expr::Number(2)))),
+    );
+
+    let mut buf = BytesMut::new();
+    expr.write_proto(&mut buf).unwrap();
+    assert_eq!(&buf[..], &[2, 0, 1, 1, 0, 2]);
+    assert_eq!(Expr::read_proto(&mut buf.freeze()).unwrap(), expr);
+}
+
+#[test]
+fn recursive_struct() {
+    let node = Node {
+        value: 1,
+        next: Some(Box::new(Node {
+            value: 2,
+            next: None,
+            children: vec![],
+        })),
+        children: vec![Node {
+            value: 3,
+            next: None,
+            children: vec![],
+        }],
+    };
+
+    let mut buf = BytesMut::new();
+    node.write_proto(&mut buf).unwrap();
+    assert_eq!(&buf[..], &[1, 1, 2, 0, 0, 1, 3, 0, 0]);
+    assert_eq!(Node::read_proto(&mut buf.freeze()).unwrap(), node);
+}
+
+#[test]
+fn nested_wrappers() {
+    let wrappers = Wrappers {
+        boxed: Box::new(0x0102),
+        optional: vec![Some(300), None],
+        map: BTreeMap::from([(2, 3), (1, 4)]),
+        shared: Rc::new(5),
+        synced: Arc::new(6),
+    };
+
+    let mut buf = BytesMut::new();
+    wrappers.write_proto(&mut buf).unwrap();
+    assert_eq!(
+        &buf[..],
+        &[1, 2, 2, 1, 0xAC, 0x02, 0, 2, 1, 0, 4, 2, 0, 3, 5, 6]
+    );
+    assert_eq!(Wrappers::read_proto(&mut buf.freeze()).unwrap(), wrappers);
+}
+
+#[test]
+fn depth_limit() {
+    let mut expr = Expr::Number(0);
+    for _ in 0..4 {
+        expr = Expr::Negate(Box::new(expr));
+    }
+
+    let mut buf = BytesMut::new();
+    expr.write_proto(&mut buf).unwrap();
+    let buf = buf.freeze();
+
+    let ctx = Context::default().with_max_depth(5);
+    assert_eq!(Expr::read_proto_ctx(&mut buf.clone(), &ctx).unwrap(), expr);
+
+    let ctx = Context::default().with_max_depth(4);
+    assert_eq!(
+        Expr::read_proto_ctx(&mut buf.clone(), &ctx)
+            .unwrap_err()
+            .to_string(),
+        "Nested deeper than the maximum depth of 4"
+    );
+}
+
+#[test]
+fn context_depth_not_compared() {
+    let ctx = Context::default().with_max_depth(4);
+    let _guard = ctx.enter().unwrap();
+    // Clones and comparisons ignore how deep reading is
+    let clone = ctx.clone();
+    assert_eq!(clone, ctx);
+    assert_eq!(clone, Context::default().with_max_depth(4));
+    for _ in 0..4 {
+        std::mem::forget(clone.enter().unwrap());
+    }
+    assert!(clone.enter().is_err());
+    assert_ne!(clone, Context::new(3).with_max_depth(4));
+}
+
+#[test]
+fn recursive_schemas() {
+    // `Self` is named after the type, so exports can refer to it
+    let Layout::Enum { variants, .. } = Expr::schema().layout else {
+        panic!("Expected an enum layout");
+    };
+    assert_eq!(
+        variants[1].fields[0].encoding,
+        Encoding::Type {
+            name: "Expr".to_string(),
+        }
+    );
+
+    let schema = Node::schema();
+    let Layout::Struct { fields } = &schema.layout else {
+        panic!("Expected a struct layout");
+    };
+    let node = Encoding::Type {
+        name: "Node".to_string(),
+    };
+    assert_eq!(
+        fields[1].encoding,
+        Encoding::Optional {
+            value: Box::new(node.clone()),
+        }
+    );
+    let Encoding::List { item, .. } = &fields[2].encoding else {
+        panic!("Expected a list");
+    };
+    assert_eq!(**item, node);
+
+    let schemas = [Expr::schema()];
+    let ksy = proto_rw_export::ksy::to_ksy(&schemas[0], &schemas);
+    assert!(ksy.contains("  expr_negate:\n    seq:\n      - id: field_0\n        type: expr\n"));
+    let lua = proto_rw_export::wireshark::to_lua("Calc", &schemas[0], &schemas);
+    assert!(lua.contains(
+        "    pinfo.cols.info:append(\" Negate\")
+    local start = offset
+    local v = {}
+    offset = dissect.expr(tvb, pinfo, offset, tree)"
+    ));
+}
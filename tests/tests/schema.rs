@@ -0,0 +1,260 @@
+use bytes::BytesMut;
+use macros::proto_rw;
+use proto_rw::{
+    schema::{
+        Constant, Encoding, EnumValue, Field, Label, Layout, Length, MessageField, Schema, Variant,
+        VariantId,
+    },
+    types::{Var, BE},
+    ProtoRw, ProtoSchema,
+};
+
+#[proto_rw]
+struct Point {
+    x: BE<u16>,
+    y: BE<u16>,
+}
+
+#[proto_rw]
+#[magic(b"PT")]
+struct Shape {
+    #[length(u8)]
+    points: Vec<Point>,
+    len: u8,
+    #[count_from(len)]
+    name: String,
+    #[when(*len > 0)]
+    flag: Option<bool>,
+}
+
+#[proto_rw(Var<u32>)]
+enum Command {
+    #[id(0 | 1, write = 0)]
+    Stop,
+    #[size_prefix(u8)]
+    Move(Point) = 2,
+}
+
+#[proto_rw(protobuf)]
+struct Message {
+    #[field(1)]
+    id: Var<i32>,
+    #[field(2)]
+    tags: Vec<String>,
+    #[field(3)]
+    kind: Option<Kind>,
+}
+
+#[proto_rw(protobuf)]
+enum Kind {
+    Text = 0,
+    Image = 1,
+}
+
+fn field(name: &str, encoding: Encoding) -> Field {
+    Field {
+        name: name.to_string(),
+        encoding,
+        when: None,
+        since: None,
+        until: None,
+        pad: None,
+        align: None,
+    }
+}
+
+fn number(name: &str) -> Encoding {
+    Encoding::Number {
+        name: name.to_string(),
+    }
+}
+
+#[test]
+fn struct_schema() {
+    assert_eq!(
+        Point::schema(),
+        Schema {
+            name: "Point".to_string(),
+            magic: None,
+            size_prefix: None,
+            layout: Layout::Struct {
+                fields: vec![field("x", number("BE<u16>")), field("y", number("BE<u16>"))],
+            },
+        }
+    );
+
+    let shape = Shape::schema();
+    assert_eq!(
+        shape.magic,
+        Some(Constant {
+            encoding: Encoding::Array {
+                len: 2,
+                item: Box::new(number("u8")),
+            },
+            value: "b\"PT\"".to_string(),
+        })
+    );
+    assert_eq!(
+        shape.layout,
+        Layout::Struct {
+            fields: vec![
+                field(
+                    "points",
+                    Encoding::List {
+                        length: Length::Prefix {
+                            encoding: Box::new(number("u8")),
+                        },
+                        item: Box::new(Encoding::Type {
+                            name: "Point".to_string(),
+                        }),
+                    },
+                ),
+                field("len", number("u8")),
+                field(
+                    "name",
+                    Encoding::String {
+                        length: Length::CountFrom {
+                            field: "len".to_string(),
+                        },
+                    },
+                ),
+                Field {
+                    when: Some("* len > 0".to_string()),
+                    ..field("flag", Encoding::Bool)
+                },
+            ],
+        }
+    );
+
+    // The fields are written in the order of the schema
+    let mut buf = BytesMut::new();
+    Shape {
+        points: vec![Point { x: 1, y: 2 }],
+        len: 0,
+        name: "a".to_string(),
+        flag: Some(true),
+    }
+    .write_proto(&mut buf)
+    .unwrap();
+    assert_eq!(&buf[..], b"PT\x01\x00\x01\x00\x02\x01a\x01");
+    assert_eq!(Shape::read_proto(&mut buf.freeze()).unwrap().len, 1);
+}
+
+#[test]
+fn enum_schema() {
+    assert_eq!(
+        Command::schema().layout,
+        Layout::Enum {
+            tag: number("Var<u32>"),
+            variants: vec![
+                Variant {
+                    name: "Stop".to_string(),
+                    ids: vec![VariantId {
+                        pattern: "0 | 1".to_string(),
+                        value: "0".to_string(),
+                        since: None,
+                        until: None,
+                    }],
+                    since: None,
+                    until: None,
+                    size_prefix: None,
+                    fields: vec![],
+                },
+                Variant {
+                    name: "Move".to_string(),
+                    ids: vec![VariantId {
+                        pattern: "2".to_string(),
+                        value: "2".to_string(),
+                        since: None,
+                        until: None,
+                    }],
+                    since: None,
+                    until: None,
+                    size_prefix: Some(number("u8")),
+                    fields: vec![field(
+                        "0",
+                        Encoding::Type {
+                            name: "Point".to_string(),
+                        },
+                    )],
+                },
+            ],
+        }
+    );
+}
+
+#[test]
+fn protobuf_schema() {
+    let message_field = |name: &str, number: u32, label, ty: &str| MessageField {
+        name: name.to_string(),
+        numbers: vec![number],
+        label,
+        ty: ty.to_string(),
+    };
+
+    assert_eq!(
+        Message::schema().layout,
+        Layout::Message {
+            fields: vec![
+                message_field("id", 1, Label::Singular, "Var<i32>"),
+                message_field("tags", 2, Label::Repeated, "String"),
+                message_field("kind", 3, Label::Optional, "Kind"),
+            ],
+        }
+    );
+    assert_eq!(
+        Kind::schema().layout,
+        Layout::MessageEnum {
+            values: vec![
+                EnumValue {
+                    name: "Text".to_string(),
+                    value: "0".to_string(),
+                },
+                EnumValue {
+                    name: "Image".to_string(),
+                    value: "1".to_string(),
+                },
+            ],
+        }
+    );
+}
+
+#[test]
+fn schema_json() {
+    let json = serde_json::to_value(Point::schema()).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "name": "Point",
+            "magic": null,
+            "size_prefix": null,
+            "layout": {
+                "kind": "struct",
+                "fields": [
+                    {
+                        "name": "x",
+                        "encoding": { "kind": "number", "name": "BE<u16>" },
+                        "when": null,
+                        "since": null,
+                        "until": null,
+                        "pad": null,
+                        "align": null,
+                    },
+                    {
+                        "name": "y",
+                        "encoding": { "kind": "number", "name": "BE<u16>" },
+                        "when": null,
+                        "since": null,
+                        "until": null,
+                        "pad": null,
+                        "align": null,
+                    },
+                ],
+            },
+        })
+    );
+
+    let schema = Shape::schema();
+    let json = serde_json::to_string(&schema).unwrap();
+    assert_eq!(serde_json::from_str::<Schema>(&json).unwrap(), schema);
+}
@@ -0,0 +1,92 @@
+use bytes::{Bytes, BytesMut};
+use macros::proto_rw;
+use proto_rw::{
+    types::{Var, BE},
+    ProtoRw,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+#[proto_rw]
+struct Inner {
+    a: BE<u16>,
+    b: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[proto_rw]
+struct Outer {
+    #[size_prefix(Var<u32>)]
+    inner: Inner,
+    #[size_prefix(u8, skip_trailing)]
+    lenient: Inner,
+    #[size_prefix(BE<u16>)]
+    #[length(u8)]
+    list: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[proto_rw]
+#[size_prefix(Var<u32>)]
+struct Framed(u8, BE<u32>);
+
+#[derive(Debug, Clone, PartialEq)]
+#[proto_rw(u8)]
+enum Packet {
+    #[size_prefix(u8)]
+    A(BE<u16>) = 0,
+    B {
+        a: u8,
+    } = 1,
+}
+
+#[test]
+fn size_prefix() {
+    let outer = Outer {
+        inner: Inner {
+            a: 1,
+            b: "a".to_string(),
+        },
+        lenient: Inner {
+            a: 2,
+            b: String::new(),
+        },
+        list: vec![1, 2],
+    };
+
+    let mut buf = BytesMut::new();
+    outer.write_proto(&mut buf).unwrap();
+    assert_eq!(&buf[..], &[4, 0, 1, 1, b'a', 3, 0, 2, 0, 0, 3, 2, 1, 2]);
+
+    let mut buf = buf.freeze();
+    assert_eq!(Outer::read_proto(&mut buf).unwrap(), outer);
+}
+
+#[test]
+fn size_prefix_trailing() {
+    let mut buf = Bytes::from_static(&[4, 0, 1, 0, 9, 4, 0, 2, 0, 9, 0, 1, 0]);
+    let err = Outer::read_proto(&mut buf).unwrap_err();
+    assert_eq!(err.to_string(), "1 bytes left after reading inner");
+
+    let mut buf = Bytes::from_static(&[3, 0, 1, 0, 4, 0, 2, 0, 9, 0, 1, 0]);
+    let outer = Outer::read_proto(&mut buf).unwrap();
+    assert_eq!(outer.lenient.a, 2);
+    assert!(buf.is_empty());
+
+    let mut buf = Bytes::from_static(&[9, 0, 1, 0]);
+    let err = Outer::read_proto(&mut buf).unwrap_err();
+    assert_eq!(err.to_string(), "Unexpected EOF");
+}
+
+#[test]
+fn size_prefix_containers() {
+    let mut buf = BytesMut::new();
+    Framed(1, 2).write_proto(&mut buf).unwrap();
+    Packet::A(3).write_proto(&mut buf).unwrap();
+    Packet::B { a: 4 }.write_proto(&mut buf).unwrap();
+    assert_eq!(&buf[..], &[5, 1, 0, 0, 0, 2, 0, 2, 0, 3, 1, 4]);
+
+    let mut buf = buf.freeze();
+    assert_eq!(Framed::read_proto(&mut buf).unwrap(), Framed(1, 2));
+    assert_eq!(Packet::read_proto(&mut buf).unwrap(), Packet::A(3));
+    assert_eq!(Packet::read_proto(&mut buf).unwrap(), Packet::B { a: 4 });
+}
@@ -0,0 +1,99 @@
+use bytes::{Bytes, BytesMut};
+use macros::proto_rw;
+use proto_rw::{
+    types::{Var, BE},
+    ProtoRw,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+#[proto_rw]
+struct Entry {
+    id: u8,
+    name: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[proto_rw]
+struct Table {
+    num_entries: BE<u16>,
+    name_len: u8,
+    entries_size: Var<u32>,
+    flags: u8,
+    #[count_from(num_entries)]
+    entries: Vec<Entry>,
+    #[count_from(name_len)]
+    name: String,
+    #[byte_len_from(entries_size)]
+    #[length(BE<u16>)]
+    sized: Vec<Vec<BE<u16>>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[proto_rw]
+struct Blob(u8, #[byte_len_from(0)] Vec<u8>);
+
+#[test]
+fn sized_fields() {
+    let table = Table {
+        num_entries: 0,
+        name_len: 0,
+        entries_size: 0,
+        flags: 1,
+        entries: vec![
+            Entry {
+                id: 1,
+                name: "a".to_string(),
+            },
+            Entry {
+                id: 2,
+                name: "bc".to_string(),
+            },
+        ],
+        name: "table".to_string(),
+        sized: vec![vec![1, 2], vec![3]],
+    };
+
+    let mut buf = BytesMut::new();
+    table.write_proto(&mut buf).unwrap();
+
+    let mut expected = vec![0, 2, 5, 10, 1];
+    expected.extend_from_slice(&[1, 1, b'a', 2, 2, b'b', b'c']);
+    expected.extend_from_slice(b"table");
+    expected.extend_from_slice(&[0, 2, 0, 1, 0, 2, 0, 1, 0, 3]);
+    assert_eq!(&buf[..], &expected[..]);
+
+    let mut buf = buf.freeze();
+    let table2 = Table::read_proto(&mut buf).unwrap();
+    assert_eq!(table2.num_entries, 2);
+    assert_eq!(table2.name_len, 5);
+    assert_eq!(table2.entries_size, 10);
+    assert_eq!(table2.entries, table.entries);
+    assert_eq!(table2.name, table.name);
+    assert_eq!(table2.sized, table.sized);
+}
+
+#[test]
+fn sized_fields_byte_len() {
+    let blob = Blob(0, vec![1, 2, 3]);
+
+    let mut buf = BytesMut::new();
+    blob.write_proto(&mut buf).unwrap();
+    assert_eq!(&buf[..], &[3, 1, 2, 3]);
+
+    let mut buf = Bytes::from_static(&[2, 1, 2, 3]);
+    assert_eq!(Blob::read_proto(&mut buf).unwrap(), Blob(2, vec![1, 2]));
+    assert_eq!(&buf[..], &[3]);
+
+    let mut buf = Bytes::from_static(&[4, 1, 2, 3]);
+    let err = Blob::read_proto(&mut buf).unwrap_err();
+    assert_eq!(err.to_string(), "Unexpected EOF");
+}
+
+#[test]
+fn sized_fields_overflow() {
+    let blob = Blob(0, vec![0; 256]);
+
+    let mut buf = BytesMut::new();
+    let err = blob.write_proto(&mut buf).unwrap_err();
+    assert_eq!(err.to_string(), "Size of index_1 does not fit in index_0");
+}
@@ -0,0 +1,77 @@
+use bytes::BytesMut;
+use macros::proto_rw;
+use proto_rw::{
+    types::{Var, BE},
+    ProtoRw,
+};
+use std::time::Instant;
+
+#[derive(Debug, Clone)]
+#[proto_rw]
+struct Message {
+    id: Var<u32>,
+    text: String,
+    #[skip]
+    cached_words: Vec<String>,
+    #[skip(default = text.len())]
+    text_len: usize,
+    #[skip(default = Some(Instant::now()))]
+    received: Option<Instant>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[proto_rw(u8)]
+enum Event {
+    Move(BE<i32>, #[skip] bool) = 0,
+    Chat {
+        #[skip(default = 7)]
+        channel: u32,
+        text: String,
+    } = 1,
+}
+
+#[test]
+fn skip_fields() {
+    let message = Message {
+        id: 1,
+        text: "hello world".to_string(),
+        cached_words: vec!["hello".to_string(), "world".to_string()],
+        text_len: 0,
+        received: None,
+    };
+
+    let mut buf = BytesMut::new();
+    message.write_proto(&mut buf).unwrap();
+    assert_eq!(buf.len(), 1 + 1 + 11);
+
+    let mut buf = buf.freeze();
+    let message2 = Message::read_proto(&mut buf).unwrap();
+    assert_eq!(message2.id, 1);
+    assert_eq!(message2.text, "hello world");
+    assert!(message2.cached_words.is_empty());
+    assert_eq!(message2.text_len, 11);
+    assert!(message2.received.is_some());
+}
+
+#[test]
+fn skip_variant_fields() {
+    let mut buf = BytesMut::new();
+    Event::Move(-1, true).write_proto(&mut buf).unwrap();
+    Event::Chat {
+        channel: 1,
+        text: "a".to_string(),
+    }
+    .write_proto(&mut buf)
+    .unwrap();
+    assert_eq!(&buf[..], &[0, 0xFF, 0xFF, 0xFF, 0xFF, 1, 1, b'a']);
+
+    let mut buf = buf.freeze();
+    assert_eq!(Event::read_proto(&mut buf).unwrap(), Event::Move(-1, false));
+    assert_eq!(
+        Event::read_proto(&mut buf).unwrap(),
+        Event::Chat {
+            channel: 7,
+            text: "a".to_string()
+        }
+    );
+}
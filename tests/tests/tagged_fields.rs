@@ -0,0 +1,104 @@
+use bytes::BytesMut;
+use macros::proto_rw;
+use proto_rw::{
+    context::Context,
+    types::{Var, BE},
+    ProtoRw, ProtoRwTagged,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+#[proto_rw(u8)]
+enum Body {
+    Ping(BE<u64>) = 0,
+    Text(String) = 1,
+    Empty = 2,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[proto_rw]
+struct Frame {
+    kind: u8,
+    flags: u8,
+    len: BE<u16>,
+    #[tag(kind)]
+    body: Body,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[proto_rw(Var<i32>)]
+enum Payload {
+    A(u8) = 10,
+    B { a: bool, b: String } = 20,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[proto_rw]
+struct Pair(Var<i32>, u8, #[tag(0)] Payload);
+
+#[test]
+fn tagged_fields() {
+    let frame = Frame {
+        kind: 1,
+        flags: 0x80,
+        len: 6,
+        body: Body::Text("hello".to_string()),
+    };
+
+    let mut buf = BytesMut::new();
+    frame.write_proto(&mut buf).unwrap();
+    assert_eq!(&buf[..], &[1, 0x80, 0, 6, 5, b'h', b'e', b'l', b'l', b'o']);
+
+    let mut buf = buf.freeze();
+    assert_eq!(Frame::read_proto(&mut buf).unwrap(), frame);
+
+    let pair = Pair(
+        20,
+        7,
+        Payload::B {
+            a: true,
+            b: "x".to_string(),
+        },
+    );
+
+    let mut buf = BytesMut::new();
+    pair.write_proto(&mut buf).unwrap();
+
+    let mut buf = buf.freeze();
+    assert_eq!(Pair::read_proto(&mut buf).unwrap(), pair);
+}
+
+#[test]
+fn tagged_fields_write_tag_from_variant() {
+    let frame = Frame {
+        kind: 42,
+        flags: 0,
+        len: 0,
+        body: Body::Empty,
+    };
+
+    let mut buf = BytesMut::new();
+    frame.write_proto(&mut buf).unwrap();
+    assert_eq!(&buf[..], &[2, 0, 0, 0]);
+
+    let mut buf = buf.freeze();
+    let frame2 = Frame::read_proto(&mut buf).unwrap();
+    assert_eq!(frame2.kind, 2);
+    assert_eq!(frame2.body, Body::Empty);
+}
+
+#[test]
+fn tagged_enum() {
+    let ctx = Context::default();
+    assert_eq!(Body::Ping(1).tag(&ctx).unwrap(), 0);
+    assert_eq!(Payload::A(1).tag(&ctx).unwrap(), 10);
+
+    let mut buf = BytesMut::new();
+    Body::Ping(1).write_proto_untagged(&mut buf, &ctx).unwrap();
+    assert_eq!(&buf[..], &[0, 0, 0, 0, 0, 0, 0, 1]);
+
+    let mut buf = buf.freeze();
+    assert_eq!(
+        Body::read_proto_tagged(&0, &mut buf, &ctx).unwrap(),
+        Body::Ping(1)
+    );
+}
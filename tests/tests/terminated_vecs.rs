@@ -0,0 +1,92 @@
+use bytes::{Bytes, BytesMut};
+use macros::proto_rw;
+use proto_rw::{types::BE, ProtoRw};
+
+#[derive(Debug, Clone, PartialEq)]
+#[proto_rw]
+struct Options {
+    #[terminator(0x00)]
+    ids: Vec<u8>,
+    #[terminator(b"\r\n")]
+    line: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[proto_rw]
+struct Entry {
+    index: u8,
+    value: BE<u16>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[proto_rw]
+struct Metadata {
+    #[terminator(0xFF)]
+    entries: Vec<Entry>,
+    #[until_item(|part| part & 0x80 == 0)]
+    parts: Vec<u8>,
+    #[until_eof]
+    rest: Vec<BE<u16>>,
+}
+
+#[test]
+fn terminated_vecs() {
+    let options = Options {
+        ids: vec![3, 1, 2],
+        line: b"hi".to_vec(),
+    };
+
+    let mut buf = BytesMut::new();
+    options.write_proto(&mut buf).unwrap();
+    assert_eq!(&buf[..], b"\x03\x01\x02\x00hi\r\n");
+    assert_eq!(Options::read_proto(&mut buf.freeze()).unwrap(), options);
+
+    let err = Options::read_proto(&mut Bytes::from_static(&[1, 2])).unwrap_err();
+    assert_eq!(err.to_string(), "Unexpected EOF");
+
+    // Items starting with the terminator would end the list early when read
+    let bad = Options {
+        ids: vec![3, 0],
+        line: b"hi".to_vec(),
+    };
+    let err = bad.write_proto(&mut BytesMut::new()).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "An item of the list starts with its terminator"
+    );
+    let bad = Options {
+        ids: vec![],
+        line: b"a\r\nb".to_vec(),
+    };
+    assert!(bad.write_proto(&mut BytesMut::new()).is_err());
+}
+
+#[test]
+fn predicate_and_eof_vecs() {
+    let metadata = Metadata {
+        entries: vec![Entry { index: 0, value: 7 }, Entry { index: 2, value: 9 }],
+        parts: vec![0x81, 0x82, 0x03],
+        rest: vec![1, 2],
+    };
+
+    let mut buf = BytesMut::new();
+    metadata.write_proto(&mut buf).unwrap();
+    assert_eq!(
+        &buf[..],
+        &[0, 0, 7, 2, 0, 9, 0xFF, 0x81, 0x82, 0x03, 0, 1, 0, 2]
+    );
+
+    let mut buf = buf.freeze();
+    assert_eq!(Metadata::read_proto(&mut buf).unwrap(), metadata);
+    assert!(buf.is_empty());
+
+    let bad = Metadata {
+        parts: vec![0x01, 0x02],
+        ..metadata
+    };
+    let err = bad.write_proto(&mut BytesMut::new()).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Only the last item of the list can match until_item"
+    );
+}
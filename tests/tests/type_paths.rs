@@ -0,0 +1,58 @@
+use bytes::BytesMut;
+use macros::proto_rw;
+use proto_rw::{
+    types::{Var, BE},
+    ProtoRw, ProtoRwWrapper,
+};
+
+type Id = Var<u32>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[proto_rw(transparent)]
+struct EntityId(Var<i32>);
+
+#[derive(Debug, Clone, PartialEq)]
+#[proto_rw(transparent)]
+struct Port {
+    value: BE<u16>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[proto_rw]
+struct Spawn {
+    kind: proto_rw::types::BE<u16>,
+    #[length(proto_rw::types::Var<u32>)]
+    data: std::vec::Vec<u8>,
+    #[transparent]
+    id: Id,
+    #[transparent]
+    #[length(u8)]
+    others: Vec<Id>,
+    entity: EntityId,
+    #[transparent]
+    port: Port,
+}
+
+#[test]
+fn type_paths() {
+    let spawn = Spawn {
+        kind: 0x0102,
+        data: vec![7],
+        id: 300,
+        others: vec![1, 2],
+        entity: EntityId(-1),
+        port: 8080,
+    };
+
+    let mut buf = BytesMut::new();
+    spawn.write_proto(&mut buf).unwrap();
+    assert_eq!(&buf[..], &[1, 2, 1, 7, 0xAC, 0x02, 2, 1, 2, 1, 0x1F, 0x90]);
+    assert_eq!(Spawn::read_proto(&mut buf.freeze()).unwrap(), spawn);
+}
+
+#[test]
+fn transparent_wrapper() {
+    assert_eq!(EntityId::from_inner(5).into_inner(), 5);
+    assert_eq!(Port::from_inner(80), Port { value: 80 });
+    assert_eq!(BE::from_inner(3u16).into_inner(), 3);
+}
@@ -0,0 +1,20 @@
+use macros::proto_rw;
+
+#[proto_rw]
+struct Packet {
+    #[tag(1 + 2)]
+    a: u8,
+    #[when]
+    b: u8,
+    #[size_prefix(u8, trailing)]
+    c: String,
+    #[validate(range = 1..3, between = 4)]
+    d: u8,
+}
+
+#[proto_rw(endian = "middle")]
+struct Defaults {
+    a: u16,
+}
+
+fn main() {}
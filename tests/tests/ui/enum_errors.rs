@@ -0,0 +1,21 @@
+use macros::proto_rw;
+
+#[proto_rw(u8)]
+enum Message {
+    Ping,
+    #[id(1 | 2)]
+    Text(String),
+    #[id(3)]
+    Legacy = 4,
+    #[id(5, read = 5)]
+    Other,
+    #[id(6 | other, write = 6)]
+    Catch,
+}
+
+#[proto_rw]
+enum Untagged {
+    A = 0,
+}
+
+fn main() {}
@@ -0,0 +1,19 @@
+use macros::proto_rw;
+
+#[proto_rw]
+struct Packet {
+    names: Vec<String>,
+    #[count_from(len)]
+    items: Vec<u8>,
+    raw: [u8],
+    #[length(u16)]
+    #[unknown(value)]
+    ok: Vec<u8>,
+    #[length(u8)]
+    #[until(value = 0)]
+    ended: Vec<u8>,
+    #[validate(range = 1..=5 max_len = 3)]
+    port: u8,
+}
+
+fn main() {}
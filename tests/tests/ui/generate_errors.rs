@@ -0,0 +1,28 @@
+use macros::proto_rw;
+
+#[proto_rw(arbitrary)]
+struct Parts {
+    #[until_item(|part| part & 0x80 == 0)]
+    parts: Vec<u8>,
+}
+
+#[proto_rw(proptest)]
+struct Wrapper<T: proto_rw::ProtoRw> {
+    value: T,
+}
+
+#[proto_rw(u8, roundtrip_test)]
+enum Never {}
+
+#[proto_rw(arbitrary)]
+struct Chain {
+    next: Box<Chain>,
+}
+
+#[proto_rw(u8, proptest)]
+enum Loop {
+    #[id(0)]
+    Again(Box<Self>),
+}
+
+fn main() {}
@@ -0,0 +1,40 @@
+use macros::{packets, proto_rw};
+
+#[proto_rw]
+struct Ping {
+    id: u8,
+}
+
+#[proto_rw]
+struct Pong {
+    id: u8,
+}
+
+#[proto_rw]
+struct Status {
+    id: u8,
+}
+
+packets! {
+    id = proto_rw::types::Var<i32>;
+
+    Play {
+        Clientbound {
+            0x00 => Ping,
+            0x01 => Ping,
+        }
+        Serverbound {
+            0x00 => Ping,
+            0x01 => [u8; 2],
+        }
+    }
+
+    Status {
+        Clientbound {
+            0x00 => Pong,
+            0 => Status,
+        }
+    }
+}
+
+fn main() {}
@@ -0,0 +1,37 @@
+use macros::proto_rw;
+
+#[proto_rw(protobuf)]
+struct Message {
+    #[field(1)]
+    a: u32,
+    #[field(1)]
+    b: u32,
+    c: u32,
+    #[field(4)]
+    #[length(u8)]
+    d: Vec<u32>,
+    #[oneof(5, 6)]
+    e: Body,
+}
+
+#[proto_rw(protobuf)]
+enum Kind {
+    A = 0,
+    B,
+}
+
+#[proto_rw(protobuf)]
+enum Body {
+    #[field(5)]
+    Text(String),
+    Empty,
+    #[field(6)]
+    Both(u32, u32),
+}
+
+#[proto_rw(u8, protobuf)]
+enum Tagged {
+    A = 0,
+}
+
+fn main() {}
@@ -0,0 +1,12 @@
+use macros::proto_rw;
+
+#[proto_rw]
+struct Empty;
+
+#[proto_rw]
+union Bits {
+    a: u8,
+    b: i8,
+}
+
+fn main() {}
@@ -0,0 +1,104 @@
+use bytes::{Bytes, BytesMut};
+use macros::proto_rw;
+use proto_rw::{error::ProtoRwError, types::BE, ProtoRw};
+
+fn is_ascii(value: &str) -> bool {
+    value.is_ascii()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[proto_rw]
+struct Login {
+    #[validate(range = 1..=65535)]
+    port: BE<u32>,
+    #[validate(min_len = 3, max_len = 16, fn = is_ascii)]
+    name: String,
+    #[validate(range = 0..100, on_write)]
+    level: u8,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[proto_rw(u8)]
+enum Command {
+    Kick(#[validate(max_len = 4)] String) = 0,
+}
+
+fn read(data: &[u8]) -> Result<Login, ProtoRwError> {
+    Login::read_proto(&mut Bytes::copy_from_slice(data))
+}
+
+fn encode(port: u32, name: &str, level: u8) -> Vec<u8> {
+    let mut data = port.to_be_bytes().to_vec();
+    data.push(name.len() as u8);
+    data.extend_from_slice(name.as_bytes());
+    data.push(level);
+    data
+}
+
+#[test]
+fn validation_read() {
+    let login = read(&encode(25565, "steve", 1)).unwrap();
+    assert_eq!(login.port, 25565);
+
+    let err = read(&encode(0, "steve", 1)).unwrap_err();
+    assert_eq!(err.to_string(), "Invalid port: 0 is not in 1 ..= 65535");
+
+    let err = read(&encode(80, "st", 1)).unwrap_err();
+    assert_eq!(err.to_string(), "Invalid name: length 2 is less than 3");
+
+    let err = read(&encode(80, "a_very_long_player_name", 1)).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Invalid name: length 23 is greater than 16"
+    );
+
+    let err = read(&encode(80, "stéve", 1)).unwrap_err();
+    assert!(matches!(
+        err,
+        ProtoRwError::Validation { field: "name", .. }
+    ));
+
+    let err = read(&encode(80, "steve", 100)).unwrap_err();
+    assert_eq!(err.to_string(), "Invalid level: 100 is not in 0 .. 100");
+
+    let mut buf = Bytes::from_static(&[0, 5, b'a', b'b', b'c', b'd', b'e']);
+    let err = Command::read_proto(&mut buf).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Invalid index_0: length 5 is greater than 4"
+    );
+
+    // Lengths of strings count characters, not bytes
+    let mut data = vec![0, 8];
+    data.extend_from_slice("éééé".as_bytes());
+    let command = Command::read_proto(&mut Bytes::from(data)).unwrap();
+    assert_eq!(command, Command::Kick("éééé".to_string()));
+
+    let mut data = vec![0, 10];
+    data.extend_from_slice("ééééé".as_bytes());
+    let err = Command::read_proto(&mut Bytes::from(data)).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Invalid index_0: length 5 is greater than 4"
+    );
+}
+
+#[test]
+fn validation_write() {
+    let login = Login {
+        port: 0,
+        name: "x".to_string(),
+        level: 1,
+    };
+    let mut buf = BytesMut::new();
+    login.write_proto(&mut buf).unwrap();
+
+    let login = Login {
+        port: 80,
+        name: "steve".to_string(),
+        level: 200,
+    };
+    let mut buf = BytesMut::new();
+    let err = login.write_proto(&mut buf).unwrap_err();
+    assert_eq!(err.to_string(), "Invalid level: 200 is not in 0 .. 100");
+}
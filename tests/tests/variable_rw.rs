@@ -17,3 +17,27 @@ fn variable_rw() {
     assert_eq!(Var::<i64>::read_proto(&mut buf).unwrap().0, -1234567);
     assert_eq!(Var::<u128>::read_proto(&mut buf).unwrap().0, 123456789);
 }
+
+#[test]
+fn zigzag_encoding() {
+    fn encode<T>(value: T) -> Vec<u8>
+    where
+        Var<T>: ProtoRw,
+    {
+        let mut buf = BytesMut::new();
+        Var(value).write_proto(&mut buf).unwrap();
+        buf.to_vec()
+    }
+
+    // Signed values alternate between positive and negative, like protobuf's sint32
+    assert_eq!(encode(0i32), [0x00]);
+    assert_eq!(encode(-1i32), [0x01]);
+    assert_eq!(encode(1i32), [0x02]);
+    assert_eq!(encode(-2i32), [0x03]);
+    assert_eq!(encode(i32::MAX), [0xfe, 0xff, 0xff, 0xff, 0x0f]);
+    assert_eq!(encode(i32::MIN), [0xff, 0xff, 0xff, 0xff, 0x0f]);
+    assert_eq!(encode(i16::MIN), [0xff, 0xff, 0x03]);
+
+    let mut buf = bytes::Bytes::from_static(&[0xff, 0xff, 0xff, 0xff, 0x0f]);
+    assert_eq!(Var::<i32>::read_proto(&mut buf).unwrap().0, i32::MIN);
+}
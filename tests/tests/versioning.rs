@@ -0,0 +1,134 @@
+use bytes::{Bytes, BytesMut};
+use macros::proto_rw;
+use proto_rw::{context::Context, types::Var, ProtoRw};
+
+#[derive(Debug, Clone, PartialEq)]
+#[proto_rw]
+struct Login {
+    name: String,
+    #[since(2)]
+    locale: Option<String>,
+    flags_present: bool,
+    #[since(3)]
+    #[when(*flags_present)]
+    flags: u8,
+    #[until(4)]
+    legacy: Var<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[proto_rw(u8)]
+enum Packet {
+    #[id(0x01)]
+    Ping,
+    #[id(0x02, until = 3)]
+    #[id(0x12, since = 3)]
+    Chat(String),
+    #[id(0x03)]
+    #[since(2)]
+    Ack,
+    #[id(0x04)]
+    #[until(2)]
+    Legacy,
+}
+
+fn write<T: ProtoRw>(value: &T, version: u32) -> Vec<u8> {
+    let mut buf = BytesMut::new();
+    value
+        .write_proto_ctx(&mut buf, &Context::new(version))
+        .unwrap();
+    buf.to_vec()
+}
+
+fn read<T: ProtoRw>(data: &[u8], version: u32) -> T {
+    let mut buf = Bytes::copy_from_slice(data);
+    let value = T::read_proto_ctx(&mut buf, &Context::new(version)).unwrap();
+    assert!(buf.is_empty());
+    value
+}
+
+#[test]
+fn versioned_fields() {
+    let v1 = Login {
+        name: "a".to_string(),
+        locale: None,
+        flags: 0,
+        flags_present: true,
+        legacy: 7,
+    };
+    assert_eq!(write(&v1, 1), vec![1, b'a', 1, 7]);
+    assert_eq!(read::<Login>(&[1, b'a', 1, 7], 1), v1);
+
+    let v3 = Login {
+        name: "a".to_string(),
+        locale: Some("en".to_string()),
+        flags: 5,
+        flags_present: true,
+        legacy: 7,
+    };
+    assert_eq!(write(&v3, 3), vec![1, b'a', 2, b'e', b'n', 1, 5, 7]);
+    assert_eq!(read::<Login>(&[1, b'a', 2, b'e', b'n', 1, 5, 7], 3), v3);
+
+    // The default context is the latest version
+    let latest = Login {
+        legacy: 0,
+        ..v3.clone()
+    };
+    let mut buf = BytesMut::new();
+    latest.write_proto(&mut buf).unwrap();
+    assert_eq!(&buf[..], &[1, b'a', 2, b'e', b'n', 1, 5]);
+    assert_eq!(Login::read_proto(&mut buf.freeze()).unwrap(), latest);
+
+    // #[until] fields are left out by the default context
+    assert_eq!(
+        Login::read_proto(&mut Bytes::from_static(&[1, b'a', 2, b'e', b'n', 1, 5, 7])).unwrap(),
+        latest
+    );
+    let err = v3.write_proto(&mut BytesMut::new()).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Field legacy is not the default value but its condition is false"
+    );
+
+    // Options have to match the version
+    let mut buf = BytesMut::new();
+    let err = v1.write_proto_ctx(&mut buf, &Context::new(2)).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Field locale is None but its condition is true"
+    );
+}
+
+#[test]
+fn versioned_variants() {
+    assert_eq!(
+        write(&Packet::Chat("x".to_string()), 2),
+        vec![0x02, 1, b'x']
+    );
+    assert_eq!(
+        write(&Packet::Chat("x".to_string()), 3),
+        vec![0x12, 1, b'x']
+    );
+    assert_eq!(
+        read::<Packet>(&[0x02, 1, b'x'], 1),
+        Packet::Chat("x".to_string())
+    );
+    assert_eq!(
+        read::<Packet>(&[0x12, 1, b'x'], 5),
+        Packet::Chat("x".to_string())
+    );
+    assert_eq!(read::<Packet>(&[0x03], 2), Packet::Ack);
+    assert_eq!(read::<Packet>(&[0x04], 1), Packet::Legacy);
+    assert_eq!(write(&Packet::Ping, 1), vec![0x01]);
+
+    let ctx = Context::new(3);
+    let err = Packet::read_proto_ctx(&mut Bytes::from_static(&[0x02, 1, b'x']), &ctx).unwrap_err();
+    assert_eq!(err.to_string(), "Get 2 while reading Packet");
+
+    let mut buf = BytesMut::new();
+    let err = Packet::Ack
+        .write_proto_ctx(&mut buf, &Context::new(1))
+        .unwrap_err();
+    assert_eq!(err.to_string(), "Packet::Ack has no id in version 1");
+    assert!(buf.is_empty());
+}
@@ -0,0 +1,159 @@
+use macros::proto_rw;
+use proto_rw::{
+    types::{Var, BE, LE},
+    ProtoRw, ProtoSchema,
+};
+
+#[proto_rw]
+struct Position {
+    x: BE<i32>,
+    y: LE<f32>,
+}
+
+#[proto_rw]
+struct Move {
+    entity: Var<u32>,
+    #[length(Var<u32>)]
+    path: Vec<Position>,
+    name: String,
+    #[when(*entity > 0)]
+    #[since(2)]
+    speed: Option<Var<i32>>,
+}
+
+#[proto_rw(Var<i32>)]
+enum Packet {
+    Ping = 0,
+    #[id(1 | 2, write = 1)]
+    Move(Move),
+}
+
+#[proto_rw]
+struct Counters {
+    total: Var<u64>,
+    delta: Var<i64>,
+    stamp: BE<u64>,
+}
+
+#[test]
+fn lua_fields() {
+    let lua = proto_rw_export::wireshark::to_lua("Game", &Position::schema(), &[]);
+
+    assert!(lua.starts_with(
+        "-- Generated by proto-rw-export. Do not edit.
+local proto = Proto(\"game\", \"Game protocol\")"
+    ));
+    assert!(lua.contains(
+        "f.position_x = ProtoField.int32(\"game.position.x\", \"x\", base.DEC)
+f.position_y = ProtoField.float(\"game.position.y\", \"y\", base.DEC)
+proto.fields = f"
+    ));
+    assert!(lua.contains(
+        "dissect.position = function(tvb, pinfo, offset, tree)
+  local start = offset
+  local v = {}
+  local subtree = tree:add(tvb(offset, 0), \"Position\")
+  do
+    local size = 4
+    local value = tvb(offset, size):int()
+    subtree:add(f.position_x, tvb(offset, size), value)
+    v.x = value
+    offset = offset + size
+  end
+  do
+    local size = 4
+    local value = tvb(offset, size):le_float()
+"
+    ));
+    assert!(lua.contains("dissect.position(tvb, pinfo, 0, subtree)"));
+    assert!(lua.ends_with("DissectorTable.get(\"udp.port\"):add_for_decode_as(proto)\n"));
+}
+
+#[test]
+fn lua_packet_ids() {
+    let schemas = [Packet::schema(), Move::schema(), Position::schema()];
+    let lua = proto_rw_export::wireshark::to_lua("Game", &schemas[0], &schemas);
+
+    // The packet id is a zigzag varint, dispatching to the variants
+    assert!(lua.contains(
+        "    local value, size = read_varint(tvb, offset)
+    value = zigzag(value)
+    subtree:add(f.packet_tag, tvb(offset, size), value)
+    v.tag = value"
+    ));
+    assert!(lua.contains(
+        "dissect.packet_body = function(tvb, pinfo, offset, tree, tag)
+  if tag == 0 then
+    tree:append_text(\": Ping\")
+    pinfo.cols.info:append(\" Ping\")
+  elseif (tag == 1 or tag == 2) then
+    tree:append_text(\": Move\")
+    pinfo.cols.info:append(\" Move\")
+    local start = offset
+    local v = {}
+    offset = dissect.move(tvb, pinfo, offset, tree)
+  else
+    tree:add_expert_info(PI_MALFORMED, PI_ERROR, \"Unknown id \" .. tostring(tag))
+    return tvb:len()
+  end
+  return offset
+end"
+    ));
+
+    // Lists nest a subtree, and conditions read earlier fields
+    assert!(lua.contains("local list = subtree:add(tvb(offset, 0), \"path\")"));
+    assert!(lua.contains("offset = dissect.position(tvb, pinfo, offset, list)"));
+    assert!(lua.contains("if proto.prefs.version >= 2 and (v.entity > 0) then"));
+    assert!(lua.contains("subtree:add(f.move_name, tvb(offset, len))"));
+
+    // The latest version writes the speed, like the dissector reads it by default
+    let mut buf = bytes::BytesMut::new();
+    Packet::Move(Move {
+        entity: 1,
+        path: vec![],
+        name: String::new(),
+        speed: Some(-1),
+    })
+    .write_proto(&mut buf)
+    .unwrap();
+    assert_eq!(&buf[..], b"\x02\x01\x00\x00\x01");
+}
+
+#[test]
+fn lua_wide_varints() {
+    let lua = proto_rw_export::wireshark::to_lua("Game", &Counters::schema(), &[]);
+    assert!(lua.contains("ProtoField.uint64(\"game.counters.total\""));
+    assert!(lua.contains("ProtoField.int64(\"game.counters.delta\""));
+
+    // 2^53 + 1 takes 8 bytes, past the 7 bytes summed exactly in a double
+    let mut buf = bytes::BytesMut::new();
+    Counters {
+        total: (1 << 53) + 1,
+        delta: -(1 << 53) - 1,
+        stamp: (1 << 53) + 1,
+    }
+    .write_proto(&mut buf)
+    .unwrap();
+    assert_eq!(buf.len(), 24);
+    assert!(lua.contains(
+        "    if size < 7 then
+      value = value + (byte % 128) * scale
+      scale = scale * 128
+    else
+      value = UInt64(value) + UInt64(byte % 128):lshift(7 * size)
+    end"
+    ));
+    assert!(lua.contains(
+        "  if type(value) ~= \"number\" then
+    local half = Int64(value:rshift(1))"
+    ));
+
+    // Fixed-width values are shown exactly, and only rounded for expressions
+    assert!(lua.contains(
+        "    local size = 8
+    local value = tvb(offset, size):uint64()
+    subtree:add(f.counters_stamp, tvb(offset, size), value)
+    v.stamp = to_number(value)"
+    ));
+    assert!(lua.contains("v.total = to_number(value)"));
+}